@@ -3,12 +3,25 @@ use itertools::Itertools;
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Ciphertext {
     pub(crate) c: Vec<Poly>,
     pub(crate) poly_type: PolyType,
     pub(crate) seed: Option<<ChaCha8Rng as SeedableRng>::Seed>,
     pub(crate) level: usize,
+    /// Heuristic upper bound on bits of noise in the ciphertext. `None` if unknown (for ex,
+    /// ciphertext was deserialized or constructed from raw polynomials).
+    pub(crate) noise_estimate: Option<usize>,
+}
+
+// `noise_estimate` is book-keeping for the evaluator and not part of the ciphertext itself
+impl PartialEq for Ciphertext {
+    fn eq(&self, other: &Self) -> bool {
+        self.c == other.c
+            && self.poly_type == other.poly_type
+            && self.seed == other.seed
+            && self.level == other.level
+    }
 }
 
 impl Ciphertext {
@@ -18,6 +31,7 @@ impl Ciphertext {
             poly_type,
             level,
             seed: None,
+            noise_estimate: None,
         }
     }
 
@@ -27,10 +41,14 @@ impl Ciphertext {
             poly_type: PolyType::Q,
             level: 0,
             seed: None,
+            noise_estimate: None,
         }
     }
 
+    /// Returns mutable reference to ciphertext polynomials. Since polynomials may be modified,
+    /// ciphertext is no longer considered seeded.
     pub fn c_ref_mut(&mut self) -> &mut [Poly] {
+        self.seed = None;
         &mut self.c
     }
 
@@ -45,6 +63,16 @@ impl Ciphertext {
     pub fn level(&self) -> usize {
         self.level
     }
+
+    pub fn noise_estimate(&self) -> Option<usize> {
+        self.noise_estimate
+    }
+
+    /// Returns true if `c1` is derived from seed. Seeded ciphertexts are serialized as `c0`
+    /// and the seed, ie at half the size.
+    pub fn is_seeded(&self) -> bool {
+        self.seed.is_some()
+    }
 }
 
 mod tests {
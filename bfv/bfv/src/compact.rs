@@ -0,0 +1,272 @@
+use crate::proto::proto::{CompactCiphertext as CompactCiphertextProto, Poly as PolyProto};
+use crate::{
+    convert_from_bytes, convert_to_bytes, Ciphertext, Evaluator, Poly, PolyType, Representation,
+};
+use itertools::Itertools;
+use ndarray::Array2;
+use prost::Message;
+use traits::TryFromWithPolyContext;
+
+impl Evaluator {
+    /// Returns lowest level (ie level with fewest moduli) ciphertext can be switched down to such that it
+    /// still decrypts correctly. Returns ciphertext's level if its noise estimate is unknown.
+    pub fn lowest_decryptable_level(&self, ct: &Ciphertext) -> usize {
+        let mut level = ct.level;
+        if let Some(mut noise) = ct.noise_estimate {
+            while level < self.params.max_level {
+                let noise_next = self.params.mod_down_noise_bits(noise, level);
+                if self.params.noise_budget_bits(level + 1, noise_next) <= 0 {
+                    break;
+                }
+                noise = noise_next;
+                level += 1;
+            }
+        }
+        level
+    }
+
+    /// Returns no. of low order bits of c1 that can be dropped from ciphertext at last level
+    /// with `noise` bits of noise.
+    ///
+    /// Rounding c1 to multiple of 2^d adds noise of at most hw * 2^(d-1), since c1 is multiplied
+    /// by the secret key during decryption.
+    fn c1_droppable_bits(&self, noise: usize) -> u32 {
+        let level = self.params.max_level;
+        let q_bits = 64 - self.params.ciphertext_moduli[0].leading_zeros();
+        let hw_bits = self.params.hw.ilog2() as usize;
+
+        // keep at least 8 bits, as required by `convert_to_bytes`
+        (0..=q_bits.saturating_sub(8))
+            .take_while(|d| {
+                let noise = std::cmp::max(noise, *d as usize + hw_bits + 1) + 1;
+                self.params.noise_budget_bits(level, noise) > 0
+            })
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// Exports ciphertext in compact wire format meant for delivery to the party that decrypts it.
+    ///
+    /// Ciphertext is switched down to `Evaluator::lowest_decryptable_level` and its coefficients are
+    /// packed with bit-width of each modulus. If `compress_c1` is set and ciphertext reaches the last level,
+    /// low order bits of c1 are dropped as well (similar to ciphertext compression in Kyber).
+    ///
+    /// Exported ciphertext does not support any further operations except decryption.
+    pub fn export_compact(&self, ct: &Ciphertext, compress_c1: bool) -> Vec<u8> {
+        assert!(ct.c.len() == 2);
+        assert!(ct.poly_type == PolyType::Q);
+
+        let level = self.lowest_decryptable_level(ct);
+        let mut ct = ct.clone();
+        self.ciphertext_change_representation(&mut ct, Representation::Coefficient);
+        self.mod_down_level(&mut ct, level);
+
+        let ctx = self.params.poly_ctx(&PolyType::Q, level);
+        let c0 = PolyProto::try_from_with_context(&ct.c[0], &ctx);
+
+        let dropped_bits = match ct.noise_estimate {
+            Some(noise) if compress_c1 && level == self.params.max_level => {
+                self.c1_droppable_bits(noise)
+            }
+            _ => 0,
+        };
+        let c1 = if dropped_bits == 0 {
+            PolyProto::try_from_with_context(&ct.c[1], &ctx)
+        } else {
+            // Only one modulus remains at last level, thus rounding in RNS is same as rounding the
+            // coefficient itself
+            let q = ctx.moduli_ops()[0].modulus();
+            let half = 1u64 << (dropped_bits - 1);
+            let values = ct.c[1]
+                .coefficients
+                .iter()
+                .map(|v| {
+                    let rounded = (v + half) >> dropped_bits;
+                    // rounded value that equals q wraps around to 0
+                    rounded * ((rounded << dropped_bits) < q) as u64
+                })
+                .collect_vec();
+            PolyProto {
+                coefficients: vec![convert_to_bytes(&values, q >> dropped_bits)],
+            }
+        };
+
+        CompactCiphertextProto {
+            level: level as u32,
+            c1_dropped_bits: dropped_bits,
+            c0: Some(c0),
+            c1: Some(c1),
+        }
+        .encode_to_vec()
+    }
+
+    /// Imports ciphertext exported with `Evaluator::export_compact`.
+    ///
+    /// Panics if bytes are malformed.
+    pub fn import_compact(&self, bytes: &[u8]) -> Ciphertext {
+        let value = CompactCiphertextProto::decode(bytes).expect("Malformed compact ciphertext");
+        let level = value.level as usize;
+        let dropped_bits = value.c1_dropped_bits;
+        assert!(level <= self.params.max_level);
+
+        let ctx = self.params.poly_ctx(&PolyType::Q, level);
+        let c0 = Poly::try_from_with_context(value.c0.as_ref().expect("c0 missing"), &ctx);
+        let c1_proto = value.c1.as_ref().expect("c1 missing");
+        let c1 = if dropped_bits == 0 {
+            Poly::try_from_with_context(c1_proto, &ctx)
+        } else {
+            assert!(level == self.params.max_level && c1_proto.coefficients.len() == 1);
+            let q = ctx.moduli_ops()[0].modulus();
+            // export keeps at least 8 bits of c1. Check explicitly since shifts below overflow
+            // silently in release builds.
+            let q_bits = 64 - q.leading_zeros();
+            assert!(
+                dropped_bits + 8 <= q_bits,
+                "Invalid no. of dropped c1 bits {}",
+                dropped_bits
+            );
+            let values = convert_from_bytes(&c1_proto.coefficients[0], q >> dropped_bits)
+                .into_iter()
+                .take(self.params.degree)
+                .map(|v| v << dropped_bits)
+                .collect_vec();
+            assert!(values.len() == self.params.degree);
+            Poly::new(
+                Array2::from_shape_vec((1, self.params.degree), values).unwrap(),
+                Representation::Coefficient,
+            )
+        };
+
+        Ciphertext::new(vec![c0, c1], PolyType::Q, level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::proto::Ciphertext as CiphertextProto;
+    use crate::{BfvParameters, Encoding, EvaluationKey, SecretKey};
+    use rand::thread_rng;
+    use traits::TryFromWithParameters;
+
+    #[test]
+    fn compact_ciphertext_round_trip() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::new(&[60; 10], 65537, 1 << 4);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let m1 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let mut m_sum = m0.clone();
+        params.plaintext_modulus_op.add_mod_fast_vec(&mut m_sum, &m1);
+
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.add(
+            &evaluator.encrypt(
+                &sk,
+                &evaluator.plaintext_encode(&m0, Encoding::default()),
+                &mut rng,
+            ),
+            &evaluator.encrypt(
+                &sk,
+                &evaluator.plaintext_encode(&m1, Encoding::default()),
+                &mut rng,
+            ),
+        );
+
+        let full_size = CiphertextProto::try_from_with_parameters(&ct, evaluator.params())
+            .encode_to_vec()
+            .len();
+
+        let compact = evaluator.export_compact(&ct, false);
+        let compressed = evaluator.export_compact(&ct, true);
+        assert!(compact.len() * 5 < full_size);
+        assert!(compressed.len() < compact.len());
+
+        for bytes in [compact, compressed] {
+            let ct_back = evaluator.import_compact(&bytes);
+            assert_eq!(ct_back.level(), evaluator.params().max_level);
+            assert_eq!(
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_back), Encoding::default()),
+                m_sum
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid no. of dropped c1 bits")]
+    fn import_compact_rejects_too_many_dropped_bits() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::new(&[60; 3], 65537, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        let mut value =
+            CompactCiphertextProto::decode(evaluator.export_compact(&ct, true).as_slice()).unwrap();
+        assert!(value.c1_dropped_bits > 0);
+        value.c1_dropped_bits = 64;
+        evaluator.import_compact(&value.encode_to_vec());
+    }
+
+    #[test]
+    fn compact_ciphertext_keeps_noisy_ciphertext_at_higher_level() {
+        let mut rng = thread_rng();
+        let mut params = BfvParameters::new(&[60; 5], 65537, 1 << 4);
+        params.enable_hybrid_key_switching(&[60, 60, 60]);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+        let mut m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let mut ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+
+        // Switching down preserves noise budget, thus ciphertext only stays at a higher level if its
+        // noise is within a few bits of exhausting the budget. Squaring adds too much noise to land in
+        // that window, hence square while the ciphertext still reaches the last level and then
+        // double it, which adds 1 bit of noise each time.
+        let max_level = evaluator.params().max_level;
+        let t = &evaluator.params().plaintext_modulus_op;
+        loop {
+            let squared = evaluator.relinearize(&evaluator.mul(&ct, &ct), &ek);
+            if evaluator.lowest_decryptable_level(&squared) != max_level {
+                break;
+            }
+            ct = squared;
+            m.iter_mut().for_each(|v| *v = t.mul_mod_fast(*v, *v));
+        }
+        while evaluator.lowest_decryptable_level(&ct) == max_level {
+            ct = evaluator.add(&ct, &ct);
+            m.iter_mut().for_each(|v| *v = t.add_mod_fast(*v, *v));
+        }
+
+        let level = evaluator.lowest_decryptable_level(&ct);
+        assert!(level > 0 && level < evaluator.params().max_level);
+
+        // c1 bits are dropped only at the last level
+        let ct_back = evaluator.import_compact(&evaluator.export_compact(&ct, true));
+        assert_eq!(ct_back.level(), level);
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_back), Encoding::default()),
+            m
+        );
+    }
+}
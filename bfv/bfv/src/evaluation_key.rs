@@ -1,4 +1,7 @@
-use crate::{rot_to_galois_element, BfvParameters, GaloisKey, RelinearizationKey, SecretKey};
+use crate::{
+    rot_to_galois_element, BfvParameters, GaloisKey, KeySwitchingStrategy, RelinearizationKey,
+    SecretKey,
+};
 use itertools::{izip, Itertools};
 use rand::{CryptoRng, RngCore};
 use std::collections::HashMap;
@@ -6,7 +9,41 @@ use std::collections::HashMap;
 #[derive(Debug, PartialEq)]
 pub struct EvaluationKey {
     pub(crate) rlks: HashMap<usize, RelinearizationKey>,
-    pub(crate) rtgs: HashMap<(isize, usize), GaloisKey>,
+    /// Galois keys by galois exponent and level
+    pub(crate) rtgs: HashMap<(usize, usize), GaloisKey>,
+}
+
+/// Returns galois exponent of rotation by `rot_by`. `2 * degree - 1` swaps rows.
+pub(crate) fn rot_to_exponent(rot_by: isize, degree: usize) -> usize {
+    if rot_by == (2 * degree - 1) as isize {
+        2 * degree - 1
+    } else {
+        rot_to_galois_element(rot_by, degree)
+    }
+}
+
+/// Returns rotation index of galois `exponent`, ie inverse of `rot_to_exponent`. Rotations by `i`
+/// and `i - degree / 2` have same exponent, the index of smaller magnitude is returned. Returns
+/// `None` if `exponent` is not a rotation (or row swap).
+pub(crate) fn exponent_to_rot(exponent: usize, degree: usize) -> Option<isize> {
+    let exponent = exponent % (2 * degree);
+    if exponent == 2 * degree - 1 {
+        return Some(exponent as isize);
+    }
+
+    let row = degree / 2;
+    let mut g = 1;
+    for i in 0..row {
+        if g == exponent {
+            return Some(if i > row / 2 {
+                i as isize - row as isize
+            } else {
+                i as isize
+            });
+        }
+        g = (g * 3) % (2 * degree);
+    }
+    None
 }
 
 impl EvaluationKey {
@@ -17,34 +54,120 @@ impl EvaluationKey {
         rtg_levels: &[usize],
         rtg_indices: &[isize],
         rng: &mut R,
+    ) -> EvaluationKey {
+        EvaluationKey::new_with_strategy(
+            params,
+            sk,
+            rlk_levels,
+            rtg_levels,
+            rtg_indices,
+            KeySwitchingStrategy::default(),
+            rng,
+        )
+    }
+
+    /// Same as `new` but relinearization and galois keys use `strategy` for key switching. Parameters
+    /// need not have special moduli when `strategy` is `KeySwitchingStrategy::Bv`.
+    pub fn new_with_strategy<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rlk_levels: &[usize],
+        rtg_levels: &[usize],
+        rtg_indices: &[isize],
+        strategy: KeySwitchingStrategy,
+        rng: &mut R,
     ) -> EvaluationKey {
         assert!(rtg_levels.len() == rtg_indices.len());
 
         let mut rlks = HashMap::new();
         rlk_levels.iter().for_each(|l| {
-            rlks.insert(*l, RelinearizationKey::new(params, sk, *l, rng));
+            rlks.insert(
+                *l,
+                RelinearizationKey::new_with_strategy(params, sk, *l, strategy, rng),
+            );
         });
 
         let mut rtgs = HashMap::new();
         izip!(rtg_indices.iter(), rtg_levels.iter()).for_each(|(index, level)| {
-            let el = {
-                if *index == (2 * params.degree - 1) as isize {
-                    2 * params.degree - 1
-                } else {
-                    rot_to_galois_element(*index, params.degree)
-                }
-            };
+            let el = rot_to_exponent(*index, params.degree);
             rtgs.insert(
-                (*index, *level),
-                GaloisKey::new(el, params, *level, sk, rng),
+                (el, *level),
+                GaloisKey::new_with_strategy(el, params, *level, sk, strategy, rng),
             );
         });
 
         EvaluationKey { rlks, rtgs }
     }
 
+    /// Same as `new_with_strategy` but galois keys are generated for raw galois `exponents` at
+    /// `galois_levels` instead of rotation indices. Exponents must be odd. Besides rotations, galois
+    /// keys for arbitrary exponents enable automorphisms like frobenius maps (`exponent = t^i`) and
+    /// conjugation (`exponent = 2 * degree - 1`).
+    pub fn new_with_galois_exponents<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rlk_levels: &[usize],
+        galois_levels: &[usize],
+        exponents: &[usize],
+        strategy: KeySwitchingStrategy,
+        rng: &mut R,
+    ) -> EvaluationKey {
+        assert!(galois_levels.len() == exponents.len());
+
+        let mut ek =
+            EvaluationKey::new_with_strategy(params, sk, rlk_levels, &[], &[], strategy, rng);
+        izip!(exponents.iter(), galois_levels.iter()).for_each(|(exponent, level)| {
+            ek.insert_galois_key(GaloisKey::new_with_strategy(
+                *exponent, params, *level, sk, strategy, rng,
+            ));
+        });
+        ek
+    }
+
+    /// Adds relinearization key, replacing the existing key at its level if any. Use it to add
+    /// relinearization key of degree > 2 (see `RelinearizationKey::new_with_degree`).
+    pub fn insert_rlk(&mut self, rlk: RelinearizationKey) {
+        self.rlks.insert(rlk.level, rlk);
+    }
+
+    /// Returns relinearization key usable for ciphertexts of `degree` (ie with `degree + 1`
+    /// polynomials) at `level`, that is the key of sufficient degree at `level` or if it's missing
+    /// the one at the closest lower level. Returns `None` if there's no such key.
+    ///
+    /// Key generated at a lower level is used at higher levels by dropping limbs of its polynomials,
+    /// hence a single key at level 0 suffices for all levels.
+    pub fn get_rlk(&self, level: usize, degree: usize) -> Option<&RelinearizationKey> {
+        (0..=level)
+            .rev()
+            .filter_map(|l| self.rlks.get(&l))
+            .find(|rlk| rlk.degree() >= degree)
+    }
+
+    /// Adds galois key, replacing the existing key for its exponent at its level if any.
+    pub fn insert_galois_key(&mut self, gk: GaloisKey) {
+        self.rtgs.insert((gk.exponent(), gk.level), gk);
+    }
+
+    /// Same as `get_rlk` but returns galois key for galois `exponent`
+    pub fn get_galois_key(&self, exponent: usize, level: usize) -> Option<&GaloisKey> {
+        let exponent = exponent % (2 * self.degree()?);
+        (0..=level)
+            .rev()
+            .find_map(|l| self.rtgs.get(&(exponent, l)))
+    }
+
+    /// Same as `get_rlk` but returns galois key for rotation by `rot_by`
+    pub fn get_rtg(&self, rot_by: isize, level: usize) -> Option<&GaloisKey> {
+        self.get_galois_key(rot_to_exponent(rot_by, self.degree()?), level)
+    }
+
     pub fn get_rtg_ref(&self, rot_by: isize, level: usize) -> &GaloisKey {
-        self.rtgs.get(&(rot_by, level)).expect("Rtg missing!")
+        self.get_rtg(rot_by, level).expect("Rtg missing!")
+    }
+
+    /// Returns ring degree of galois keys, or `None` if there are none
+    fn degree(&self) -> Option<usize> {
+        self.rtgs.values().next().map(|gk| gk.substitution.degree)
     }
 }
 
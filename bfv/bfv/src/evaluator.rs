@@ -1,24 +1,170 @@
-use crate::relinearization_key::RelinearizationKey;
-use crate::{BfvParameters, Ciphertext, EvaluationKey, PolyType};
-use crate::{Encoding, GaloisKey, Plaintext, SecretKey};
-use crate::{Poly, Representation};
+use crate::evaluation_key::rot_to_exponent;
+use crate::{BfvParameters, Ciphertext, EvaluationKey, PolyType, ScratchArena};
+use crate::{Encoding, GaloisKey, KeySwitchingKey, Plaintext, PublicKey, SecretKey};
+use crate::{utils::join, Poly, PolyContext, Representation};
 use itertools::{izip, Itertools};
 use num_bigint::{BigUint, RandBigInt};
 use rand::{thread_rng, CryptoRng, Rng, RngCore};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cmp::max;
 
 pub struct Evaluator {
     pub(crate) params: BfvParameters,
+    pub(crate) auto_mod_switch: bool,
+    pub(crate) scratch: ScratchArena,
 }
 
 impl Evaluator {
     pub fn new(params: BfvParameters) -> Evaluator {
-        Evaluator { params }
+        Evaluator {
+            params,
+            auto_mod_switch: false,
+            scratch: ScratchArena::default(),
+        }
     }
 
     pub fn params(&self) -> &BfvParameters {
         &self.params
     }
 
+    /// Returns arena of scratch buffers used by the evaluator.
+    ///
+    /// Operations take their intermediate polynomials from the arena and return them once done.
+    /// `*_into` and `*_assign` variants of operations additionally reuse polynomials of the output
+    /// ciphertext. Hence, in a loop that repeatedly writes to same output ciphertexts no memory is
+    /// allocated after the first iteration.
+    pub fn scratch_arena(&self) -> &ScratchArena {
+        &self.scratch
+    }
+
+    /// Returns polynomials of the ciphertext to the scratch arena, so that they are reused by
+    /// subsequent operations instead of allocating new ones.
+    pub fn recycle(&self, c0: Ciphertext) {
+        self.scratch.give_ciphertext(c0);
+    }
+
+    /// Enables automatic modulus switching.
+    ///
+    /// Evaluator keeps track of an estimate of noise in every ciphertext (see `Ciphertext::noise_estimate`).
+    /// With automatic modulus switching enabled, before every relinearization the ciphertext is switched
+    /// down to the lowest level at which its noise, after division by dropped moduli, stays above rounding
+    /// noise of `mod_down_next`. In other words, levels are dropped as long as dropping them does not eat
+    /// into the noise budget. Ciphertexts at different levels passed to `mul`, `mul_lazy`, `add`, and `sub`
+    /// (and their assign variants) are brought to the same level by switching down the one at higher level.
+    ///
+    /// Relinearization and galois keys can be used at their level and at any higher level (see `EvaluationKey::get_rlk`).
+    /// Ciphertexts are only switched down to levels for which `EvaluationKey` passed to `relinearize` has a usable
+    /// relinearization key. If no galois key is usable at ciphertext's level, `rotate` switches ciphertext down to next
+    /// level for which the key exists.
+    pub fn enable_auto_mod_switching(&mut self) {
+        self.auto_mod_switch = true;
+    }
+
+    pub fn auto_mod_switching(&self) -> bool {
+        self.auto_mod_switch
+    }
+
+    /// Returns remaining noise budget in bits of the ciphertext as per evaluator's noise estimate.
+    /// Returns `None` if noise estimate of ciphertext is unknown.
+    pub fn noise_budget_estimate(&self, c0: &Ciphertext) -> Option<isize> {
+        c0.noise_estimate
+            .map(|n| self.params.noise_budget_bits(c0.level, n))
+    }
+
+    /// Returns level to which ciphertext can be switched down without losing noise budget. Only
+    /// levels for which `has_key` returns true are considered. If key at ciphertext's level is missing
+    /// returns the next level with key, irrespective of noise. Returns `None` if there's no level
+    /// with key.
+    fn auto_mod_switch_level<F: Fn(usize) -> bool>(
+        &self,
+        c0: &Ciphertext,
+        has_key: F,
+    ) -> Option<usize> {
+        let mut target = None;
+        if has_key(c0.level) {
+            target = Some(c0.level);
+        }
+
+        if let Some(mut noise) = c0.noise_estimate {
+            let floor = self.params.mod_down_noise_floor_bits();
+            let mut level = c0.level;
+            while level < self.params.max_level {
+                let noise_next = self.params.mod_down_noise_bits(noise, level);
+                // stop once rounding noise dominates
+                if noise_next <= floor + 1 {
+                    break;
+                }
+                noise = noise_next;
+                level += 1;
+                if has_key(level) {
+                    target = Some(level);
+                }
+            }
+        }
+
+        if target.is_none() {
+            target = (c0.level + 1..=self.params.max_level).find(|l| has_key(*l));
+        }
+        target
+    }
+
+    /// Returns whether `ek` has relinearization key usable for ciphertexts of `degree` at `level`. Key
+    /// switching is not possible at the last level, since ciphertexts at the last level have a single
+    /// modulus.
+    fn has_rlk(&self, ek: &EvaluationKey, level: usize, degree: usize) -> bool {
+        level < self.params.max_level && ek.get_rlk(level, degree).is_some()
+    }
+
+    /// Same as `has_rlk` but for galois key for rotation by `rotate_by`
+    fn has_rtg(&self, ek: &EvaluationKey, rotate_by: isize, level: usize) -> bool {
+        level < self.params.max_level && ek.get_rtg(rotate_by, level).is_some()
+    }
+
+    /// Same as `has_rlk` but for galois key for galois `exponent`
+    fn has_galois_key(&self, ek: &EvaluationKey, exponent: usize, level: usize) -> bool {
+        level < self.params.max_level && ek.get_galois_key(exponent, level).is_some()
+    }
+
+    /// Brings ciphertexts to same level by switching down the one at higher level. Does nothing
+    /// unless automatic modulus switching is enabled.
+    fn align_levels<'a>(
+        &self,
+        c0: &'a Ciphertext,
+        c1: &'a Ciphertext,
+    ) -> (Cow<'a, Ciphertext>, Cow<'a, Ciphertext>) {
+        if !self.auto_mod_switch || c0.level == c1.level || c0.poly_type != PolyType::Q {
+            return (Cow::Borrowed(c0), Cow::Borrowed(c1));
+        }
+
+        if c0.level < c1.level {
+            let mut c0 = c0.clone();
+            self.mod_down_level(&mut c0, c1.level);
+            (Cow::Owned(c0), Cow::Borrowed(c1))
+        } else {
+            let mut c1 = c1.clone();
+            self.mod_down_level(&mut c1, c0.level);
+            (Cow::Borrowed(c0), Cow::Owned(c1))
+        }
+    }
+
+    /// Same as `align_levels` but switches `c0` down in place.
+    fn align_levels_assign<'a>(&self, c0: &mut Ciphertext, c1: &'a Ciphertext) -> Cow<'a, Ciphertext> {
+        if !self.auto_mod_switch || c0.level == c1.level || c0.poly_type != PolyType::Q {
+            return Cow::Borrowed(c1);
+        }
+
+        if c0.level < c1.level {
+            self.mod_down_level(c0, c1.level);
+            Cow::Borrowed(c1)
+        } else {
+            let mut c1 = c1.clone();
+            self.mod_down_level(&mut c1, c0.level);
+            Cow::Owned(c1)
+        }
+    }
+
     pub fn ciphertext_change_representation(&self, c0: &mut Ciphertext, to: Representation) {
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
         c0.c.iter_mut().for_each(|p| {
@@ -27,13 +173,43 @@ impl Evaluator {
     }
 
     pub fn mul(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Ciphertext {
-        let mut res = self.mul_lazy(lhs, rhs);
-        self.scale_and_round(&mut res)
+        let mut res = Ciphertext::placeholder();
+        self.mul_into(lhs, rhs, &mut res);
+        res
+    }
+
+    /// Same as `mul` but writes the result to `out`, reusing its polynomials. Intermediate degree 2
+    /// ciphertext is taken from and returned to the scratch arena.
+    pub fn mul_into(&self, lhs: &Ciphertext, rhs: &Ciphertext, out: &mut Ciphertext) {
+        let mut res = Ciphertext::placeholder();
+        self.mul_lazy_into(lhs, rhs, &mut res);
+        self.scale_and_round_into(&mut res, out);
+        self.recycle(res);
     }
 
     pub fn mul_lazy(&self, lhs: &Ciphertext, rhs: &Ciphertext) -> Ciphertext {
-        assert!(lhs.c.len() == 2);
-        assert!(rhs.c.len() == 2);
+        let mut res = Ciphertext::placeholder();
+        self.mul_lazy_into(lhs, rhs, &mut res);
+        res
+    }
+
+    /// Same as `mul_lazy` but writes the result to `out`, reusing its polynomials. Dispatches to
+    /// `square_lazy_into` if `lhs` and `rhs` are the same ciphertext.
+    ///
+    /// Operands need not be relinearized. Product of ciphertexts with `k` and `l` polynomials has
+    /// `k + l - 1` polynomials and must be relinearized with a relinearization key of sufficient
+    /// degree (see `RelinearizationKey::new_with_degree`).
+    pub fn mul_lazy_into(&self, lhs: &Ciphertext, rhs: &Ciphertext, out: &mut Ciphertext) {
+        let (lhs, rhs) = self.align_levels(lhs, rhs);
+        let (lhs, rhs) = (lhs.as_ref(), rhs.as_ref());
+
+        if std::ptr::eq(lhs, rhs) {
+            self.square_lazy_into(lhs, out);
+            return;
+        }
+
+        assert!(lhs.c.len() >= 2);
+        assert!(rhs.c.len() >= 2);
         #[cfg(debug_assertions)]
         {
             // We save 2 ntts if polynomial passed to `fast_expand_crt_basis_p_over_q` is in coefficient form. Hence
@@ -52,23 +228,161 @@ impl Evaluator {
         assert!(lhs.poly_type == PolyType::Q);
 
         let level = lhs.level;
-        let q_ctx = self.params.poly_ctx(&PolyType::Q, level);
-        let p_ctx = self.params.poly_ctx(&PolyType::P, level);
         let pq_ctx = self.params.poly_ctx(&PolyType::PQ, level);
 
-        // let mut now = std::time::Instant::now();
-        let mut c00 = q_ctx.expand_crt_basis(
-            &lhs.c[0],
-            &pq_ctx,
-            &p_ctx,
-            &self.params.ql_hat_modpl[level],
-            &self.params.ql_hat_inv_modql[level],
-            &self.params.ql_hat_inv_modql_shoup[level],
-            &self.params.ql_inv[level],
-            &self.params.alphal_modpl[level],
+        // c0 and c1 are independent, thus can be extended in parallel
+        let expand_lhs = |q_poly: &Poly, p: &mut Poly| self.expand_lhs_into(level, q_poly, p);
+        let expand_rhs = |q_poly: &Poly, p: &mut Poly| self.expand_rhs_into(level, q_poly, p);
+
+        if lhs.c.len() != 2 || rhs.c.len() != 2 {
+            self.tensor_into(lhs, rhs, &pq_ctx, expand_lhs, expand_rhs, out);
+            out.poly_type = PolyType::PQ;
+            out.level = level;
+            out.seed = None;
+            // decryption of higher degree ciphertext multiplies its polynomials with higher powers of
+            // secret key, each of which expands noise by roughly hamming weight of secret key
+            let extra_degree = lhs.c.len() + rhs.c.len() - 4;
+            out.noise_estimate = lhs.noise_estimate.zip(rhs.noise_estimate).map(|(a, b)| {
+                self.params.mul_noise_bits(a, b) + extra_degree * self.params.hw.ilog2() as usize
+            });
+            return;
+        }
+
+        // lhs is extended into c1 and c2 of output and rhs into scratch polynomials
+        self.scratch.prepare_ciphertext(out, &pq_ctx, 3);
+        let mut c10 = self.scratch.take_poly(&pq_ctx, Representation::Unknown);
+        let mut c11 = self.scratch.take_poly(&pq_ctx, Representation::Unknown);
+        let (c_r0, c_rest) = out.c.split_at_mut(1);
+        let (c00, c01) = c_rest.split_at_mut(1);
+        let (c_r0, c00, c01) = (&mut c_r0[0], &mut c00[0], &mut c01[0]);
+        join(
+            || join(|| expand_lhs(&lhs.c[0], c00), || expand_lhs(&lhs.c[1], c01)),
+            || {
+                join(
+                    || expand_rhs(&rhs.c[0], &mut c10),
+                    || expand_rhs(&rhs.c[1], &mut c11),
+                )
+            },
         );
-        let mut c01 = q_ctx.expand_crt_basis(
-            &lhs.c[1],
+
+        // tensor
+        // c00 * c10
+        pq_ctx.mul_into(c00, &c10, c_r0);
+
+        // c00 * c11 + c01 * c10
+        pq_ctx.mul_assign(c00, &c11);
+        pq_ctx.mul_assign(&mut c10, c01);
+        pq_ctx.add_assign(c00, &c10);
+
+        // c01 * c11
+        pq_ctx.mul_assign(c01, &c11);
+
+        self.scratch.give(c10);
+        self.scratch.give(c11);
+
+        out.poly_type = PolyType::PQ;
+        out.level = level;
+        out.seed = None;
+        out.noise_estimate = lhs
+            .noise_estimate
+            .zip(rhs.noise_estimate)
+            .map(|(a, b)| self.params.mul_noise_bits(a, b));
+    }
+
+    pub fn square(&self, c0: &Ciphertext) -> Ciphertext {
+        let mut res = Ciphertext::placeholder();
+        self.square_into(c0, &mut res);
+        res
+    }
+
+    /// Same as `square` but writes the result to `out`, reusing its polynomials.
+    pub fn square_into(&self, c0: &Ciphertext, out: &mut Ciphertext) {
+        let mut res = Ciphertext::placeholder();
+        self.square_lazy_into(c0, &mut res);
+        self.scale_and_round_into(&mut res, out);
+        self.recycle(res);
+    }
+
+    pub fn square_lazy(&self, c0: &Ciphertext) -> Ciphertext {
+        let mut res = Ciphertext::placeholder();
+        self.square_lazy_into(c0, &mut res);
+        res
+    }
+
+    /// Same as `mul_lazy_into(c0, c0, out)` but cheaper. Degree 2 ciphertext
+    /// `(c0^2, 2 * c0 * c1, c1^2)` is computed with 3 products in PQ instead of 4.
+    ///
+    /// Basis extensions are not saved. Left operand of the product is extended exactly and right
+    /// operand scaled by P/Q, thus both extensions of `c0` and `c1` (4 in total) are needed as in
+    /// `mul_lazy_into`.
+    pub fn square_lazy_into(&self, c0: &Ciphertext, out: &mut Ciphertext) {
+        assert!(c0.c.len() >= 2);
+        assert!(c0.poly_type == PolyType::Q);
+
+        let level = c0.level;
+        let pq_ctx = self.params.poly_ctx(&PolyType::PQ, level);
+
+        let expand_lhs = |q_poly: &Poly, p: &mut Poly| self.expand_lhs_into(level, q_poly, p);
+        let expand_rhs = |q_poly: &Poly, p: &mut Poly| self.expand_rhs_into(level, q_poly, p);
+
+        if c0.c.len() != 2 {
+            self.tensor_into(c0, c0, &pq_ctx, expand_lhs, expand_rhs, out);
+            out.poly_type = PolyType::PQ;
+            out.level = level;
+            out.seed = None;
+            let extra_degree = 2 * c0.c.len() - 4;
+            out.noise_estimate = c0.noise_estimate.map(|a| {
+                self.params.mul_noise_bits(a, a) + extra_degree * self.params.hw.ilog2() as usize
+            });
+            return;
+        }
+
+        // c0 is extended into c1 and c2 of output and its P/Q scaled extension into scratch
+        // polynomials
+        self.scratch.prepare_ciphertext(out, &pq_ctx, 3);
+        let mut c10 = self.scratch.take_poly(&pq_ctx, Representation::Unknown);
+        let mut c11 = self.scratch.take_poly(&pq_ctx, Representation::Unknown);
+        let (c_r0, c_rest) = out.c.split_at_mut(1);
+        let (c00, c01) = c_rest.split_at_mut(1);
+        let (c_r0, c00, c01) = (&mut c_r0[0], &mut c00[0], &mut c01[0]);
+        join(
+            || join(|| expand_lhs(&c0.c[0], c00), || expand_lhs(&c0.c[1], c01)),
+            || {
+                join(
+                    || expand_rhs(&c0.c[0], &mut c10),
+                    || expand_rhs(&c0.c[1], &mut c11),
+                )
+            },
+        );
+
+        // c00 * c10
+        pq_ctx.mul_into(c00, &c10, c_r0);
+
+        // 2 * c00 * c11
+        c10.coefficients.assign(&c11.coefficients);
+        pq_ctx.add_assign(&mut c10, &c11);
+        pq_ctx.mul_assign(c00, &c10);
+
+        // c01 * c11
+        pq_ctx.mul_assign(c01, &c11);
+
+        self.scratch.give(c10);
+        self.scratch.give(c11);
+
+        out.poly_type = PolyType::PQ;
+        out.level = level;
+        out.seed = None;
+        out.noise_estimate = c0.noise_estimate.map(|a| self.params.mul_noise_bits(a, a));
+    }
+
+    /// Extends `q_poly` at `level` to PQ basis and changes it to `Evaluation`. Used for left operand
+    /// of multiplication.
+    fn expand_lhs_into(&self, level: usize, q_poly: &Poly, p: &mut Poly) {
+        let q_ctx = self.params.poly_ctx(&PolyType::Q, level);
+        let p_ctx = self.params.poly_ctx(&PolyType::P, level);
+        let pq_ctx = self.params.poly_ctx(&PolyType::PQ, level);
+        q_ctx.expand_crt_basis_into(
+            q_poly,
             &pq_ctx,
             &p_ctx,
             &self.params.ql_hat_modpl[level],
@@ -76,31 +390,21 @@ impl Evaluator {
             &self.params.ql_hat_inv_modql_shoup[level],
             &self.params.ql_inv[level],
             &self.params.alphal_modpl[level],
+            p,
         );
-        // println!("Extend1 {:?}", now.elapsed());
-        if c00.representation != Representation::Evaluation {
-            pq_ctx.change_representation(&mut c00, Representation::Evaluation);
-            pq_ctx.change_representation(&mut c01, Representation::Evaluation);
+        if p.representation != Representation::Evaluation {
+            pq_ctx.change_representation(p, Representation::Evaluation);
         }
-        // println!("Extend1 (In Evaluation) {:?}", now.elapsed());
+    }
 
-        // now = std::time::Instant::now();
-        let mut c10 = q_ctx.fast_expand_crt_basis_p_over_q(
-            &rhs.c[0],
-            &p_ctx,
-            &pq_ctx,
-            &self.params.neg_pql_hat_inv_modql[level],
-            &self.params.neg_pql_hat_inv_modql_shoup[level],
-            &self.params.ql_inv[level],
-            &self.params.ql_inv_modpl[level],
-            &self.params.pl_hat_modql[level],
-            &self.params.pl_hat_inv_modpl[level],
-            &self.params.pl_hat_inv_modpl_shoup[level],
-            &self.params.pl_inv[level],
-            &self.params.alphal_modql[level],
-        );
-        let mut c11 = q_ctx.fast_expand_crt_basis_p_over_q(
-            &rhs.c[1],
+    /// Extends `q_poly` at `level` scaled by P/Q to PQ basis and changes it to `Evaluation`. Used for
+    /// right operand of multiplication.
+    fn expand_rhs_into(&self, level: usize, q_poly: &Poly, p: &mut Poly) {
+        let q_ctx = self.params.poly_ctx(&PolyType::Q, level);
+        let p_ctx = self.params.poly_ctx(&PolyType::P, level);
+        let pq_ctx = self.params.poly_ctx(&PolyType::PQ, level);
+        q_ctx.fast_expand_crt_basis_p_over_q_into(
+            q_poly,
             &p_ctx,
             &pq_ctx,
             &self.params.neg_pql_hat_inv_modql[level],
@@ -112,35 +416,80 @@ impl Evaluator {
             &self.params.pl_hat_inv_modpl_shoup[level],
             &self.params.pl_inv[level],
             &self.params.alphal_modql[level],
+            p,
         );
-        // println!("Extend2 {:?}", now.elapsed());
-        pq_ctx.change_representation(&mut c10, Representation::Evaluation);
-        pq_ctx.change_representation(&mut c11, Representation::Evaluation);
-        // println!("Extend2 (In Evaluation) {:?}", now.elapsed());
-
-        // now = std::time::Instant::now();
-        // tensor
-        // c00 * c10
-        let c_r0 = pq_ctx.mul(&c00, &c10);
+        pq_ctx.change_representation(p, Representation::Evaluation);
+    }
 
-        // c00 * c11 + c01 * c10
-        pq_ctx.mul_assign(&mut c00, &c11);
-        pq_ctx.mul_assign(&mut c10, &c01);
-        pq_ctx.add_assign(&mut c00, &c10);
+    /// Tensors ciphertexts of arbitrary degree, ie `out_k = \sum_{i + j = k} lhs_i * rhs_j`, after
+    /// extending `lhs` with `expand_lhs` and `rhs` with `expand_rhs` to `pq_ctx`.
+    fn tensor_into<L, R>(
+        &self,
+        lhs: &Ciphertext,
+        rhs: &Ciphertext,
+        pq_ctx: &PolyContext<'_>,
+        expand_lhs: L,
+        expand_rhs: R,
+        out: &mut Ciphertext,
+    ) where
+        L: Fn(&Poly, &mut Poly) + Sync,
+        R: Fn(&Poly, &mut Poly) + Sync,
+    {
+        let expand = |c: &[Poly], f: &(dyn Fn(&Poly, &mut Poly) + Sync)| {
+            #[cfg(feature = "parallel")]
+            let c_iter = c.par_iter();
+            #[cfg(not(feature = "parallel"))]
+            let c_iter = c.iter();
+
+            c_iter
+                .map(|q_poly| {
+                    let mut p = self.scratch.take_poly(pq_ctx, Representation::Unknown);
+                    f(q_poly, &mut p);
+                    p
+                })
+                .collect::<Vec<Poly>>()
+        };
+        let (lhs_c, rhs_c) = join(
+            || expand(&lhs.c, &expand_lhs),
+            || expand(&rhs.c, &expand_rhs),
+        );
 
-        // c01 * c11
-        pq_ctx.mul_assign(&mut c01, &c11);
-        // println!("Tensor {:?}", now.elapsed());
+        self.scratch
+            .prepare_ciphertext(out, pq_ctx, lhs_c.len() + rhs_c.len() - 1);
+
+        #[cfg(feature = "parallel")]
+        let out_iter = out.c.par_iter_mut().enumerate();
+        #[cfg(not(feature = "parallel"))]
+        let out_iter = out.c.iter_mut().enumerate();
+
+        out_iter.for_each(|(k, out_k)| {
+            let mut product = self.scratch.take_poly(pq_ctx, Representation::Unknown);
+            let i_range = k.saturating_sub(rhs_c.len() - 1)..=std::cmp::min(k, lhs_c.len() - 1);
+            i_range.enumerate().for_each(|(index, i)| {
+                if index == 0 {
+                    pq_ctx.mul_into(&lhs_c[i], &rhs_c[k - i], out_k);
+                } else {
+                    pq_ctx.mul_into(&lhs_c[i], &rhs_c[k - i], &mut product);
+                    pq_ctx.add_assign(out_k, &product);
+                }
+            });
+            self.scratch.give(product);
+        });
 
-        Ciphertext {
-            c: vec![c_r0, c00, c01],
-            poly_type: PolyType::PQ,
-            level: level,
-            seed: None,
-        }
+        lhs_c
+            .into_iter()
+            .chain(rhs_c)
+            .for_each(|p| self.scratch.give(p));
     }
 
     pub fn scale_and_round(&self, c0: &mut Ciphertext) -> Ciphertext {
+        let mut res = Ciphertext::placeholder();
+        self.scale_and_round_into(c0, &mut res);
+        res
+    }
+
+    /// Same as `scale_and_round` but writes the result to `out`, reusing its polynomials.
+    pub fn scale_and_round_into(&self, c0: &mut Ciphertext, out: &mut Ciphertext) {
         // debug_assert!(c0.c[0].representation == Representation::E)
         assert!(c0.poly_type == PolyType::PQ);
         let level = c0.level;
@@ -148,91 +497,275 @@ impl Evaluator {
         let q_ctx = self.params.poly_ctx(&PolyType::Q, level);
         let p_ctx = self.params.poly_ctx(&PolyType::P, level);
 
-        let c =
-            c0.c.iter_mut()
-                .map(|pq_poly| {
-                    pq_ctx.change_representation(pq_poly, Representation::Coefficient);
-                    pq_ctx.scale_and_round(
-                        pq_poly,
-                        &q_ctx,
-                        &p_ctx,
-                        &q_ctx,
-                        &self.params.tql_pl_hat_inv_modpl_divpl_modql[level],
-                        &self.params.tql_pl_hat_inv_modpl_divpl_frachi[level],
-                        &self.params.tql_pl_hat_inv_modpl_divpl_fraclo[level],
-                    )
-                })
-                .collect_vec();
+        self.scratch.prepare_ciphertext(out, &q_ctx, c0.c.len());
+
+        #[cfg(feature = "parallel")]
+        let c_iter = c0.c.par_iter_mut().zip(out.c.par_iter_mut());
+        #[cfg(not(feature = "parallel"))]
+        let c_iter = c0.c.iter_mut().zip(out.c.iter_mut());
+
+        c_iter.for_each(|(pq_poly, q_poly)| {
+            pq_ctx.change_representation(pq_poly, Representation::Coefficient);
+            pq_ctx.scale_and_round_into(
+                pq_poly,
+                &q_ctx,
+                &p_ctx,
+                &q_ctx,
+                &self.params.tql_pl_hat_inv_modpl_divpl_modql[level],
+                &self.params.tql_pl_hat_inv_modpl_divpl_frachi[level],
+                &self.params.tql_pl_hat_inv_modpl_divpl_fraclo[level],
+                q_poly,
+            )
+        });
 
-        Ciphertext {
-            c,
-            poly_type: PolyType::Q,
-            level,
-            seed: None,
-        }
+        out.poly_type = PolyType::Q;
+        out.level = level;
+        out.seed = None;
+        out.noise_estimate = c0.noise_estimate;
     }
 
     pub fn relinearize(&self, c0: &Ciphertext, ek: &EvaluationKey) -> Ciphertext {
-        ek.rlks
-            .get(&c0.level)
+        let mut res = Ciphertext::placeholder();
+        self.relinearize_into(c0, ek, &mut res);
+        res
+    }
+
+    /// Same as `relinearize` but writes the result to `out`. Previous polynomials of `out` are
+    /// returned to the scratch arena.
+    pub fn relinearize_into(&self, c0: &Ciphertext, ek: &EvaluationKey, out: &mut Ciphertext) {
+        if self.auto_mod_switch {
+            let degree = c0.c.len() - 1;
+            if let Some(level) = self.auto_mod_switch_level(c0, |l| self.has_rlk(ek, l, degree)) {
+                if level != c0.level {
+                    let mut c0 = c0.clone();
+                    self.mod_down_level(&mut c0, level);
+                    return self.relinearize_into(&c0, ek, out);
+                }
+            }
+        }
+
+        ek.get_rlk(c0.level, c0.c.len() - 1)
             .expect("Rlk missing!")
-            .relinearize(&c0, &self.params)
+            .relinearize_into(c0, &self.params, &self.scratch, out)
+    }
+
+    /// Relinearizes ciphertext in place. Polynomials beyond `c1` are returned to the scratch arena.
+    pub fn relinearize_assign(&self, c0: &mut Ciphertext, ek: &EvaluationKey) {
+        if self.auto_mod_switch {
+            let degree = c0.c.len() - 1;
+            if let Some(level) = self.auto_mod_switch_level(c0, |l| self.has_rlk(ek, l, degree)) {
+                self.mod_down_level(c0, level);
+            }
+        }
+
+        ek.get_rlk(c0.level, c0.c.len() - 1)
+            .expect("Rlk missing!")
+            .relinearize_assign(c0, &self.params, &self.scratch)
     }
 
     pub fn rotate(&self, c0: &Ciphertext, rotate_by: isize, ek: &EvaluationKey) -> Ciphertext {
-        ek.rtgs
-            .get(&(rotate_by, c0.level))
-            .expect(&format!("Rtg missing! :{rotate_by} {}", c0.level))
-            .rotate(&c0, &self.params)
+        let mut res = Ciphertext::placeholder();
+        self.rotate_into(c0, rotate_by, ek, &mut res);
+        res
+    }
+
+    /// Same as `rotate` but writes the result to `out`. Previous polynomials of `out` are
+    /// returned to the scratch arena.
+    pub fn rotate_into(
+        &self,
+        c0: &Ciphertext,
+        rotate_by: isize,
+        ek: &EvaluationKey,
+        out: &mut Ciphertext,
+    ) {
+        self.apply_galois_into(c0, rot_to_exponent(rotate_by, self.params.degree), ek, out)
+    }
+
+    /// Rotates ciphertext in place. Previous polynomials of ciphertext are returned to the scratch
+    /// arena.
+    pub fn rotate_assign(&self, c0: &mut Ciphertext, rotate_by: isize, ek: &EvaluationKey) {
+        let mut res = Ciphertext::placeholder();
+        self.rotate_into(c0, rotate_by, ek, &mut res);
+        self.recycle(std::mem::replace(c0, res));
+    }
+
+    /// Applies automorphism `X -> X^exponent` to ciphertext using galois key for `exponent` in `ek`
+    /// (see `EvaluationKey::new_with_galois_exponents`). With SIMD encoding exponent `3^i` rotates
+    /// rows left by `i`, `2 * degree - 1` swaps rows and `t^i` applies frobenius map.
+    pub fn apply_galois(&self, c0: &Ciphertext, exponent: usize, ek: &EvaluationKey) -> Ciphertext {
+        let mut res = Ciphertext::placeholder();
+        self.apply_galois_into(c0, exponent, ek, &mut res);
+        res
+    }
+
+    /// Same as `apply_galois` but writes the result to `out`. Previous polynomials of `out` are
+    /// returned to the scratch arena.
+    pub fn apply_galois_into(
+        &self,
+        c0: &Ciphertext,
+        exponent: usize,
+        ek: &EvaluationKey,
+        out: &mut Ciphertext,
+    ) {
+        if self.auto_mod_switch && !self.has_galois_key(ek, exponent, c0.level) {
+            if let Some(level) = (c0.level + 1..=self.params.max_level)
+                .find(|l| self.has_galois_key(ek, exponent, *l))
+            {
+                let mut c0 = c0.clone();
+                self.mod_down_level(&mut c0, level);
+                return self.apply_galois_into(&c0, exponent, ek, out);
+            }
+        }
+
+        ek.get_galois_key(exponent, c0.level)
+            .expect(&format!("Galois key missing! :{exponent} {}", c0.level))
+            .rotate_into(c0, &self.params, &self.scratch, out)
+    }
+
+    /// Applies automorphism to ciphertext in place. Previous polynomials of ciphertext are
+    /// returned to the scratch arena.
+    pub fn apply_galois_assign(&self, c0: &mut Ciphertext, exponent: usize, ek: &EvaluationKey) {
+        let mut res = Ciphertext::placeholder();
+        self.apply_galois_into(c0, exponent, ek, &mut res);
+        self.recycle(std::mem::replace(c0, res));
+    }
+
+    /// Rotates ciphertext by each of `rotate_by` and returns rotated ciphertexts in the same order.
+    ///
+    /// Cheaper than calling `rotate` for every rotation since `c1` is decomposed for key switching only
+    /// once and the decomposition is shared by all rotations.
+    pub fn rotate_many(
+        &self,
+        c0: &Ciphertext,
+        rotate_by: &[isize],
+        ek: &EvaluationKey,
+    ) -> Vec<Ciphertext> {
+        let has_keys = |l: usize| rotate_by.iter().all(|r| self.has_rtg(ek, *r, l));
+        if self.auto_mod_switch && !has_keys(c0.level) {
+            if let Some(level) = (c0.level + 1..=self.params.max_level).find(|l| has_keys(*l)) {
+                let mut c0 = c0.clone();
+                self.mod_down_level(&mut c0, level);
+                return self.rotate_many(&c0, rotate_by, ek);
+            }
+        }
+
+        let keys = rotate_by
+            .iter()
+            .map(|r| {
+                ek.get_rtg(*r, c0.level)
+                    .expect(&format!("Rtg missing! :{r} {}", c0.level))
+            })
+            .collect_vec();
+        GaloisKey::rotate_hoisted(&keys, c0, &self.params, &self.scratch)
     }
 
+    /// Switches ciphertext to secret key that `ksk` switches to. Ciphertext must be at level
+    /// of `ksk`, unless automatic modulus switching is enabled in which case ciphertext at lower
+    /// level is switched down to level of `ksk`.
+    pub fn switch_key(&self, c0: &Ciphertext, ksk: &KeySwitchingKey) -> Ciphertext {
+        if self.auto_mod_switch && c0.level < ksk.level {
+            let mut c0 = c0.clone();
+            self.mod_down_level(&mut c0, ksk.level);
+            return ksk.switch(&c0, &self.params);
+        }
+
+        ksk.switch(c0, &self.params)
+    }
+
+    /// Adds `c1` to `c0`. Ciphertexts may have different no. of polynomials (ie one of them is not
+    /// relinearized), in which case the sum has as many polynomials as the larger one.
     pub fn add_assign(&self, c0: &mut Ciphertext, c1: &Ciphertext) {
+        let c1 = self.align_levels_assign(c0, c1);
+
         // TODO: perform checks
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
         izip!(c0.c.iter_mut(), c1.c.iter()).for_each(|(p0, p1)| {
             ctx.add_assign(p0, p1);
         });
+        if c1.c.len() > c0.c.len() {
+            let extra = c1.c[c0.c.len()..].to_vec();
+            c0.c.extend(extra);
+        }
         c0.seed = None;
+        c0.noise_estimate = c0
+            .noise_estimate
+            .zip(c1.noise_estimate)
+            .map(|(a, b)| max(a, b) + 1);
     }
 
     pub fn add(&self, c0: &Ciphertext, c1: &Ciphertext) -> Ciphertext {
+        let (c0, c1) = self.align_levels(c0, c1);
+        let (c0, c1) = (c0.as_ref(), c1.as_ref());
+
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
-        let c = izip!(c0.c.iter(), c1.c.iter())
+        let mut c = izip!(c0.c.iter(), c1.c.iter())
             .map(|(p0, p1)| ctx.add(p0, p1))
             .collect_vec();
+        // remaining polynomials of the larger ciphertext
+        let larger = if c0.c.len() > c1.c.len() { c0 } else { c1 };
+        c.extend_from_slice(&larger.c[c.len()..]);
 
         Ciphertext {
             c,
             poly_type: c0.poly_type.clone(),
             level: c0.level,
             seed: None,
+            noise_estimate: c0
+                .noise_estimate
+                .zip(c1.noise_estimate)
+                .map(|(a, b)| max(a, b) + 1),
         }
     }
 
+    /// Subtracts `c1` from `c0`. Like `add_assign` ciphertexts may have different no. of polynomials.
     pub fn sub_assign(&self, c0: &mut Ciphertext, c1: &Ciphertext) {
+        let c1 = self.align_levels_assign(c0, c1);
+
         // TODO: perform checks
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
         izip!(c0.c.iter_mut(), c1.c.iter()).for_each(|(p0, p1)| {
             ctx.sub_assign(p0, p1);
         });
+        if c1.c.len() > c0.c.len() {
+            let extra = c1.c[c0.c.len()..].iter().map(|p| ctx.neg(p)).collect_vec();
+            c0.c.extend(extra);
+        }
         c0.seed = None;
+        c0.noise_estimate = c0
+            .noise_estimate
+            .zip(c1.noise_estimate)
+            .map(|(a, b)| max(a, b) + 1);
     }
 
     pub fn sub(&self, c0: &Ciphertext, c1: &Ciphertext) -> Ciphertext {
+        let (c0, c1) = self.align_levels(c0, c1);
+        let (c0, c1) = (c0.as_ref(), c1.as_ref());
+
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
-        let c = izip!(c0.c.iter(), c1.c.iter())
+        let mut c = izip!(c0.c.iter(), c1.c.iter())
             .map(|(p0, p1)| ctx.sub(p0, p1))
             .collect_vec();
+        // remaining polynomials of the larger ciphertext
+        if c0.c.len() > c1.c.len() {
+            c.extend_from_slice(&c0.c[c.len()..]);
+        } else {
+            let extra = c1.c[c.len()..].iter().map(|p| ctx.neg(p)).collect_vec();
+            c.extend(extra);
+        }
 
         Ciphertext {
             c,
             poly_type: c0.poly_type.clone(),
             level: c0.level,
             seed: None,
+            noise_estimate: c0
+                .noise_estimate
+                .zip(c1.noise_estimate)
+                .map(|(a, b)| max(a, b) + 1),
         }
     }
 
@@ -251,6 +784,7 @@ impl Evaluator {
             poly_type: c0.poly_type.clone(),
             level: c0.level,
             seed: None,
+            noise_estimate: c0.noise_estimate,
         }
     }
 
@@ -262,6 +796,10 @@ impl Evaluator {
         });
 
         c0.seed = None;
+        c0.noise_estimate = c0
+            .noise_estimate
+            .zip(c1.noise_estimate)
+            .map(|(a, b)| max(a, self.params.mul_plaintext_noise_bits(b)) + 1);
     }
 
     pub fn mul_poly_assign(&self, c0: &mut Ciphertext, poly: &Poly) {
@@ -269,6 +807,9 @@ impl Evaluator {
         c0.c.iter_mut().for_each(|p0| ctx.mul_assign(p0, poly));
 
         c0.seed = None;
+        c0.noise_estimate = c0
+            .noise_estimate
+            .map(|n| self.params.mul_plaintext_noise_bits(n));
     }
 
     pub fn mul_poly(&self, c0: &Ciphertext, poly: &Poly) -> Ciphertext {
@@ -279,6 +820,9 @@ impl Evaluator {
             poly_type: c0.poly_type.clone(),
             level: c0.level,
             seed: None,
+            noise_estimate: c0
+                .noise_estimate
+                .map(|n| self.params.mul_plaintext_noise_bits(n)),
         }
     }
 
@@ -301,7 +845,9 @@ impl Evaluator {
         assert!(ct.poly_type() == PolyType::Q);
 
         let ctx = self.params.poly_ctx(&ct.poly_type, ct.level);
-        ctx.add_assign(&mut ct.c_ref_mut()[0], pt.add_sub_poly_ref());
+        // since c1 does not change seed remains valid. Modify c0 directly as `c_ref_mut` clears seed
+        ctx.add_assign(&mut ct.c[0], pt.add_sub_poly_ref());
+        ct.noise_estimate = ct.noise_estimate.map(|n| n + 1);
     }
 
     pub fn add_plaintext(&self, ct: &Ciphertext, pt: &Plaintext) -> Ciphertext {
@@ -320,6 +866,7 @@ impl Evaluator {
             seed: ct.seed.clone(),
             poly_type: ct.poly_type.clone(),
             level: ct.level,
+            noise_estimate: ct.noise_estimate.map(|n| n + 1),
         }
     }
 
@@ -328,7 +875,9 @@ impl Evaluator {
         assert!(ct.poly_type() == PolyType::Q);
 
         let ctx = self.params.poly_ctx(&ct.poly_type, ct.level);
-        ctx.sub_assign(&mut ct.c_ref_mut()[0], pt.add_sub_poly_ref());
+        // since c1 does not change seed remains valid. Modify c0 directly as `c_ref_mut` clears seed
+        ctx.sub_assign(&mut ct.c[0], pt.add_sub_poly_ref());
+        ct.noise_estimate = ct.noise_estimate.map(|n| n + 1);
     }
 
     pub fn sub_plaintext(&self, ct: &Ciphertext, pt: &Plaintext) -> Ciphertext {
@@ -347,6 +896,7 @@ impl Evaluator {
             seed: ct.seed.clone(),
             poly_type: ct.poly_type.clone(),
             level: ct.level,
+            noise_estimate: ct.noise_estimate.map(|n| n + 1),
         }
     }
 
@@ -357,6 +907,7 @@ impl Evaluator {
         ctx.neg_assign(&mut c0.c[1]);
 
         c0.seed = None;
+        c0.noise_estimate = c0.noise_estimate.map(|n| n + 1);
     }
 
     pub fn mod_down_next(&self, c0: &mut Ciphertext) {
@@ -369,6 +920,9 @@ impl Evaluator {
         c0.level = level + 1;
 
         c0.seed = None;
+        c0.noise_estimate = c0
+            .noise_estimate
+            .map(|n| self.params.mod_down_noise_bits(n, level));
     }
 
     pub fn mod_down_level(&self, c0: &mut Ciphertext, level: usize) {
@@ -403,6 +957,63 @@ impl Evaluator {
         sk.measure_noise(ct, &self.params)
     }
 
+    /// Rerandomizes ciphertext so that it reveals nothing beyond the plaintext it encrypts, ie noise no longer
+    /// depends on the computation (and inputs) that produced the ciphertext.
+    ///
+    /// Adds a fresh encryption of zero under `pk` and smudging noise sampled uniformly from
+    /// (-2^smudging_bits, 2^smudging_bits). For statistical circuit privacy, `smudging_bits` must exceed bits
+    /// of noise in the ciphertext by the statistical security parameter (for ex, 40 bits).
+    ///
+    /// Panics if estimated noise after smudging exceeds the noise budget.
+    pub fn sanitize<R: CryptoRng + RngCore>(
+        &self,
+        c0: &Ciphertext,
+        pk: &PublicKey,
+        smudging_bits: usize,
+        rng: &mut R,
+    ) -> Ciphertext {
+        assert!(c0.c.len() == 2);
+        assert!(c0.poly_type == PolyType::Q);
+
+        let noise_estimate = c0
+            .noise_estimate
+            .map(|n| max(max(n, smudging_bits), self.params.public_key_noise_bits()) + 1);
+        assert!(
+            self.params.noise_budget_bits(c0.level, noise_estimate.unwrap_or(smudging_bits + 1)) > 0,
+            "Smudging noise of {smudging_bits} bits exceeds noise budget"
+        );
+
+        let ctx = self.params.poly_ctx(&PolyType::Q, c0.level);
+        let mut ct = pk.encrypt_zero(&self.params, c0.level, rng);
+
+        // smudging noise
+        let big_q = ctx.big_q();
+        let noise = (0..ctx.degree)
+            .map(|_| {
+                let v = rng.gen_biguint(smudging_bits as u64);
+                if rng.gen::<bool>() {
+                    &big_q - v
+                } else {
+                    v
+                }
+            })
+            .collect_vec();
+        ctx.add_assign(
+            &mut ct.c[0],
+            &ctx.try_convert_from_biguint(&noise, Representation::Coefficient),
+        );
+
+        izip!(ct.c.iter_mut(), c0.c.iter()).for_each(|(p, p0)| {
+            if p0.representation != Representation::Coefficient {
+                ctx.change_representation(p, p0.representation.clone());
+            }
+            ctx.add_assign(p, p0);
+        });
+        ct.noise_estimate = noise_estimate;
+        ct
+    }
+
+    #[deprecated(note = "use `Evaluator::sanitize` instead")]
     pub unsafe fn add_noise(&self, c0: &mut Ciphertext, bit_size: usize) {
         let ctx = self.params.poly_ctx(&c0.poly_type, c0.level);
 
@@ -420,6 +1031,7 @@ impl Evaluator {
             ctx.add_assign(p, &noise_poly);
         });
         c0.seed = None;
+        c0.noise_estimate = c0.noise_estimate.map(|n| max(n, bit_size) + 1);
     }
 }
 
@@ -429,7 +1041,10 @@ mod tests {
 
     use rand::thread_rng;
 
-    use crate::{relinearization_key::RelinearizationKey, utils::rot_to_galois_element, PolyCache};
+    use crate::{
+        relinearization_key::RelinearizationKey, utils::rot_to_galois_element,
+        KeySwitchingStrategy, PolyCache, PublicKey,
+    };
 
     use super::*;
 
@@ -502,6 +1117,48 @@ mod tests {
         assert_eq!(&res_m_relin, &m0);
     }
 
+    #[test]
+    fn mul_relinearize_with_multiple_column_chunks() {
+        // degree is larger than no. of coefficients processed by single task in basis conversions
+        // when `parallel` feature is enabled
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 12);
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+
+        let mut m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let m1 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let ct0 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m0, Encoding::default()),
+            &mut rng,
+        );
+        let ct1 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m1, Encoding::default()),
+            &mut rng,
+        );
+
+        let mut ct01 = evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek);
+        evaluator.mod_down_next(&mut ct01);
+
+        evaluator
+            .params
+            .plaintext_modulus_op
+            .mul_mod_fast_vec(&mut m0, &m1);
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct01), Encoding::default()),
+            m0
+        );
+    }
+
     #[test]
     fn test_add_sub_plaintext() {
         let mut rng = thread_rng();
@@ -593,6 +1250,75 @@ mod tests {
         assert_eq!(&res, &m0m1);
     }
 
+    #[test]
+    fn poly_encoding_multiplies_negacyclic() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let degree = params.degree;
+        let t = params.plaintext_modulus;
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+
+        // coefficients are not required to be reduced
+        let m0 = (0..degree).map(|i| i as u64 + t).collect_vec();
+        let m1 = params.plaintext_modulus_op.random_vec(degree, &mut rng);
+
+        // m0 * m1 mod X^N + 1
+        let mut m0m1 = vec![0u64; degree];
+        for i in 0..degree {
+            for j in 0..degree {
+                let v = ((m0[i] % t) as u128 * m1[j] as u128 % t as u128) as u64;
+                let k = (i + j) % degree;
+                m0m1[k] = if i + j < degree {
+                    (m0m1[k] + v) % t
+                } else {
+                    (m0m1[k] + t - v) % t
+                };
+            }
+        }
+
+        let evaluator = Evaluator::new(params);
+        let encoding = Encoding::poly(0, PolyCache::None);
+        let ct0 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m0, encoding.clone()),
+            &mut rng,
+        );
+        let ct1 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m1, encoding.clone()),
+            &mut rng,
+        );
+        let decrypt = |ct: &Ciphertext| -> Vec<u64> {
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), encoding.clone())
+        };
+        assert_eq!(decrypt(&ct0), m0.iter().map(|v| v % t).collect_vec());
+
+        // plaintext multiplication
+        let pt1 = evaluator.plaintext_encode(&m1, Encoding::poly(0, PolyCache::Mul(PolyType::Q)));
+        let mut ct0_eval = ct0.clone();
+        evaluator.ciphertext_change_representation(&mut ct0_eval, Representation::Evaluation);
+        assert_eq!(decrypt(&evaluator.mul_plaintext(&ct0_eval, &pt1)), m0m1);
+
+        // ciphertext multiplication
+        let ct01 = evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek);
+        assert_eq!(decrypt(&ct01), m0m1);
+
+        // SIMD slots of coefficient encoded message
+        let pt = evaluator.decrypt(&sk, &ct01);
+        assert_eq!(
+            evaluator.plaintext_decode(&pt, Encoding::default()),
+            evaluator.params().coefficients_to_slots_plain(&m0m1)
+        );
+        assert_eq!(
+            evaluator.params().slots_to_coefficients_plain(
+                &evaluator.params().coefficients_to_slots_plain(&m0m1)
+            ),
+            m0m1
+        );
+    }
+
     #[test]
     fn test_rotations() {
         let mut rng = thread_rng();
@@ -622,6 +1348,161 @@ mod tests {
         dbg!(&res_m, &m0);
     }
 
+    #[test]
+    fn apply_galois_matches_rotations() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let row_swap = 2 * params.degree - 1;
+
+        let ek = EvaluationKey::new(
+            &params,
+            &sk,
+            &[],
+            &[0, 0],
+            &[1, row_swap as isize],
+            &mut rng,
+        );
+        // 2 * degree - 3 = -3, ie rotation by 1 followed by row swap
+        let ek_galois = EvaluationKey::new_with_galois_exponents(
+            &params,
+            &sk,
+            &[],
+            &[0, 0],
+            &[3, row_swap - 2],
+            KeySwitchingStrategy::Hybrid,
+            &mut rng,
+        );
+        // galois key for exponent 3 is the key for rotation by 1
+        assert!(ek_galois.get_rtg(1, 0).is_some());
+        assert!(ek_galois.get_rtg(2, 0).is_none());
+
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        let decrypt = |ct: &Ciphertext| {
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default())
+        };
+
+        let expected = decrypt(&evaluator.rotate(&ct, 1, &ek));
+        assert_eq!(
+            decrypt(&evaluator.apply_galois(&ct, 3, &ek_galois)),
+            expected
+        );
+        assert_eq!(decrypt(&evaluator.apply_galois(&ct, 3, &ek)), expected);
+        // exponents are reduced modulo 2 * degree
+        assert_eq!(
+            decrypt(&evaluator.apply_galois(&ct, 3 + 2 * evaluator.params().degree, &ek)),
+            expected
+        );
+
+        let mut ct_swapped = evaluator.rotate(&ct, 1, &ek);
+        evaluator.rotate_assign(&mut ct_swapped, row_swap as isize, &ek);
+        let mut ct_galois = ct.clone();
+        evaluator.apply_galois_assign(&mut ct_galois, row_swap - 2, &ek_galois);
+        assert_eq!(decrypt(&ct_galois), decrypt(&ct_swapped));
+    }
+
+    #[test]
+    fn rotate_many_matches_rotate() {
+        let mut rng = thread_rng();
+        let mut params = BfvParameters::new(&[50; 3], 65537, 1 << 4);
+        params.enable_hybrid_key_switching(&[50, 50, 50]);
+
+        let rotations = [1, 2, -1, 2 * 16 - 1];
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[], &[0; 4], &rotations, &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        let mut ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
+
+        for representation in [Representation::Coefficient, Representation::Evaluation] {
+            let q_ctx = evaluator.params.poly_ctx(&PolyType::Q, 0);
+            ct0.c_ref_mut()
+                .iter_mut()
+                .for_each(|p| q_ctx.change_representation(p, representation.clone()));
+
+            let rotated = evaluator.rotate_many(&ct0, &rotations, &ek);
+            assert_eq!(rotated.len(), rotations.len());
+            izip!(rotated.iter(), rotations.iter()).for_each(|(ct, r)| {
+                let expected = evaluator.rotate(&ct0, *r, &ek);
+                assert_eq!(
+                    evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default()),
+                    evaluator
+                        .plaintext_decode(&evaluator.decrypt(&sk, &expected), Encoding::default())
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn bv_key_switching_relinearize_and_rotate() {
+        let mut rng = thread_rng();
+        // BV key switching does not require special moduli
+        let params = BfvParameters::new(&[50; 3], 65537, 1 << 4);
+
+        let rotations = [1, -1];
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new_with_strategy(
+            &params,
+            &sk,
+            &[0],
+            &[0; 2],
+            &rotations,
+            KeySwitchingStrategy::Bv,
+            &mut rng,
+        );
+        assert_eq!(ek.rlks[&0].strategy(), KeySwitchingStrategy::Bv);
+
+        let mut m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let m1 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let decrypt = |ct: &Ciphertext| {
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default())
+        };
+        let ct0 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m0, Encoding::default()),
+            &mut rng,
+        );
+        let ct1 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m1, Encoding::default()),
+            &mut rng,
+        );
+
+        // rotations
+        let rotated = evaluator.rotate(&ct0, 1, &ek);
+        assert_eq!(decrypt(&evaluator.rotate(&rotated, -1, &ek)), m0);
+        let rotated_many = evaluator.rotate_many(&ct0, &rotations, &ek);
+        assert_eq!(decrypt(&rotated_many[0]), decrypt(&rotated));
+
+        // relinearization
+        let ct01 = evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek);
+        evaluator
+            .params
+            .plaintext_modulus_op
+            .mul_mod_fast_vec(&mut m0, &m1);
+        assert!(ct01.c.len() == 2);
+        assert_eq!(decrypt(&ct01), m0);
+    }
+
     #[test]
     #[ignore = "Takes long because degree is set to 2^15"]
     fn test_mul_lazy_add_and_relinearize() {
@@ -679,6 +1560,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn add_noise_works() {
         let mut rng = thread_rng();
         let params = BfvParameters::default(15, 1 << 4);
@@ -718,4 +1600,480 @@ mod tests {
         evaluator.mod_down_next(&mut ct0);
         assert!(evaluator.measure_noise(&sk, &ct0) <= noise_before);
     }
+
+    #[test]
+    fn noise_estimate_bounds_measured_noise() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(8, 1 << 4);
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0, 1], &[0], &[1], &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        let mut ct = evaluator.encrypt(&sk, &pt0, &mut rng);
+        let ct0 = ct.clone();
+
+        assert!(ct.noise_estimate().unwrap() as u64 >= evaluator.measure_noise(&sk, &ct));
+
+        for _ in 0..3 {
+            ct = evaluator.relinearize(&evaluator.mul(&ct, &ct0), &ek);
+            assert!(ct.noise_estimate().unwrap() as u64 >= evaluator.measure_noise(&sk, &ct));
+
+            ct = evaluator.add(&ct, &ct0);
+            assert!(ct.noise_estimate().unwrap() as u64 >= evaluator.measure_noise(&sk, &ct));
+        }
+
+        ct = evaluator.rotate(&ct, 1, &ek);
+        assert!(ct.noise_estimate().unwrap() as u64 >= evaluator.measure_noise(&sk, &ct));
+
+        evaluator.mod_down_next(&mut ct);
+        assert!(ct.noise_estimate().unwrap() as u64 >= evaluator.measure_noise(&sk, &ct));
+        assert!(evaluator.noise_budget_estimate(&ct).unwrap() > 0);
+    }
+
+    #[test]
+    fn auto_mod_switching() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(10, 1 << 4);
+
+        // gen keys
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let rlk_levels = (0..params.max_level).collect_vec();
+        let ek = EvaluationKey::new(&params, &sk, &rlk_levels, &[], &[], &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let mut evaluator = Evaluator::new(params);
+        evaluator.enable_auto_mod_switching();
+
+        let pt0 = evaluator.plaintext_encode(&m0, Encoding::default());
+        let ct0 = evaluator.encrypt(&sk, &pt0, &mut rng);
+
+        let mut ct = ct0.clone();
+        let mut m = m0.clone();
+        for _ in 0..4 {
+            // `ct` and `ct0` are at different levels after the first iteration
+            ct = evaluator.relinearize(&evaluator.mul(&ct, &ct0), &ek);
+            evaluator
+                .params
+                .plaintext_modulus_op
+                .mul_mod_fast_vec(&mut m, &m0);
+        }
+        assert!(ct.level() > 0);
+
+        let sum = evaluator.add(&ct, &ct0);
+        assert!(sum.level() == ct.level());
+
+        let rm = evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct), Encoding::default());
+        assert_eq!(rm, m);
+
+        let mut m_sum = m.clone();
+        evaluator
+            .params
+            .plaintext_modulus_op
+            .add_mod_fast_vec(&mut m_sum, &m0);
+        let rm = evaluator.plaintext_decode(&evaluator.decrypt(&sk, &sum), Encoding::default());
+        assert_eq!(rm, m_sum);
+    }
+
+    #[test]
+    fn mul_and_relinearize_higher_degree() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let mut ek = EvaluationKey::new(&params, &sk, &[], &[], &[], &mut rng);
+        ek.insert_rlk(RelinearizationKey::new_with_degree(
+            &params,
+            &sk,
+            0,
+            4,
+            KeySwitchingStrategy::Hybrid,
+            &mut rng,
+        ));
+        assert_eq!(ek.get_rlk(0, 4).unwrap().degree(), 4);
+
+        // degree 2 key at a higher level does not shadow degree 4 key at a lower level
+        ek.insert_rlk(RelinearizationKey::new_with_degree(
+            &params,
+            &sk,
+            1,
+            2,
+            KeySwitchingStrategy::Hybrid,
+            &mut rng,
+        ));
+        assert_eq!(ek.get_rlk(1, 2).unwrap().level, 1);
+        assert_eq!(ek.get_rlk(1, 4).unwrap().level, 0);
+        assert!(ek.get_rlk(1, 5).is_none());
+
+        let modt = params.plaintext_modulus_op.clone();
+        let m = (0..3)
+            .map(|_| modt.random_vec(params.degree, &mut rng))
+            .collect_vec();
+        let evaluator = Evaluator::new(params);
+        let ct = m
+            .iter()
+            .map(|mi| {
+                evaluator.encrypt(
+                    &sk,
+                    &evaluator.plaintext_encode(mi, Encoding::default()),
+                    &mut rng,
+                )
+            })
+            .collect_vec();
+        let decrypt = |ct: &Ciphertext| {
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default())
+        };
+        let mul = |a: &[u64], b: &[u64]| {
+            let mut r = a.to_vec();
+            modt.mul_mod_fast_vec(&mut r, b);
+            r
+        };
+
+        // degree 2
+        let ct01 = evaluator.mul(&ct[0], &ct[1]);
+        let m01 = mul(&m[0], &m[1]);
+        assert!(ct01.c.len() == 3);
+
+        // degree 3 = degree 2 * degree 1, summed with degree 2 and degree 1 ciphertexts
+        let mut ct012 = evaluator.mul(&ct01, &ct[2]);
+        assert!(ct012.c.len() == 4);
+        let mut m012 = mul(&m01, &m[2]);
+        evaluator.add_assign(&mut ct012, &ct01);
+        modt.add_mod_fast_vec(&mut m012, &m01);
+        let ct012 = evaluator.sub(&ct[0], &ct012);
+        let mut m_sub = m[0].clone();
+        modt.sub_mod_fast_vec(&mut m_sub, &m012);
+        assert!(ct012.c.len() == 4);
+
+        let relinearized = evaluator.relinearize(&ct012, &ek);
+        assert!(relinearized.c.len() == 2);
+        assert_eq!(decrypt(&relinearized), m_sub);
+
+        // degree 4 = degree 2 * degree 2
+        let mut ct0101 = evaluator.mul(&ct01, &ct01);
+        assert!(ct0101.c.len() == 5);
+        evaluator.relinearize_assign(&mut ct0101, &ek);
+        assert!(ct0101.c.len() == 2);
+        assert_eq!(decrypt(&ct0101), mul(&m01, &m01));
+    }
+
+    #[test]
+    fn square_matches_mul() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+
+        let modt = params.plaintext_modulus_op.clone();
+        let m = modt.random_vec(params.degree, &mut rng);
+        let mut m_sq = m.clone();
+        modt.mul_mod_fast_vec(&mut m_sq, &m);
+        let mut m_quad = m_sq.clone();
+        modt.mul_mod_fast_vec(&mut m_quad, &m_sq);
+
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        let decrypt = |ct: &Ciphertext| {
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default())
+        };
+
+        let ct_sq = evaluator.square(&ct);
+        assert!(ct_sq.c.len() == 3);
+        assert_eq!(decrypt(&ct_sq), m_sq);
+        assert_eq!(
+            ct_sq.noise_estimate(),
+            evaluator.mul(&ct, &ct.clone()).noise_estimate()
+        );
+
+        let ct_sq = evaluator.relinearize(&ct_sq, &ek);
+        assert_eq!(decrypt(&ct_sq), m_sq);
+
+        // squaring an unrelinearized ciphertext
+        let ct_quad = evaluator.square(&evaluator.square(&ct));
+        assert!(ct_quad.c.len() == 5);
+        assert_eq!(decrypt(&ct_quad), m_quad);
+
+        let ct_quad = evaluator.relinearize(&evaluator.square(&ct_sq), &ek);
+        assert_eq!(decrypt(&ct_quad), m_quad);
+    }
+
+    #[test]
+    fn level_zero_keys_work_at_all_levels() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let m1 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let mut m01 = m0.clone();
+        params.plaintext_modulus_op.mul_mod_fast_vec(&mut m01, &m1);
+
+        let mut evaluator = Evaluator::new(params);
+        let decrypt = |evaluator: &Evaluator, ct: &Ciphertext| {
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default())
+        };
+
+        for strategy in [KeySwitchingStrategy::Hybrid, KeySwitchingStrategy::Bv] {
+            let ek = EvaluationKey::new_with_strategy(
+                &evaluator.params,
+                &sk,
+                &[0],
+                &[0],
+                &[1],
+                strategy,
+                &mut rng,
+            );
+            assert_eq!(ek.get_rlk(3, 2).unwrap().level, 0);
+            assert!(ek.get_rtg(-1, 3).is_none());
+
+            let ct0 = evaluator.encrypt(
+                &sk,
+                &evaluator.plaintext_encode(&m0, Encoding::default()),
+                &mut rng,
+            );
+            let rotated = decrypt(&evaluator, &evaluator.rotate(&ct0, 1, &ek));
+
+            for level in 1..evaluator.params.max_level {
+                let ct0 = evaluator.encrypt(
+                    &sk,
+                    &evaluator.plaintext_encode(&m0, Encoding::simd(level, PolyCache::None)),
+                    &mut rng,
+                );
+                let ct1 = evaluator.encrypt(
+                    &sk,
+                    &evaluator.plaintext_encode(&m1, Encoding::simd(level, PolyCache::None)),
+                    &mut rng,
+                );
+
+                let ct01 = evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek);
+                assert_eq!(ct01.level(), level);
+                assert_eq!(decrypt(&evaluator, &ct01), m01);
+
+                let ct0_rotated = evaluator.rotate(&ct0, 1, &ek);
+                assert_eq!(ct0_rotated.level(), level);
+                assert_eq!(decrypt(&evaluator, &ct0_rotated), rotated);
+            }
+        }
+
+        // with automatic modulus switching ciphertext is switched down even though there's only
+        // a single relinearization key
+        let ek = EvaluationKey::new(&evaluator.params, &sk, &[0], &[], &[], &mut rng);
+        evaluator.enable_auto_mod_switching();
+        let ct0 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m0, Encoding::default()),
+            &mut rng,
+        );
+        let mut ct = ct0.clone();
+        let mut m = m0.clone();
+        for _ in 0..3 {
+            ct = evaluator.relinearize(&evaluator.mul(&ct, &ct0), &ek);
+            evaluator
+                .params
+                .plaintext_modulus_op
+                .mul_mod_fast_vec(&mut m, &m0);
+        }
+        assert!(ct.level() > 0);
+        assert_eq!(decrypt(&evaluator, &ct), m);
+    }
+
+    #[test]
+    fn switch_key_works() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let trader_sk = SecretKey::random_with_params(&params, &mut rng);
+        let trader_pk = PublicKey::new(&params, &trader_sk, &mut rng);
+
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let level = 2;
+        let ksk = KeySwitchingKey::new(&params, &sk, &trader_sk, level, &mut rng);
+        let ksk_pk = KeySwitchingKey::new_with_public_key(&params, &sk, &trader_pk, level, &mut rng);
+
+        let mut evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::simd(level, PolyCache::None)),
+            &mut rng,
+        );
+
+        for ksk in [&ksk, &ksk_pk] {
+            let ct_trader = evaluator.switch_key(&ct, ksk);
+            let res = evaluator
+                .plaintext_decode(&evaluator.decrypt(&trader_sk, &ct_trader), Encoding::default());
+            assert_eq!(res, m);
+            assert_ne!(
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_trader), Encoding::default()),
+                m
+            );
+        }
+
+        // ciphertext at lower level is switched down to level of the key
+        evaluator.enable_auto_mod_switching();
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        let ct_trader = evaluator.switch_key(&ct, &ksk_pk);
+        assert_eq!(ct_trader.level(), level);
+        let res = evaluator
+            .plaintext_decode(&evaluator.decrypt(&trader_sk, &ct_trader), Encoding::default());
+        assert_eq!(res, m);
+    }
+
+    #[test]
+    fn sanitize_hides_computation_noise() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let pk = PublicKey::new(&params, &sk, &mut rng);
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let mut m_sq = m.clone();
+        params.plaintext_modulus_op.mul_mod_fast_vec(&mut m_sq, &m);
+
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+
+        // same plaintext produced by computations with different noise
+        let rlk = RelinearizationKey::new(&evaluator.params, &sk, 0, &mut rng);
+        let ct_sq = rlk.relinearize(&evaluator.mul(&ct, &ct), &evaluator.params);
+        let ct_sq_fresh = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m_sq, Encoding::default()),
+            &mut rng,
+        );
+        assert!(evaluator.measure_noise(&sk, &ct_sq) > evaluator.measure_noise(&sk, &ct_sq_fresh) + 10);
+
+        let smudging_bits = ct_sq.noise_estimate().unwrap() + 40;
+        for c in [&ct_sq, &ct_sq_fresh] {
+            let sanitized = evaluator.sanitize(c, &pk, smudging_bits, &mut rng);
+            assert_eq!(
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &sanitized), Encoding::default()),
+                m_sq
+            );
+
+            // noise is dominated by smudging noise, irrespective of the computation
+            let noise = evaluator.measure_noise(&sk, &sanitized) as usize;
+            assert!(noise >= smudging_bits - 1 && noise <= smudging_bits + 1);
+            assert!(noise <= sanitized.noise_estimate().unwrap());
+
+            // ciphertext is rerandomized
+            assert_ne!(sanitized.c[1], c.c[1]);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn sanitize_panics_on_excessive_smudging() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(2, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let pk = PublicKey::new(&params, &sk, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&[0; 16], Encoding::default()),
+            &mut rng,
+        );
+        evaluator.sanitize(&ct, &pk, 200, &mut rng);
+    }
+
+    #[test]
+    fn into_and_assign_variants_reuse_buffers() {
+        let mut rng = thread_rng();
+        let mut params = BfvParameters::new(&[50; 3], 65537, 1 << 4);
+        params.enable_hybrid_key_switching(&[50, 50, 50]);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[0], &[1], &mut rng);
+
+        let m0 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let m1 = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let ct0 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m0, Encoding::default()),
+            &mut rng,
+        );
+        let ct1 = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m1, Encoding::default()),
+            &mut rng,
+        );
+
+        let decrypt = |ct: &Ciphertext| {
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default())
+        };
+        let expected_mul = evaluator.relinearize(&evaluator.mul(&ct0, &ct1), &ek);
+        let expected_rot = evaluator.rotate(&ct0, 1, &ek);
+
+        let mut ct01 = Ciphertext::placeholder();
+        let mut ct01_relin = Ciphertext::placeholder();
+        let mut ct0_rot = Ciphertext::placeholder();
+        let mut out_ptrs = None;
+        for _ in 0..3 {
+            evaluator.mul_into(&ct0, &ct1, &mut ct01);
+            evaluator.relinearize_into(&ct01, &ek, &mut ct01_relin);
+            evaluator.rotate_into(&ct0, 1, &ek, &mut ct0_rot);
+
+            assert_eq!(decrypt(&ct01_relin), decrypt(&expected_mul));
+            assert_eq!(decrypt(&ct0_rot), decrypt(&expected_rot));
+
+            // outputs are overwritten in place after the first iteration
+            let ptrs = [&ct01_relin, &ct0_rot]
+                .map(|ct| ct.c.iter().map(|p| p.coefficients.as_ptr()).collect_vec());
+            assert!(*out_ptrs.get_or_insert_with(|| ptrs.clone()) == ptrs);
+        }
+        // intermediate polynomials are retained for reuse
+        assert!(evaluator.scratch_arena().buffers() > 0);
+
+        let mut ct = ct01.clone();
+        evaluator.relinearize_assign(&mut ct, &ek);
+        evaluator.rotate_assign(&mut ct, 1, &ek);
+        assert_eq!(
+            decrypt(&ct),
+            decrypt(&evaluator.rotate(&expected_mul, 1, &ek))
+        );
+
+        evaluator.recycle(ct);
+        evaluator.scratch_arena().clear();
+        assert_eq!(evaluator.scratch_arena().size_in_bytes(), 0);
+
+        // operations without an arena retain buffers in arena of the thread
+        ek.get_rtg_ref(1, 0).rotate(&ct0, evaluator.params());
+        assert!(ScratchArena::with_thread_local(|arena| arena.buffers()) > 0);
+        ScratchArena::clear_thread_local();
+        assert_eq!(ScratchArena::with_thread_local(|arena| arena.buffers()), 0);
+    }
 }
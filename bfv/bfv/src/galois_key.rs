@@ -1,13 +1,17 @@
+use crate::key_switching_key::SwitchingKey;
 use crate::{
-    BfvParameters, Ciphertext, HybridKeySwitchingKey, Modulus, Poly, PolyContext, PolyType,
-    Representation, SecretKey, Substitution,
+    BfvParameters, Ciphertext, HybridKeySwitchingKey, KeySwitchingStrategy, Modulus, Poly,
+    PolyContext, PolyType, Representation, ScratchArena, SecretKey, Substitution,
 };
 use rand::{CryptoRng, RngCore};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use zeroize::Zeroizing;
 
 #[derive(Debug, PartialEq)]
 pub struct GaloisKey {
     pub(crate) substitution: Substitution,
-    pub(crate) ksk_key: HybridKeySwitchingKey,
+    pub(crate) ksk_key: SwitchingKey,
     pub(crate) level: usize,
 }
 
@@ -18,28 +22,37 @@ impl GaloisKey {
         level: usize,
         sk: &SecretKey,
         rng: &mut R,
+    ) -> GaloisKey {
+        GaloisKey::new_with_strategy(
+            exponent,
+            params,
+            level,
+            sk,
+            KeySwitchingStrategy::default(),
+            rng,
+        )
+    }
+
+    /// Generates galois key for `exponent` at `level` that uses `strategy` for key switching. The key
+    /// also rotates ciphertexts at any level higher than `level`.
+    pub fn new_with_strategy<R: CryptoRng + RngCore>(
+        exponent: usize,
+        params: &BfvParameters,
+        level: usize,
+        sk: &SecretKey,
+        strategy: KeySwitchingStrategy,
+        rng: &mut R,
     ) -> GaloisKey {
         let substitution = Substitution::new(exponent, params.degree);
 
         let q_ctx = params.poly_ctx(&PolyType::Q, level);
-        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
 
         // Substitute secret key
-        let mut sk_poly =
-            q_ctx.try_convert_from_i64_small(&sk.coefficients, Representation::Coefficient);
-        q_ctx.change_representation(&mut sk_poly, Representation::Evaluation);
-
-        let sk_poly = q_ctx.substitute(&sk_poly, &substitution);
+        let sk_poly = sk.to_poly(&q_ctx);
+        let sk_poly = Zeroizing::new(q_ctx.substitute(&sk_poly, &substitution));
 
         // Generate key switching key for substituted secret key
-        let ksk_key = HybridKeySwitchingKey::new(
-            params.hybrid_key_switching_params_at_level(level),
-            &sk_poly,
-            &sk,
-            &qp_ctx,
-            params.variance,
-            rng,
-        );
+        let ksk_key = SwitchingKey::new(strategy, params, level, &sk_poly, sk, rng);
 
         GaloisKey {
             substitution,
@@ -48,43 +61,177 @@ impl GaloisKey {
         }
     }
 
+    pub fn strategy(&self) -> KeySwitchingStrategy {
+        self.ksk_key.strategy()
+    }
+
+    /// Returns galois exponent of the key, reduced modulo `2 * degree`
+    pub fn exponent(&self) -> usize {
+        self.substitution.exponent
+    }
+
     pub fn rotate(&self, ct: &Ciphertext, params: &BfvParameters) -> Ciphertext {
+        let mut out = Ciphertext::placeholder();
+        ScratchArena::with_thread_local(|scratch| self.rotate_into(ct, params, scratch, &mut out));
+        out
+    }
+
+    /// Same as `rotate` but writes the result to `out`. Intermediate polynomials are taken from
+    /// `scratch`. Polynomials of `out` are overwritten in place if they are in context Q at level of
+    /// `ct`, otherwise they are replaced with ones taken from `scratch`.
+    pub(crate) fn rotate_into(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+        scratch: &ScratchArena,
+        out: &mut Ciphertext,
+    ) {
         assert!(ct.c.len() == 2);
-        assert!(ct.level == self.level);
+        // key generated at a lower level can be used at higher levels
+        assert!(ct.level >= self.level);
         assert!(ct.poly_type == PolyType::Q);
 
-        let level = self.level;
+        let level = ct.level;
         let q_ctx = params.poly_ctx(&PolyType::Q, level);
-        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
-        let specialp_ctx = params.poly_ctx(&PolyType::SpecialP, level);
 
         // Key switch c1
-        let mut c1 = q_ctx.substitute(&ct.c[1], &self.substitution);
+        let mut c1 = scratch.take_poly(&q_ctx, Representation::Unknown);
+        q_ctx.substitute_into(&ct.c[1], &self.substitution, &mut c1);
         if c1.representation == Representation::Evaluation {
             q_ctx.change_representation(&mut c1, Representation::Coefficient);
         }
 
-        let (mut cs0, mut cs1) = self.ksk_key.switch(
-            params.hybrid_key_switching_params_at_level(level),
-            &c1,
-            &qp_ctx,
-            &q_ctx,
-            &specialp_ctx,
-        );
+        scratch.prepare_ciphertext(out, &q_ctx, 2);
+        let (cs0, cs1) = out.c.split_at_mut(1);
+        let (cs0, cs1) = (&mut cs0[0], &mut cs1[0]);
+        self.ksk_key
+            .switch_into(params, level, &c1, scratch, cs0, cs1);
 
         // Key switch returns polynomial in Evaluation form
         if ct.c[0].representation != cs0.representation {
-            q_ctx.change_representation(&mut cs0, ct.c[0].representation.clone());
-            q_ctx.change_representation(&mut cs1, ct.c[0].representation.clone());
+            q_ctx.change_representation(cs0, ct.c[0].representation.clone());
+            q_ctx.change_representation(cs1, ct.c[0].representation.clone());
         }
 
-        q_ctx.add_assign(&mut cs0, &q_ctx.substitute(&ct.c[0], &self.substitution));
+        // reuse c1's buffer for substituted c0
+        q_ctx.substitute_into(&ct.c[0], &self.substitution, &mut c1);
+        q_ctx.add_assign(cs0, &c1);
+        scratch.give(c1);
 
-        Ciphertext {
-            c: vec![cs0, cs1],
-            poly_type: PolyType::Q,
-            level,
-            seed: None,
+        out.poly_type = PolyType::Q;
+        out.level = level;
+        out.seed = None;
+        out.noise_estimate = ct
+            .noise_estimate
+            .map(|n| std::cmp::max(n, self.ksk_key.noise_bits(params, level)) + 1);
+    }
+
+    /// Rotates `ct` with each of the `keys` and returns rotated ciphertexts in the same order.
+    ///
+    /// Rotations share a single decomposition of `c1` into digits in QP (ie hoisting), instead of
+    /// decomposing substituted `c1` for every key like `rotate` does. Digits are substituted in QP
+    /// before multiplying them with the key, which is valid since the substitution commutes with the
+    /// basis extension. All keys must be at level of `ct` or at a lower level. Hoisting applies to hybrid keys only; if any
+    /// of the `keys` uses BV key switching, `ct` is rotated with each key separately.
+    pub(crate) fn rotate_hoisted(
+        keys: &[&GaloisKey],
+        ct: &Ciphertext,
+        params: &BfvParameters,
+        scratch: &ScratchArena,
+    ) -> Vec<Ciphertext> {
+        assert!(ct.c.len() == 2);
+        assert!(ct.poly_type == PolyType::Q);
+        assert!(keys.iter().all(|k| k.level <= ct.level));
+
+        let hybrid_keys = keys
+            .iter()
+            .filter_map(|k| match &k.ksk_key {
+                SwitchingKey::Hybrid(ksk) => Some(ksk),
+                SwitchingKey::Bv(_) => None,
+            })
+            .collect::<Vec<&HybridKeySwitchingKey>>();
+        if hybrid_keys.len() != keys.len() {
+            return keys
+                .iter()
+                .map(|key| {
+                    let mut out = Ciphertext::placeholder();
+                    key.rotate_into(ct, params, scratch, &mut out);
+                    out
+                })
+                .collect();
         }
+
+        let level = ct.level;
+        let q_ctx = params.poly_ctx(&PolyType::Q, level);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+        let specialp_ctx = params.poly_ctx(&PolyType::SpecialP, level);
+        let ksk_params = params.hybrid_key_switching_params_at_level(level);
+
+        // Decompose c1 once
+        let digits = if ct.c[1].representation == Representation::Coefficient {
+            HybridKeySwitchingKey::decompose(ksk_params, &ct.c[1], &qp_ctx, scratch)
+        } else {
+            let mut c1 = scratch.take_poly(&q_ctx, Representation::Unknown);
+            c1.coefficients.assign(&ct.c[1].coefficients);
+            c1.representation = ct.c[1].representation.clone();
+            q_ctx.change_representation(&mut c1, Representation::Coefficient);
+            let digits = HybridKeySwitchingKey::decompose(ksk_params, &c1, &qp_ctx, scratch);
+            scratch.give(c1);
+            digits
+        };
+
+        #[cfg(feature = "parallel")]
+        let keys_iter = keys.par_iter().zip(hybrid_keys.par_iter());
+        #[cfg(not(feature = "parallel"))]
+        let keys_iter = keys.iter().zip(hybrid_keys.iter());
+
+        let rotated = keys_iter
+            .map(|(key, ksk)| {
+                let substituted_digits = digits
+                    .iter()
+                    .map(|d| {
+                        let mut sd = scratch.take_poly(&qp_ctx, Representation::Unknown);
+                        qp_ctx.substitute_into(d, &key.substitution, &mut sd);
+                        sd
+                    })
+                    .collect::<Vec<Poly>>();
+
+                let (mut cs0, mut cs1) = ksk.key_product(
+                    ksk_params,
+                    &substituted_digits,
+                    &qp_ctx,
+                    &q_ctx,
+                    &specialp_ctx,
+                    scratch,
+                );
+                substituted_digits
+                    .into_iter()
+                    .for_each(|sd| scratch.give(sd));
+
+                // Key product returns polynomial in Evaluation form
+                if ct.c[0].representation != cs0.representation {
+                    q_ctx.change_representation(&mut cs0, ct.c[0].representation.clone());
+                    q_ctx.change_representation(&mut cs1, ct.c[0].representation.clone());
+                }
+
+                let mut c0 = scratch.take_poly(&q_ctx, Representation::Unknown);
+                q_ctx.substitute_into(&ct.c[0], &key.substitution, &mut c0);
+                q_ctx.add_assign(&mut cs0, &c0);
+                scratch.give(c0);
+
+                Ciphertext {
+                    c: vec![cs0, cs1],
+                    poly_type: PolyType::Q,
+                    level,
+                    seed: None,
+                    noise_estimate: ct
+                        .noise_estimate
+                        .map(|n| std::cmp::max(n, params.key_switching_noise_bits(level)) + 1),
+                }
+            })
+            .collect::<Vec<Ciphertext>>();
+
+        digits.into_iter().for_each(|d| scratch.give(d));
+        rotated
     }
 }
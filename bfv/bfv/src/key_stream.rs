@@ -0,0 +1,449 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use crate::evaluation_key::rot_to_exponent;
+use crate::proto::proto::{
+    evaluation_key_frame::Key as FrameKeyProto, EvaluationKeyFrame as EvaluationKeyFrameProto,
+    EvaluationKeyStreamEntry, EvaluationKeyStreamHeader, GaloisKey as GaloisKeyProto,
+    RelinearizationKey as RelinearizationKeyProto,
+};
+use crate::{BfvParameters, EvaluationKey, GaloisKey, RelinearizationKey};
+use itertools::Itertools;
+use prost::Message;
+use traits::TryFromWithParameters;
+
+const STREAM_VERSION: u32 = 1;
+
+/// Single key of an `EvaluationKey` stream
+#[derive(Debug, PartialEq)]
+pub enum EvaluationKeyFrame {
+    Relinearization(RelinearizationKey),
+    Galois(GaloisKey),
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn write_frame<W: Write, M: Message>(writer: &mut W, message: &M) -> Result<()> {
+    writer.write_all(&message.encode_length_delimited_to_vec())
+}
+
+/// Reads a length delimited frame without trusting the length prefix for allocation
+fn read_frame<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut len = 0u64;
+    for i in 0.. {
+        if i == 10 {
+            return Err(invalid_data("Invalid frame length"));
+        }
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte)?;
+        len |= ((byte[0] & 0x7f) as u64) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+
+    let mut bytes = vec![];
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(Error::from(ErrorKind::UnexpectedEof));
+    }
+    Ok(bytes)
+}
+
+/// Writes `EvaluationKey` as a stream of frames, one key per frame.
+///
+/// Keys are written in order declared in the header, thus key generator can generate and write keys one
+/// at a time without ever holding the entire `EvaluationKey` in memory.
+pub struct EvaluationKeyStreamWriter<'a, W: Write> {
+    writer: W,
+    params: &'a BfvParameters,
+    pending: VecDeque<EvaluationKeyStreamEntry>,
+}
+
+impl<'a, W: Write> EvaluationKeyStreamWriter<'a, W> {
+    /// Writes stream header declaring relinearization keys at `rlk_levels` followed by galois keys for
+    /// `rtg_indices` at `rtg_levels`. Arguments are same as of `EvaluationKey::new`.
+    ///
+    /// Panics if `rtg_levels` and `rtg_indices` are of different lengths.
+    pub fn new(
+        params: &'a BfvParameters,
+        writer: W,
+        rlk_levels: &[usize],
+        rtg_levels: &[usize],
+        rtg_indices: &[isize],
+    ) -> Result<EvaluationKeyStreamWriter<'a, W>> {
+        assert!(rtg_levels.len() == rtg_indices.len());
+
+        let exponents = rtg_indices
+            .iter()
+            .map(|index| rot_to_exponent(*index, params.degree))
+            .collect_vec();
+        EvaluationKeyStreamWriter::new_with_galois_exponents(
+            params, writer, rlk_levels, rtg_levels, &exponents,
+        )
+    }
+
+    /// Same as `new` but galois keys are declared by galois `exponents` at `galois_levels`. Arguments
+    /// are same as of `EvaluationKey::new_with_galois_exponents`.
+    ///
+    /// Panics if `galois_levels` and `exponents` are of different lengths.
+    pub fn new_with_galois_exponents(
+        params: &'a BfvParameters,
+        mut writer: W,
+        rlk_levels: &[usize],
+        galois_levels: &[usize],
+        exponents: &[usize],
+    ) -> Result<EvaluationKeyStreamWriter<'a, W>> {
+        assert!(galois_levels.len() == exponents.len());
+
+        let entries = rlk_levels
+            .iter()
+            .map(|level| EvaluationKeyStreamEntry {
+                level: *level as u32,
+                exponent: None,
+            })
+            .chain(
+                galois_levels
+                    .iter()
+                    .zip(exponents.iter())
+                    .map(|(level, exponent)| EvaluationKeyStreamEntry {
+                        level: *level as u32,
+                        exponent: Some((*exponent % (2 * params.degree)) as u32),
+                    }),
+            )
+            .collect_vec();
+
+        write_frame(
+            &mut writer,
+            &EvaluationKeyStreamHeader {
+                version: STREAM_VERSION,
+                entries: entries.clone(),
+            },
+        )?;
+
+        Ok(EvaluationKeyStreamWriter {
+            writer,
+            params,
+            pending: entries.into(),
+        })
+    }
+
+    /// Writes relinearization key as next key of the stream.
+    ///
+    /// Panics if relinearization key at `rlk.level` is not the next key declared in header.
+    pub fn write_relinearization_key(&mut self, rlk: &RelinearizationKey) -> Result<()> {
+        self.check_next(rlk.level, None);
+        let key = FrameKeyProto::Rlk(RelinearizationKeyProto::try_from_with_parameters(
+            rlk,
+            self.params,
+        ));
+        write_frame(
+            &mut self.writer,
+            &EvaluationKeyFrameProto { key: Some(key) },
+        )
+    }
+
+    /// Writes galois key as next key of the stream.
+    ///
+    /// Panics if galois key for `gk.exponent()` at `gk.level` is not the next key declared in header.
+    pub fn write_galois_key(&mut self, gk: &GaloisKey) -> Result<()> {
+        self.check_next(gk.level, Some(gk.exponent()));
+        let key = FrameKeyProto::Rtg(GaloisKeyProto::try_from_with_parameters(gk, self.params));
+        write_frame(
+            &mut self.writer,
+            &EvaluationKeyFrameProto { key: Some(key) },
+        )
+    }
+
+    fn check_next(&mut self, level: usize, exponent: Option<usize>) {
+        let expected = self.pending.pop_front().expect("All keys already written");
+        assert!(
+            expected.level as usize == level && expected.exponent.map(|e| e as usize) == exponent,
+            "Key does not match the next key declared in header"
+        );
+    }
+
+    /// Flushes and returns the underlying writer.
+    ///
+    /// Panics if not all keys declared in header have been written.
+    pub fn finish(mut self) -> Result<W> {
+        assert!(self.pending.is_empty(), "Not all keys have been written");
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads `EvaluationKey` stream written by `EvaluationKeyStreamWriter` one key at a time.
+///
+/// Iterates over keys in the order declared in stream header. Peak memory is bounded by size of single
+/// key, which allows to forward keys (for ex. from key generator to committee nodes) without buffering
+/// the entire `EvaluationKey`.
+pub struct EvaluationKeyStreamReader<'a, R: Read> {
+    reader: R,
+    params: &'a BfvParameters,
+    pending: VecDeque<EvaluationKeyStreamEntry>,
+}
+
+impl<'a, R: Read> EvaluationKeyStreamReader<'a, R> {
+    /// Reads stream header from `reader`
+    pub fn new(
+        params: &'a BfvParameters,
+        mut reader: R,
+    ) -> Result<EvaluationKeyStreamReader<'a, R>> {
+        let header = EvaluationKeyStreamHeader::decode(read_frame(&mut reader)?.as_slice())
+            .map_err(|_| invalid_data("Malformed stream header"))?;
+        if header.version != STREAM_VERSION {
+            return Err(invalid_data("Unsupported stream version"));
+        }
+
+        if header
+            .entries
+            .iter()
+            .any(|entry| entry.level as usize >= params.max_level)
+        {
+            return Err(invalid_data("Key level not supported by parameters"));
+        }
+
+        Ok(EvaluationKeyStreamReader {
+            reader,
+            params,
+            pending: header.entries.into(),
+        })
+    }
+
+    /// Returns no. of keys remaining in the stream
+    pub fn remaining(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn read_next(&mut self, expected: EvaluationKeyStreamEntry) -> Result<EvaluationKeyFrame> {
+        let frame = EvaluationKeyFrameProto::decode(read_frame(&mut self.reader)?.as_slice())
+            .map_err(|_| invalid_data("Malformed key frame"))?;
+
+        // frame comes from an untrusted source, thus check that its key decodes before decoding it
+        let validation = match &frame.key {
+            Some(FrameKeyProto::Rlk(rlk)) => rlk.validate(self.params),
+            Some(FrameKeyProto::Rtg(gk)) => gk.validate(self.params),
+            None => Err("Key missing".into()),
+        };
+        validation.map_err(|e| invalid_data(&e))?;
+
+        let frame = match (frame.key, expected.exponent) {
+            (Some(FrameKeyProto::Rlk(rlk)), None) if rlk.level == expected.level => {
+                EvaluationKeyFrame::Relinearization(RelinearizationKey::try_from_with_parameters(
+                    &rlk,
+                    self.params,
+                ))
+            }
+            (Some(FrameKeyProto::Rtg(gk)), Some(exponent))
+                if gk.level == expected.level && gk.exponent == exponent =>
+            {
+                EvaluationKeyFrame::Galois(GaloisKey::try_from_with_parameters(&gk, self.params))
+            }
+            _ => return Err(invalid_data("Key does not match stream header")),
+        };
+        Ok(frame)
+    }
+}
+
+impl<'a, R: Read> Iterator for EvaluationKeyStreamReader<'a, R> {
+    type Item = Result<EvaluationKeyFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let expected = self.pending.pop_front()?;
+        let frame = self.read_next(expected);
+        if frame.is_err() {
+            // stream is unusable after an error
+            self.pending.clear();
+        }
+        Some(frame)
+    }
+}
+
+impl EvaluationKey {
+    /// Writes evaluation key to `writer` as stream of frames (see `EvaluationKeyStreamWriter`).
+    /// Keys are written in canonical order, ie sorted by level, then exponent.
+    pub fn write_stream<W: Write>(&self, params: &BfvParameters, writer: W) -> Result<W> {
+        let rlk_levels = self.rlks.keys().copied().sorted().collect_vec();
+        let rtg_keys = self
+            .rtgs
+            .keys()
+            .copied()
+            .sorted_by_key(|(exponent, level)| (*level, *exponent))
+            .collect_vec();
+        let exponents = rtg_keys.iter().map(|(exponent, _)| *exponent).collect_vec();
+        let rtg_levels = rtg_keys.iter().map(|(_, level)| *level).collect_vec();
+
+        let mut stream = EvaluationKeyStreamWriter::new_with_galois_exponents(
+            params,
+            writer,
+            &rlk_levels,
+            &rtg_levels,
+            &exponents,
+        )?;
+        for level in rlk_levels.iter() {
+            stream.write_relinearization_key(&self.rlks[level])?;
+        }
+        for key in rtg_keys.iter() {
+            stream.write_galois_key(&self.rtgs[key])?;
+        }
+        stream.finish()
+    }
+
+    /// Reads evaluation key from stream written with `EvaluationKey::write_stream` or
+    /// `EvaluationKeyStreamWriter`
+    pub fn read_stream<R: Read>(params: &BfvParameters, reader: R) -> Result<EvaluationKey> {
+        let mut rlks = HashMap::new();
+        let mut rtgs = HashMap::new();
+        for frame in EvaluationKeyStreamReader::new(params, reader)? {
+            match frame? {
+                EvaluationKeyFrame::Relinearization(rlk) => {
+                    rlks.insert(rlk.level, rlk);
+                }
+                EvaluationKeyFrame::Galois(gk) => {
+                    rtgs.insert((gk.exponent(), gk.level), gk);
+                }
+            }
+        }
+        Ok(EvaluationKey { rlks, rtgs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rot_to_galois_element, SecretKey};
+    use rand::thread_rng;
+
+    #[test]
+    fn evaluation_key_stream_round_trip() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(6, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let mut ek = EvaluationKey::new(
+            &params,
+            &sk,
+            &[0, 1],
+            &[0, 0, 1, 2],
+            &[1, -1, 1, 3],
+            &mut rng,
+        );
+        // galois key that isn't a rotation
+        ek.insert_galois_key(GaloisKey::new(
+            2 * params.degree - 3,
+            &params,
+            1,
+            &sk,
+            &mut rng,
+        ));
+
+        let bytes = ek.write_stream(&params, vec![]).unwrap();
+        let ek_back = EvaluationKey::read_stream(&params, bytes.as_slice()).unwrap();
+        assert_eq!(ek, ek_back);
+
+        // stream is deterministic
+        assert_eq!(bytes, ek_back.write_stream(&params, vec![]).unwrap());
+    }
+
+    #[test]
+    fn evaluation_key_stream_one_key_at_a_time() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(6, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let rtg_indices = [1, 2, -1];
+        let rtg_levels = [0, 1, 2];
+        let mut writer =
+            EvaluationKeyStreamWriter::new(&params, vec![], &[0], &rtg_levels, &rtg_indices)
+                .unwrap();
+        let rlk = RelinearizationKey::new(&params, &sk, 0, &mut rng);
+        writer.write_relinearization_key(&rlk).unwrap();
+        let mut gks = vec![];
+        for (index, level) in rtg_indices.iter().zip(rtg_levels.iter()) {
+            let gk = GaloisKey::new(
+                rot_to_galois_element(*index, params.degree),
+                &params,
+                *level,
+                &sk,
+                &mut rng,
+            );
+            writer.write_galois_key(&gk).unwrap();
+            gks.push(gk);
+        }
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = EvaluationKeyStreamReader::new(&params, bytes.as_slice()).unwrap();
+        assert_eq!(reader.remaining(), 4);
+        assert_eq!(
+            reader.next().unwrap().unwrap(),
+            EvaluationKeyFrame::Relinearization(rlk)
+        );
+        for gk in gks {
+            assert_eq!(
+                reader.next().unwrap().unwrap(),
+                EvaluationKeyFrame::Galois(gk)
+            );
+        }
+        assert!(reader.next().is_none());
+
+        // truncated stream
+        let ek = EvaluationKey::read_stream(&params, &bytes[..bytes.len() - 1]);
+        assert_eq!(ek.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        // stream for different parameters
+        let other_params = BfvParameters::default(3, 1 << 4);
+        let ek = EvaluationKey::read_stream(&other_params, bytes.as_slice());
+        assert_eq!(ek.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn evaluation_key_stream_rejects_malformed_keys() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(6, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let read_frame_key = |key: FrameKeyProto| {
+            let exponent = match &key {
+                FrameKeyProto::Rlk(_) => None,
+                FrameKeyProto::Rtg(gk) => Some(gk.exponent),
+            };
+            let mut bytes = vec![];
+            write_frame(
+                &mut bytes,
+                &EvaluationKeyStreamHeader {
+                    version: STREAM_VERSION,
+                    entries: vec![EvaluationKeyStreamEntry { level: 0, exponent }],
+                },
+            )
+            .unwrap();
+            write_frame(&mut bytes, &EvaluationKeyFrameProto { key: Some(key) }).unwrap();
+            EvaluationKey::read_stream(&params, bytes.as_slice())
+        };
+
+        // keys without key switching key
+        let ek = read_frame_key(FrameKeyProto::Rlk(RelinearizationKeyProto::default()));
+        assert_eq!(ek.unwrap_err().kind(), ErrorKind::InvalidData);
+        let ek = read_frame_key(FrameKeyProto::Rtg(GaloisKeyProto {
+            exponent: 3,
+            ..Default::default()
+        }));
+        assert_eq!(ek.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // truncated polynomial
+        let rlk = RelinearizationKey::new(&params, &sk, 0, &mut rng);
+        let mut rlk_proto = RelinearizationKeyProto::try_from_with_parameters(&rlk, &params);
+        rlk_proto.ksk.as_mut().unwrap().c0s[0].coefficients[0].pop();
+        let ek = read_frame_key(FrameKeyProto::Rlk(rlk_proto));
+        assert_eq!(ek.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // short seed
+        let gk = GaloisKey::new(3, &params, 0, &sk, &mut rng);
+        let mut gk_proto = GaloisKeyProto::try_from_with_parameters(&gk, &params);
+        gk_proto.ksk.as_mut().unwrap().seed.as_mut().unwrap().pop();
+        let ek = read_frame_key(FrameKeyProto::Rtg(gk_proto));
+        assert_eq!(ek.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -1,21 +1,160 @@
 use crate::modulus::Modulus;
 use crate::{mod_inverse_biguint, mod_inverse_biguint_u64};
 use crate::{
-    secret_key::SecretKey, HybridKeySwitchingParameters, Poly, PolyContext, Representation,
+    secret_key::SecretKey, BfvParameters, Ciphertext, HybridKeySwitchingParameters, Poly,
+    PolyContext, PolyType, PublicKey, Representation, ScratchArena,
 };
 use crypto_bigint::rand_core::CryptoRngCore;
 use itertools::{izip, Itertools};
 use ndarray::{azip, s, Array1, Array2, Array3, Axis, IntoNdProducer};
 use num_bigint::{BigUint, ToBigInt};
 use num_traits::{FromPrimitive, One, ToPrimitive};
-use rand::{CryptoRng, SeedableRng};
+use rand::{CryptoRng, RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use std::borrow::Cow;
 use std::default;
 use traits::Ntt;
-struct BVKeySwitchingKey {
-    c0s: Box<[Poly]>,
-    c1s: Box<[Poly]>,
-    seed: <ChaCha8Rng as SeedableRng>::Seed,
+use zeroize::Zeroizing;
+
+/// Key switching algorithm used by relinearization and galois keys.
+///
+/// `Hybrid` divides ciphertext moduli into digits of `alpha` moduli and requires special moduli (see
+/// `BfvParameters::enable_hybrid_key_switching`). `Bv` decomposes polynomial into its RNS limbs and only
+/// requires ciphertext moduli. BV keys have one component per ciphertext modulus and add more noise
+/// (roughly bits in the largest ciphertext modulus), but they avoid extension to QP and for parameters
+/// with few moduli are smaller than hybrid keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeySwitchingStrategy {
+    #[default]
+    Hybrid,
+    Bv,
+}
+
+/// Key switching key of either strategy. Relinearization and galois keys hold `SwitchingKey`.
+#[derive(Debug, PartialEq)]
+pub(crate) enum SwitchingKey {
+    Hybrid(HybridKeySwitchingKey),
+    Bv(BVKeySwitchingKey),
+}
+
+impl SwitchingKey {
+    /// Generates key switching key from `poly` to `sk` for ciphertexts at `level`. `poly` must be in
+    /// context Q at `level` and in `Evaluation` representation.
+    pub(crate) fn new<R: CryptoRng + RngCore>(
+        strategy: KeySwitchingStrategy,
+        params: &BfvParameters,
+        level: usize,
+        poly: &Poly,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> SwitchingKey {
+        match strategy {
+            KeySwitchingStrategy::Hybrid => SwitchingKey::Hybrid(HybridKeySwitchingKey::new(
+                params.hybrid_key_switching_params_at_level(level),
+                poly,
+                sk,
+                &params.poly_ctx(&PolyType::QP, level),
+                params.variance,
+                rng,
+            )),
+            KeySwitchingStrategy::Bv => SwitchingKey::Bv(BVKeySwitchingKey::new(
+                poly,
+                sk,
+                &params.poly_ctx(&PolyType::Q, level),
+                params.variance,
+                rng,
+            )),
+        }
+    }
+
+    pub(crate) fn strategy(&self) -> KeySwitchingStrategy {
+        match self {
+            SwitchingKey::Hybrid(_) => KeySwitchingStrategy::Hybrid,
+            SwitchingKey::Bv(_) => KeySwitchingStrategy::Bv,
+        }
+    }
+
+    /// Key switches `poly` in context Q at `level` and in `Coefficient` representation. Writes the
+    /// results to `c0_out` and `c1_out`, which must be in context Q at `level`, in `Evaluation`
+    /// representation.
+    ///
+    /// Key may have been generated at any level <= `level`. Limbs of key polynomials for ciphertext
+    /// moduli dropped at `level` are ignored.
+    pub(crate) fn switch_into(
+        &self,
+        params: &BfvParameters,
+        level: usize,
+        poly: &Poly,
+        scratch: &ScratchArena,
+        c0_out: &mut Poly,
+        c1_out: &mut Poly,
+    ) {
+        let q_ctx = params.poly_ctx(&PolyType::Q, level);
+        match self {
+            SwitchingKey::Hybrid(ksk) => {
+                let ksk_params = params.hybrid_key_switching_params_at_level(level);
+                let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+                let digits = HybridKeySwitchingKey::decompose(ksk_params, poly, &qp_ctx, scratch);
+                ksk.key_product_into(
+                    ksk_params,
+                    &digits,
+                    &qp_ctx,
+                    &q_ctx,
+                    &params.poly_ctx(&PolyType::SpecialP, level),
+                    scratch,
+                    c0_out,
+                    c1_out,
+                );
+                digits.into_iter().for_each(|d| scratch.give(d));
+            }
+            SwitchingKey::Bv(ksk) => ksk.switch_into(poly, &q_ctx, scratch, c0_out, c1_out),
+        }
+    }
+
+    /// Returns estimated bits of noise added by key switching at `level`
+    pub(crate) fn noise_bits(&self, params: &BfvParameters, level: usize) -> usize {
+        match self {
+            SwitchingKey::Hybrid(_) => params.key_switching_noise_bits(level),
+            SwitchingKey::Bv(_) => params.bv_key_switching_noise_bits(level),
+        }
+    }
+}
+
+/// Returns polynomial of key switching key, generated at a lower level than the current one, with
+/// limbs of ciphertext moduli dropped at the current level removed. Key polynomials consist of limbs
+/// of ciphertext moduli followed by limbs of special moduli, of which the first `q_count` and the last
+/// `p_count` respectively are retained. `key_poly` is borrowed if it has no limbs to drop.
+fn key_poly_at_level<'a>(
+    key_poly: &'a Poly,
+    q_count: usize,
+    p_count: usize,
+    scratch: &ScratchArena,
+) -> Cow<'a, Poly> {
+    let rows = key_poly.coefficients.nrows();
+    debug_assert!(rows >= q_count + p_count);
+    if rows == q_count + p_count {
+        return Cow::Borrowed(key_poly);
+    }
+
+    let mut coefficients = scratch.take((q_count + p_count, key_poly.coefficients.ncols()));
+    coefficients
+        .slice_mut(s![..q_count, ..])
+        .assign(&key_poly.coefficients.slice(s![..q_count, ..]));
+    coefficients
+        .slice_mut(s![q_count.., ..])
+        .assign(&key_poly.coefficients.slice(s![rows - p_count.., ..]));
+    Cow::Owned(Poly::new(coefficients, key_poly.representation.clone()))
+}
+
+/// BV key switching key. Polynomial is decomposed into its RNS limbs, each of which is multiplied
+/// with a component of the key. Components are in context Q and `Evaluation` representation, and
+/// `c1s` are generated from `seed`.
+#[derive(Debug, PartialEq)]
+pub struct BVKeySwitchingKey {
+    pub(crate) c0s: Box<[Poly]>,
+    pub(crate) c1s: Box<[Poly]>,
+    pub(crate) seed: <ChaCha8Rng as SeedableRng>::Seed,
 }
 
 impl BVKeySwitchingKey {
@@ -23,16 +162,20 @@ impl BVKeySwitchingKey {
         poly: &Poly,
         sk: &SecretKey,
         ksk_ctx: &PolyContext<'_>,
+        variance: usize,
         rng: &mut R,
     ) -> BVKeySwitchingKey {
         // check that ciphertext context has more than on moduli, otherwise key switching does not makes sense
-        debug_assert!(ksk_ctx.moduli_count > 1);
+        assert!(
+            ksk_ctx.moduli_count > 1,
+            "BV key switching requires more than one modulus in key switching context"
+        );
 
         // c1s
         let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
         rng.fill_bytes(&mut seed);
         let c1s = Self::generate_c1(ksk_ctx, seed);
-        let c0s = Self::generate_c0(ksk_ctx, poly, &c1s, sk, rng);
+        let c0s = Self::generate_c0(ksk_ctx, poly, &c1s, sk, variance, rng);
 
         BVKeySwitchingKey {
             c0s: c0s.into_boxed_slice(),
@@ -41,48 +184,92 @@ impl BVKeySwitchingKey {
         }
     }
 
+    /// Key switches `poly` in context `ksk_ctx`. Key must have been generated at level of `ksk_ctx` or
+    /// at a lower level.
     pub fn switch(&self, poly: &Poly, ksk_ctx: &PolyContext<'_>) -> (Poly, Poly) {
+        ScratchArena::with_thread_local(|scratch| self.switch_with_scratch(poly, ksk_ctx, scratch))
+    }
+
+    /// Same as `switch` but intermediate polynomials are taken from and returned to `scratch`.
+    /// Output polynomials are taken from `scratch` as well.
+    pub(crate) fn switch_with_scratch(
+        &self,
+        poly: &Poly,
+        ksk_ctx: &PolyContext<'_>,
+        scratch: &ScratchArena,
+    ) -> (Poly, Poly) {
+        let mut c0_out = scratch.take_poly(ksk_ctx, Representation::Evaluation);
+        let mut c1_out = scratch.take_poly(ksk_ctx, Representation::Evaluation);
+        self.switch_into(poly, ksk_ctx, scratch, &mut c0_out, &mut c1_out);
+        (c0_out, c1_out)
+    }
+
+    /// Same as `switch_with_scratch` but writes the results to `c0_out` and `c1_out`, which must be
+    /// in `ksk_ctx`.
+    pub(crate) fn switch_into(
+        &self,
+        poly: &Poly,
+        ksk_ctx: &PolyContext<'_>,
+        scratch: &ScratchArena,
+        c0_out: &mut Poly,
+        c1_out: &mut Poly,
+    ) {
         // TODO: check that poly matches ksk_ctx
-        // And ksk_ctx matches the key
         debug_assert!(poly.representation == Representation::Coefficient);
+        debug_assert!(self.c0s.len() >= ksk_ctx.moduli_count);
 
-        let mut p = ksk_ctx.try_convert_from_u64(
-            poly.coefficients.slice(s![0, ..]).as_slice().unwrap(),
-            Representation::Coefficient,
-        );
-        ksk_ctx.change_representation(&mut p, Representation::Evaluation);
-        let mut c1_out = ksk_ctx.mul(&self.c1s[0], &p);
-        ksk_ctx.mul_assign(&mut p, &self.c0s[0]);
-        let mut c0_out = p;
+        c0_out.coefficients.fill(0);
+        c0_out.representation = Representation::Evaluation;
+        c1_out.coefficients.fill(0);
+        c1_out.representation = Representation::Evaluation;
 
+        let mut p = scratch.take_poly(ksk_ctx, Representation::Coefficient);
+        let mut product = scratch.take_poly(ksk_ctx, Representation::Evaluation);
         izip!(
             self.c0s.iter(),
             self.c1s.iter(),
             poly.coefficients.outer_iter()
         )
-        .skip(1)
         .for_each(|(c0, c1, rests)| {
-            let mut p = ksk_ctx
-                .try_convert_from_u64(rests.as_slice().unwrap(), Representation::Coefficient);
+            let c0 = key_poly_at_level(c0, ksk_ctx.moduli_count, 0, scratch);
+            let c1 = key_poly_at_level(c1, ksk_ctx.moduli_count, 0, scratch);
+
+            // every limb of `p` is set to `rests` reduced modulo the limb's modulus
+            izip!(p.coefficients.outer_iter_mut(), ksk_ctx.iter_moduli_ops()).for_each(
+                |(mut pi, modqi)| {
+                    let pi = pi.as_slice_mut().unwrap();
+                    pi.copy_from_slice(rests.as_slice().unwrap());
+                    modqi.reduce_vec(pi);
+                },
+            );
+            p.representation = Representation::Coefficient;
             ksk_ctx.change_representation(&mut p, Representation::Evaluation);
 
-            ksk_ctx.add_assign(&mut c1_out, &ksk_ctx.mul(c1, &p));
-            ksk_ctx.mul_assign(&mut p, &c0);
-            ksk_ctx.add_assign(&mut c0_out, &p);
-        });
+            ksk_ctx.mul_into(&c1, &p, &mut product);
+            ksk_ctx.add_assign(c1_out, &product);
+            ksk_ctx.mul_into(&c0, &p, &mut product);
+            ksk_ctx.add_assign(c0_out, &product);
 
-        (c0_out, c1_out)
+            [c0, c1].into_iter().for_each(|k| {
+                if let Cow::Owned(k) = k {
+                    scratch.give(k);
+                }
+            });
+        });
+        scratch.give(p);
+        scratch.give(product);
     }
 
     pub fn generate_c1(
         ksk_ctx: &PolyContext<'_>,
         seed: <ChaCha8Rng as SeedableRng>::Seed,
     ) -> Vec<Poly> {
+        // c1s must be independent, hence sample all of them from a single stream
         let mut rng = ChaCha8Rng::from_seed(seed);
         (0..ksk_ctx.moduli_count)
             .into_iter()
             .map(|_| {
-                let mut p = ksk_ctx.random_with_seed(seed);
+                let mut p = ksk_ctx.random(Representation::Coefficient, &mut rng);
                 ksk_ctx.change_representation(&mut p, Representation::Evaluation);
                 p
             })
@@ -94,13 +281,12 @@ impl BVKeySwitchingKey {
         poly: &Poly,
         c1s: &[Poly],
         sk: &SecretKey,
+        variance: usize,
         rng: &mut R,
     ) -> Vec<Poly> {
         debug_assert!(poly.representation == Representation::Evaluation);
 
-        let mut sk =
-            ksk_ctx.try_convert_from_i64_small(&sk.coefficients, Representation::Coefficient);
-        ksk_ctx.change_representation(&mut sk, Representation::Evaluation);
+        let sk = sk.to_poly(ksk_ctx);
 
         // gi = (q/qi) * [(q/qi)^-1]_qi
         let big_q = ksk_ctx.big_q();
@@ -122,7 +308,7 @@ impl BVKeySwitchingKey {
                 // m = gi*poly
                 ksk_ctx.mul_assign(&mut g, &poly);
 
-                let mut e = ksk_ctx.random_gaussian(Representation::Coefficient, 10, rng);
+                let mut e = ksk_ctx.random_gaussian(Representation::Coefficient, variance, rng);
                 ksk_ctx.change_representation(&mut e, Representation::Evaluation);
                 // m + e
                 ksk_ctx.add_assign(&mut e, &g);
@@ -181,6 +367,67 @@ impl HybridKeySwitchingKey {
         }
     }
 
+    /// Generates key switching key for `poly` with public key (`pk0`, `pk1`) of the target secret key
+    /// in place of the secret key itself. Each part is encryption of `g_i * poly` under the public key,
+    /// ie `c0 = g_i * poly + u * pk0 + e0` and `c1 = u * pk1 + e1` with ternary `u` of hamming weight `hw`.
+    ///
+    /// Public key must be in `Evaluation` representation over `qp_ctx`. c1s are not derived from a seed,
+    /// hence the key cannot be seed compressed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_public_key<R: CryptoRng + CryptoRngCore>(
+        ksk_params: &HybridKeySwitchingParameters,
+        poly: &Poly,
+        pk0: &Poly,
+        pk1: &Poly,
+        qp_ctx: &PolyContext<'_>,
+        hw: usize,
+        variance: usize,
+        rng: &mut R,
+    ) -> HybridKeySwitchingKey {
+        debug_assert!(poly.representation == Representation::Evaluation);
+        debug_assert!(pk0.representation == Representation::Evaluation);
+        debug_assert!(pk1.representation == Representation::Evaluation);
+
+        let mut c0s = Vec::with_capacity(ksk_params.g.len());
+        let mut c1s = Vec::with_capacity(ksk_params.g.len());
+        ksk_params.g.iter().for_each(|g_part| {
+            let u = SecretKey::random(qp_ctx.degree, hw, rng).to_poly(qp_ctx);
+
+            // u * pk0 + e0
+            let mut c0 = qp_ctx.random_gaussian(Representation::Coefficient, variance, rng);
+            qp_ctx.change_representation(&mut c0, Representation::Evaluation);
+            qp_ctx.add_assign(&mut c0, &qp_ctx.mul(&u, pk0));
+
+            // u * pk1 + e1
+            let mut c1 = qp_ctx.random_gaussian(Representation::Coefficient, variance, rng);
+            qp_ctx.change_representation(&mut c1, Representation::Evaluation);
+            qp_ctx.add_assign(&mut c1, &qp_ctx.mul(&u, pk1));
+
+            // [c0]_qi += [g * poly]_qi
+            // (`g` vanishes over pi)
+            izip!(
+                qp_ctx.moduli_ops.0.iter(),
+                poly.coefficients.outer_iter(),
+                c0.coefficients.outer_iter_mut(),
+            )
+            .for_each(|(modqi, xi, mut c0qi)| {
+                let mut g_poly = Zeroizing::new(xi.to_vec());
+                let g_u64 = (g_part % modqi.modulus()).to_u64().unwrap();
+                modqi.scalar_mul_mod_fast_vec(&mut g_poly, g_u64);
+                modqi.add_mod_fast_vec(c0qi.as_slice_mut().unwrap(), &g_poly);
+            });
+
+            c0s.push(c0);
+            c1s.push(c1);
+        });
+
+        HybridKeySwitchingKey {
+            seed: None,
+            c0s: c0s.into_boxed_slice(),
+            c1s: c1s.into_boxed_slice(),
+        }
+    }
+
     pub fn switch(
         &self,
         ksk_params: &HybridKeySwitchingParameters,
@@ -189,131 +436,239 @@ impl HybridKeySwitchingKey {
         ksk_ctx: &PolyContext<'_>,
         specialp_ctx: &PolyContext<'_>,
     ) -> (Poly, Poly) {
+        ScratchArena::with_thread_local(|scratch| {
+            self.switch_with_scratch(ksk_params, poly, qp_ctx, ksk_ctx, specialp_ctx, scratch)
+        })
+    }
+
+    /// Same as `switch` but intermediate polynomials are taken from and returned to `scratch`.
+    /// Output polynomials are taken from `scratch` as well.
+    pub(crate) fn switch_with_scratch(
+        &self,
+        ksk_params: &HybridKeySwitchingParameters,
+        poly: &Poly,
+        qp_ctx: &PolyContext<'_>,
+        ksk_ctx: &PolyContext<'_>,
+        specialp_ctx: &PolyContext<'_>,
+        scratch: &ScratchArena,
+    ) -> (Poly, Poly) {
+        let digits = HybridKeySwitchingKey::decompose(ksk_params, poly, qp_ctx, scratch);
+        let res = self.key_product(ksk_params, &digits, qp_ctx, ksk_ctx, specialp_ctx, scratch);
+        digits.into_iter().for_each(|d| scratch.give(d));
+        res
+    }
+
+    /// Divides `poly` into `dnum` parts and switches each part from Qj to QP. Returns the parts
+    /// (ie digits) in `Evaluation` representation.
+    ///
+    /// Digits only depend on `poly`, hence they can be computed once and reused for key switching
+    /// with several keys (see `key_product`).
+    pub(crate) fn decompose(
+        ksk_params: &HybridKeySwitchingParameters,
+        poly: &Poly,
+        qp_ctx: &PolyContext<'_>,
+        scratch: &ScratchArena,
+    ) -> Vec<Poly> {
         // TODO: check poly context
         debug_assert!(poly.representation == Representation::Coefficient);
 
         let alpha = ksk_params.alpha;
 
-        // divide poly into parts and switch them from Qj to QP and key switch
-        let mut c0_out = Poly::placeholder();
-        let mut c1_out = Poly::placeholder();
-        for i in 0..ksk_params.dnum {
-            let mut qp_poly = qp_ctx.zero(Representation::Coefficient);
-
-            let qj_coefficients = {
-                if (i + 1) == ksk_params.dnum {
-                    poly.coefficients.slice(s![(i * alpha).., ..])
-                } else {
-                    poly.coefficients
-                        .slice(s![(i * alpha)..((i + 1) * alpha), ..])
-                }
-            };
-            let mut parts_count = qj_coefficients.shape()[0];
-
-            let mut p_whole_coefficients = PolyContext::approx_switch_crt_basis(
-                &qj_coefficients,
-                &ksk_params.qj_moduli_ops_parts[i],
-                qp_ctx.degree,
-                &ksk_params.qj_hat_inv_modqj_parts[i],
-                &ksk_params.qj_hat_modqpj_parts[i],
-                &ksk_params.qpj_moduli_ops_parts[i],
-            );
+        (0..ksk_params.dnum)
+            .map(|i| {
+                // every row of `qp_poly` is overwritten
+                let mut qp_poly = scratch.take_poly(qp_ctx, Representation::Coefficient);
+
+                let qj_coefficients = {
+                    if (i + 1) == ksk_params.dnum {
+                        poly.coefficients.slice(s![(i * alpha).., ..])
+                    } else {
+                        poly.coefficients
+                            .slice(s![(i * alpha)..((i + 1) * alpha), ..])
+                    }
+                };
+                let parts_count = qj_coefficients.shape()[0];
+
+                let mut p_whole_coefficients =
+                    scratch.take((ksk_params.qpj_moduli_ops_parts[i].len(), qp_ctx.degree));
+                PolyContext::approx_switch_crt_basis_into(
+                    &qj_coefficients,
+                    &ksk_params.qj_moduli_ops_parts[i],
+                    qp_ctx.degree,
+                    &ksk_params.qj_hat_inv_modqj_parts[i],
+                    &ksk_params.qj_hat_modqpj_parts[i],
+                    &ksk_params.qpj_moduli_ops_parts[i],
+                    &mut p_whole_coefficients,
+                );
 
-            // ..p_start
-            izip!(
-                qp_poly
-                    .coefficients
-                    .slice_mut(s![..(i * alpha), ..])
-                    .outer_iter_mut()
-                    .into_producer(),
-                p_whole_coefficients
-                    .slice(s![..(i * alpha), ..])
-                    .outer_iter()
-                    .into_producer()
-            )
-            .for_each(|(mut qpi, pi)| {
-                qpi.as_slice_mut()
-                    .unwrap()
-                    .copy_from_slice(pi.as_slice().unwrap());
-            });
+                // ..p_start
+                izip!(
+                    qp_poly
+                        .coefficients
+                        .slice_mut(s![..(i * alpha), ..])
+                        .outer_iter_mut()
+                        .into_producer(),
+                    p_whole_coefficients
+                        .slice(s![..(i * alpha), ..])
+                        .outer_iter()
+                        .into_producer()
+                )
+                .for_each(|(mut qpi, pi)| {
+                    qpi.as_slice_mut()
+                        .unwrap()
+                        .copy_from_slice(pi.as_slice().unwrap());
+                });
 
-            // p_start..p_start+qj
-            izip!(
-                qp_poly
-                    .coefficients
-                    .slice_mut(s![(i * alpha)..(i * alpha + parts_count), ..])
-                    .outer_iter_mut()
-                    .into_producer(),
-                qj_coefficients.outer_iter().into_producer()
-            )
-            .for_each(|(mut qpi, qj)| {
-                qpi.as_slice_mut()
-                    .unwrap()
-                    .copy_from_slice(qj.as_slice().unwrap());
-            });
+                // p_start..p_start+qj
+                izip!(
+                    qp_poly
+                        .coefficients
+                        .slice_mut(s![(i * alpha)..(i * alpha + parts_count), ..])
+                        .outer_iter_mut()
+                        .into_producer(),
+                    qj_coefficients.outer_iter().into_producer()
+                )
+                .for_each(|(mut qpi, qj)| {
+                    qpi.as_slice_mut()
+                        .unwrap()
+                        .copy_from_slice(qj.as_slice().unwrap());
+                });
 
-            // p_start+qj..
-            izip!(
+                // p_start+qj..
+                izip!(
+                    qp_poly
+                        .coefficients
+                        .slice_mut(s![(i * alpha + parts_count).., ..])
+                        .outer_iter_mut()
+                        .into_producer(),
+                    p_whole_coefficients
+                        .slice(s![i * alpha.., ..])
+                        .outer_iter()
+                        .into_producer()
+                )
+                .for_each(|(mut qpi, pi)| {
+                    qpi.as_slice_mut()
+                        .unwrap()
+                        .copy_from_slice(pi.as_slice().unwrap());
+                });
+
+                scratch.give(Poly::new(p_whole_coefficients, Representation::Unknown));
+
+                qp_ctx.change_representation(&mut qp_poly, Representation::Evaluation);
                 qp_poly
-                    .coefficients
-                    .slice_mut(s![(i * alpha + parts_count).., ..])
-                    .outer_iter_mut()
-                    .into_producer(),
-                p_whole_coefficients
-                    .slice(s![i * alpha.., ..])
-                    .outer_iter()
-                    .into_producer()
-            )
-            .for_each(|(mut qpi, pi)| {
-                qpi.as_slice_mut()
-                    .unwrap()
-                    .copy_from_slice(pi.as_slice().unwrap());
-            });
+            })
+            .collect()
+    }
 
-            qp_ctx.change_representation(&mut qp_poly, Representation::Evaluation);
+    /// Multiplies `digits` of a polynomial (see `decompose`) with the key and switches the result
+    /// from QP to Q. Returns polynomials in `Evaluation` representation, which are taken from
+    /// `scratch`.
+    pub(crate) fn key_product(
+        &self,
+        ksk_params: &HybridKeySwitchingParameters,
+        digits: &[Poly],
+        qp_ctx: &PolyContext<'_>,
+        ksk_ctx: &PolyContext<'_>,
+        specialp_ctx: &PolyContext<'_>,
+        scratch: &ScratchArena,
+    ) -> (Poly, Poly) {
+        let mut c0_out = scratch.take_poly(ksk_ctx, Representation::Evaluation);
+        let mut c1_out = scratch.take_poly(ksk_ctx, Representation::Evaluation);
+        self.key_product_into(
+            ksk_params,
+            digits,
+            qp_ctx,
+            ksk_ctx,
+            specialp_ctx,
+            scratch,
+            &mut c0_out,
+            &mut c1_out,
+        );
+        (c0_out, c1_out)
+    }
 
-            if c1_out.representation == Representation::Unknown {
-                c1_out = qp_ctx.mul(&qp_poly, &self.c1s[i]);
-                qp_ctx.mul_assign(&mut qp_poly, &self.c0s[i]);
-                c0_out = qp_poly;
+    /// Same as `key_product` but writes the results to `c0_out` and `c1_out`, which must be in
+    /// `ksk_ctx`. Products in QP are taken from and returned to `scratch`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn key_product_into(
+        &self,
+        ksk_params: &HybridKeySwitchingParameters,
+        digits: &[Poly],
+        qp_ctx: &PolyContext<'_>,
+        ksk_ctx: &PolyContext<'_>,
+        specialp_ctx: &PolyContext<'_>,
+        scratch: &ScratchArena,
+        c0_out: &mut Poly,
+        c1_out: &mut Poly,
+    ) {
+        debug_assert!(digits.len() == ksk_params.dnum);
+
+        let mut product = scratch.take_poly(qp_ctx, Representation::Evaluation);
+        let mut c0_qp = Poly::placeholder();
+        let mut c1_qp = Poly::placeholder();
+        izip!(digits.iter(), self.c0s.iter(), self.c1s.iter()).for_each(|(qp_poly, c0, c1)| {
+            // key may have been generated at a lower level
+            let c0 =
+                key_poly_at_level(c0, ksk_ctx.moduli_count, specialp_ctx.moduli_count, scratch);
+            let c1 =
+                key_poly_at_level(c1, ksk_ctx.moduli_count, specialp_ctx.moduli_count, scratch);
+            if c1_qp.representation == Representation::Unknown {
+                c1_qp = scratch.take_poly(qp_ctx, Representation::Evaluation);
+                c0_qp = scratch.take_poly(qp_ctx, Representation::Evaluation);
+                qp_ctx.mul_into(qp_poly, &c1, &mut c1_qp);
+                qp_ctx.mul_into(qp_poly, &c0, &mut c0_qp);
             } else {
-                qp_ctx.add_assign(&mut c1_out, &qp_ctx.mul(&qp_poly, &self.c1s[i]));
-                qp_ctx.mul_assign(&mut qp_poly, &self.c0s[i]);
-                qp_ctx.add_assign(&mut c0_out, &qp_poly);
+                qp_ctx.mul_into(qp_poly, &c1, &mut product);
+                qp_ctx.add_assign(&mut c1_qp, &product);
+                qp_ctx.mul_into(qp_poly, &c0, &mut product);
+                qp_ctx.add_assign(&mut c0_qp, &product);
             }
-        }
+            [c0, c1].into_iter().for_each(|k| {
+                if let Cow::Owned(k) = k {
+                    scratch.give(k);
+                }
+            });
+        });
+        scratch.give(product);
 
-        // switch results from QP to Q
-        let c0_out = qp_ctx.approx_mod_down(
-            c0_out,
-            &ksk_ctx,
-            &specialp_ctx,
+        // switch results from QP to Q and return QP buffers to the arena
+        qp_ctx.approx_mod_down_into(
+            &mut c0_qp,
+            ksk_ctx,
+            specialp_ctx,
             &ksk_params.p_hat_inv_modp,
             &ksk_params.p_hat_modq,
             &ksk_params.p_inv_modq,
+            c0_out,
         );
-
-        let c1_out = qp_ctx.approx_mod_down(
-            c1_out,
-            &ksk_ctx,
-            &specialp_ctx,
+        qp_ctx.approx_mod_down_into(
+            &mut c1_qp,
+            ksk_ctx,
+            specialp_ctx,
             &ksk_params.p_hat_inv_modp,
             &ksk_params.p_hat_modq,
             &ksk_params.p_inv_modq,
+            c1_out,
         );
-
-        (c0_out, c1_out)
+        scratch.give(c0_qp);
+        scratch.give(c1_qp);
     }
 
+    /// Version of `generate_c1`, stored with serialized seeded keys. Version 1 samples all c1s from a
+    /// single stream of the seed. Seeded keys without version generated every c1 from the seed alone,
+    /// thus with identical c1s, and cannot be deserialized.
+    pub const SEED_VERSION: u32 = 1;
+
     /// Generates `count` polynomials from the seed and returns them in `Coefficient` representation
     pub fn generate_c1(
         count: usize,
         qp_ctx: &PolyContext<'_>,
         seed: <ChaCha8Rng as SeedableRng>::Seed,
     ) -> Vec<Poly> {
+        // c1s must be independent, hence sample all of them from a single stream
+        let mut rng = ChaCha8Rng::from_seed(seed);
         (0..count)
-            .into_iter()
-            .map(|_| qp_ctx.random_with_seed(seed))
+            .map(|_| qp_ctx.random(Representation::Coefficient, &mut rng))
             .collect_vec()
     }
 
@@ -353,7 +708,7 @@ impl HybridKeySwitchingKey {
                     e.coefficients.outer_iter(),
                 )
                 .for_each(|(modqi, nttqi, xi, mut c0qi, c1qi, eqi)| {
-                    let mut skqi = modqi.reduce_vec_i64_small(&sk.coefficients);
+                    let mut skqi = Zeroizing::new(modqi.reduce_vec_i64_small(&sk.coefficients));
                     nttqi.forward(&mut skqi);
 
                     // [g * poly]_qi
@@ -389,7 +744,7 @@ impl HybridKeySwitchingKey {
                         .unwrap()
                         .copy_from_slice(epi.as_slice().unwrap());
 
-                    let mut skpi = modpi.reduce_vec_i64_small(&sk.coefficients);
+                    let mut skpi = Zeroizing::new(modpi.reduce_vec_i64_small(&sk.coefficients));
                     nttpi.forward(&mut skpi);
                     modpi.mul_mod_fast_vec(&mut skpi, c1pi.as_slice().unwrap());
                     modpi.sub_mod_fast_vec(c0pi.as_slice_mut().unwrap(), &skpi);
@@ -402,6 +757,118 @@ impl HybridKeySwitchingKey {
     }
 }
 
+/// Key switching key from one secret key to another at a given level.
+///
+/// Ciphertext decryptable by the source secret key is switched to a ciphertext of the same
+/// plaintext decryptable only by the target secret key. The key can be generated with either the
+/// target secret key or its `PublicKey` (ie proxy re-encryption), but the source secret key is
+/// always required.
+#[derive(Debug, PartialEq)]
+pub struct KeySwitchingKey {
+    pub(crate) ksk: HybridKeySwitchingKey,
+    pub(crate) level: usize,
+}
+
+impl KeySwitchingKey {
+    /// Generates key switching key from `from` to `to` for ciphertexts at `level`
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        from: &SecretKey,
+        to: &SecretKey,
+        level: usize,
+        rng: &mut R,
+    ) -> KeySwitchingKey {
+        let q_ctx = params.poly_ctx(&PolyType::Q, level);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+
+        let from_poly = from.to_poly(&q_ctx);
+        let ksk = HybridKeySwitchingKey::new(
+            params.hybrid_key_switching_params_at_level(level),
+            &from_poly,
+            to,
+            &qp_ctx,
+            params.variance,
+            rng,
+        );
+
+        KeySwitchingKey { ksk, level }
+    }
+
+    /// Generates key switching key from `from` to secret key of public key `to` for ciphertexts
+    /// at `level`
+    pub fn new_with_public_key<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        from: &SecretKey,
+        to: &PublicKey,
+        level: usize,
+        rng: &mut R,
+    ) -> KeySwitchingKey {
+        let q_ctx = params.poly_ctx(&PolyType::Q, level);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+
+        let from_poly = from.to_poly(&q_ctx);
+        let (pk0, pk1) = to.polys_at_level(params, &PolyType::QP, level);
+        let ksk = HybridKeySwitchingKey::new_with_public_key(
+            params.hybrid_key_switching_params_at_level(level),
+            &from_poly,
+            &pk0,
+            &pk1,
+            &qp_ctx,
+            params.hw,
+            params.variance,
+            rng,
+        );
+
+        KeySwitchingKey { ksk, level }
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn switch(&self, ct: &Ciphertext, params: &BfvParameters) -> Ciphertext {
+        assert!(ct.c.len() == 2);
+        assert!(ct.level == self.level);
+        assert!(ct.poly_type == PolyType::Q);
+
+        let level = self.level;
+        let q_ctx = params.poly_ctx(&PolyType::Q, level);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
+        let specialp_ctx = params.poly_ctx(&PolyType::SpecialP, level);
+
+        let mut c1 = ct.c[1].clone();
+        if c1.representation == Representation::Evaluation {
+            q_ctx.change_representation(&mut c1, Representation::Coefficient);
+        }
+
+        let (mut cs0, mut cs1) = self.ksk.switch(
+            params.hybrid_key_switching_params_at_level(level),
+            &c1,
+            &qp_ctx,
+            &q_ctx,
+            &specialp_ctx,
+        );
+
+        // Key switch returns polynomial in Evaluation form
+        if ct.c[0].representation != cs0.representation {
+            q_ctx.change_representation(&mut cs0, ct.c[0].representation.clone());
+            q_ctx.change_representation(&mut cs1, ct.c[0].representation.clone());
+        }
+
+        q_ctx.add_assign(&mut cs0, &ct.c[0]);
+
+        Ciphertext {
+            c: vec![cs0, cs1],
+            poly_type: PolyType::Q,
+            level,
+            seed: None,
+            noise_estimate: ct
+                .noise_estimate
+                .map(|n| std::cmp::max(n, params.key_switching_noise_bits(level)) + 1),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -419,7 +886,7 @@ mod tests {
         let sk = SecretKey::random(params.degree, params.hw, &mut rng);
 
         let poly = ksk_ctx.random(Representation::Evaluation, &mut rng);
-        let ksk = BVKeySwitchingKey::new(&poly, &sk, &ksk_ctx, &mut rng);
+        let ksk = BVKeySwitchingKey::new(&poly, &sk, &ksk_ctx, params.variance, &mut rng);
 
         let mut other_poly = ksk_ctx.random(Representation::Coefficient, &mut rng);
 
@@ -445,6 +912,34 @@ mod tests {
         });
     }
 
+    #[test]
+    #[should_panic(expected = "BV key switching requires more than one modulus")]
+    fn bv_key_switching_rejects_single_modulus() {
+        let params = BfvParameters::default(1, 1 << 4);
+        let ksk_ctx = params.poly_ctx(&PolyType::Q, 0);
+        let mut rng = thread_rng();
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let poly = ksk_ctx.random(Representation::Evaluation, &mut rng);
+        BVKeySwitchingKey::new(&poly, &sk, &ksk_ctx, params.variance, &mut rng);
+    }
+
+    #[test]
+    fn seeded_c1s_are_independent() {
+        let params = BfvParameters::default(6, 1 << 4);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, 0);
+        let mut rng = thread_rng();
+
+        let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+        rng.fill_bytes(&mut seed);
+        let c1s = HybridKeySwitchingKey::generate_c1(3, &qp_ctx, seed);
+        assert_ne!(c1s[0], c1s[1]);
+        assert_ne!(c1s[1], c1s[2]);
+
+        // c1s are reproducible from the seed
+        assert_eq!(c1s, HybridKeySwitchingKey::generate_c1(3, &qp_ctx, seed));
+    }
+
     #[test]
     fn hybrid_key_switching() {
         let params = BfvParameters::default(5, 1 << 6);
@@ -0,0 +1,397 @@
+use crate::proto::proto::{Keystore as KeystoreProto, SecretKey as SecretKeyProto};
+use crate::{BfvParameters, SecretKey};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use prost::Message;
+use rand::{CryptoRng, RngCore};
+use std::fmt::{Display, Formatter};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use traits::TryFromWithParameters;
+use zeroize::Zeroizing;
+
+const KEYSTORE_VERSION: u32 = 1;
+const SALT_BYTES: usize = 16;
+const NONCE_BYTES: usize = 12;
+
+/// Upper bounds of KDF parameters. Keystore files are untrusted, thus KDF parameters are checked
+/// before deriving the key so that a hostile file cannot demand arbitrary memory or time.
+const MAX_KDF_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_KDF_ITERATIONS: u32 = 64;
+const MAX_KDF_PARALLELISM: u32 = 16;
+
+#[derive(Debug)]
+pub enum KeystoreError {
+    Io(std::io::Error),
+    /// Keystore bytes could not be decoded
+    Malformed,
+    UnsupportedVersion(u32),
+    /// Keystore was created for different `BfvParameters`
+    ParametersMismatch,
+    /// KDF parameters are invalid
+    InvalidKdfParameters,
+    /// Either the passphrase is wrong or the keystore has been tampered with
+    DecryptionFailed,
+}
+
+impl Display for KeystoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeystoreError::Io(e) => write!(f, "keystore io error: {e}"),
+            KeystoreError::Malformed => write!(f, "malformed keystore"),
+            KeystoreError::UnsupportedVersion(v) => {
+                write!(f, "unsupported keystore version {v}")
+            }
+            KeystoreError::ParametersMismatch => {
+                write!(f, "keystore was created for different parameters")
+            }
+            KeystoreError::InvalidKdfParameters => write!(f, "invalid kdf parameters"),
+            KeystoreError::DecryptionFailed => {
+                write!(f, "wrong passphrase or keystore has been tampered with")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeystoreError {}
+
+impl From<std::io::Error> for KeystoreError {
+    fn from(value: std::io::Error) -> Self {
+        KeystoreError::Io(value)
+    }
+}
+
+/// Unencrypted metadata stored alongside encrypted secret key. Metadata is authenticated,
+/// hence can be trusted only after keystore has been successfully decrypted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeystoreMetadata {
+    pub label: String,
+    /// Seconds since unix epoch
+    pub created_at: u64,
+    pub parameters_fingerprint: [u8; 32],
+}
+
+/// Options for creating a keystore.
+///
+/// Defaults to Argon2id with 19 MiB of memory, 2 iterations, and parallelism of 1. Memory is capped
+/// at 1 GiB, iterations at 64 and parallelism at 16.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeystoreOptions {
+    pub label: String,
+    pub kdf_memory_kib: u32,
+    pub kdf_iterations: u32,
+    pub kdf_parallelism: u32,
+}
+
+impl Default for KeystoreOptions {
+    fn default() -> Self {
+        KeystoreOptions {
+            label: String::new(),
+            kdf_memory_kib: 19 * 1024,
+            kdf_iterations: 2,
+            kdf_parallelism: 1,
+        }
+    }
+}
+
+fn check_kdf_parameters(keystore: &KeystoreProto) -> Result<(), KeystoreError> {
+    if keystore.kdf_memory_kib > MAX_KDF_MEMORY_KIB
+        || keystore.kdf_iterations > MAX_KDF_ITERATIONS
+        || keystore.kdf_parallelism > MAX_KDF_PARALLELISM
+    {
+        return Err(KeystoreError::InvalidKdfParameters);
+    }
+    Ok(())
+}
+
+fn derive_key(
+    passphrase: &[u8],
+    keystore: &KeystoreProto,
+) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+    check_kdf_parameters(keystore)?;
+    let params = Params::new(
+        keystore.kdf_memory_kib,
+        keystore.kdf_iterations,
+        keystore.kdf_parallelism,
+        Some(32),
+    )
+    .map_err(|_| KeystoreError::InvalidKdfParameters)?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, &keystore.salt, key.as_mut())
+        .map_err(|_| KeystoreError::InvalidKdfParameters)?;
+    Ok(key)
+}
+
+/// Returns associated data of the keystore, ie all fields except `ciphertext`
+fn associated_data(keystore: &KeystoreProto) -> Vec<u8> {
+    KeystoreProto {
+        ciphertext: vec![],
+        ..keystore.clone()
+    }
+    .encode_to_vec()
+}
+
+fn decode_keystore(bytes: &[u8]) -> Result<KeystoreProto, KeystoreError> {
+    let keystore = KeystoreProto::decode(bytes).map_err(|_| KeystoreError::Malformed)?;
+    if keystore.version != KEYSTORE_VERSION {
+        return Err(KeystoreError::UnsupportedVersion(keystore.version));
+    }
+    if keystore.parameters_fingerprint.len() != 32
+        || keystore.salt.len() != SALT_BYTES
+        || keystore.nonce.len() != NONCE_BYTES
+    {
+        return Err(KeystoreError::Malformed);
+    }
+    check_kdf_parameters(&keystore)?;
+    Ok(keystore)
+}
+
+fn metadata(keystore: &KeystoreProto) -> KeystoreMetadata {
+    KeystoreMetadata {
+        label: keystore.label.clone(),
+        created_at: keystore.created_at,
+        parameters_fingerprint: keystore.parameters_fingerprint.as_slice().try_into().unwrap(),
+    }
+}
+
+/// Returns metadata of keystore without decrypting it
+pub fn read_keystore_metadata(bytes: &[u8]) -> Result<KeystoreMetadata, KeystoreError> {
+    decode_keystore(bytes).map(|keystore| metadata(&keystore))
+}
+
+impl SecretKey {
+    /// Encrypts secret key under key derived from `passphrase` and returns keystore bytes.
+    ///
+    /// Keystore stores fingerprint of `params` and creation metadata, which are authenticated along
+    /// with the encrypted secret key.
+    pub fn to_keystore<R: CryptoRng + RngCore>(
+        &self,
+        params: &BfvParameters,
+        passphrase: &[u8],
+        options: &KeystoreOptions,
+        rng: &mut R,
+    ) -> Result<Vec<u8>, KeystoreError> {
+        let mut salt = vec![0u8; SALT_BYTES];
+        rng.fill_bytes(&mut salt);
+        let mut nonce = vec![0u8; NONCE_BYTES];
+        rng.fill_bytes(&mut nonce);
+
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let mut keystore = KeystoreProto {
+            version: KEYSTORE_VERSION,
+            parameters_fingerprint: params.fingerprint().to_vec(),
+            created_at,
+            label: options.label.clone(),
+            salt,
+            kdf_memory_kib: options.kdf_memory_kib,
+            kdf_iterations: options.kdf_iterations,
+            kdf_parallelism: options.kdf_parallelism,
+            nonce,
+            ciphertext: vec![],
+        };
+
+        let key = derive_key(passphrase, &keystore)?;
+        let sk_bytes =
+            Zeroizing::new(SecretKeyProto::try_from_with_parameters(self, params).encode_to_vec());
+
+        let aad = associated_data(&keystore);
+        keystore.ciphertext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .encrypt(
+                Nonce::from_slice(&keystore.nonce),
+                Payload {
+                    msg: &sk_bytes,
+                    aad: &aad,
+                },
+            )
+            .expect("Encryption failed");
+
+        Ok(keystore.encode_to_vec())
+    }
+
+    /// Decrypts secret key from keystore bytes with key derived from `passphrase`.
+    ///
+    /// Returns error if keystore was created for different `params`, if passphrase is wrong, or
+    /// if the keystore has been tampered with.
+    pub fn from_keystore(
+        bytes: &[u8],
+        params: &BfvParameters,
+        passphrase: &[u8],
+    ) -> Result<(SecretKey, KeystoreMetadata), KeystoreError> {
+        let keystore = decode_keystore(bytes)?;
+        if keystore.parameters_fingerprint != params.fingerprint() {
+            return Err(KeystoreError::ParametersMismatch);
+        }
+
+        let key = derive_key(passphrase, &keystore)?;
+        let aad = associated_data(&keystore);
+        let sk_bytes = Zeroizing::new(
+            ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+                .decrypt(
+                    Nonce::from_slice(&keystore.nonce),
+                    Payload {
+                        msg: &keystore.ciphertext,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|_| KeystoreError::DecryptionFailed)?,
+        );
+
+        let sk_proto =
+            SecretKeyProto::decode(sk_bytes.as_slice()).map_err(|_| KeystoreError::Malformed)?;
+        if sk_proto.coefficients.len() < params.degree.div_ceil(4) {
+            return Err(KeystoreError::Malformed);
+        }
+
+        Ok((
+            SecretKey::try_from_with_parameters(&sk_proto, params),
+            metadata(&keystore),
+        ))
+    }
+
+    /// Writes keystore of secret key to file at `path`. On unix the file is only readable
+    /// and writable by the owner.
+    pub fn save_keystore<P: AsRef<Path>, R: CryptoRng + RngCore>(
+        &self,
+        path: P,
+        params: &BfvParameters,
+        passphrase: &[u8],
+        options: &KeystoreOptions,
+        rng: &mut R,
+    ) -> Result<(), KeystoreError> {
+        let bytes = self.to_keystore(params, passphrase, options, rng)?;
+
+        let mut open_options = OpenOptions::new();
+        open_options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            open_options.mode(0o600);
+        }
+        let mut file = open_options.open(path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Reads and decrypts keystore at `path`. See `SecretKey::from_keystore`.
+    pub fn load_keystore<P: AsRef<Path>>(
+        path: P,
+        params: &BfvParameters,
+        passphrase: &[u8],
+    ) -> Result<(SecretKey, KeystoreMetadata), KeystoreError> {
+        let bytes = std::fs::read(path)?;
+        SecretKey::from_keystore(&bytes, params, passphrase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    fn test_options() -> KeystoreOptions {
+        KeystoreOptions {
+            label: "matching-committee-0".to_string(),
+            kdf_memory_kib: 1024,
+            kdf_iterations: 1,
+            kdf_parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn keystore_round_trip() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let bytes = sk
+            .to_keystore(&params, b"passphrase", &test_options(), &mut rng)
+            .unwrap();
+
+        let metadata = read_keystore_metadata(&bytes).unwrap();
+        assert_eq!(metadata.label, "matching-committee-0");
+        assert_eq!(metadata.parameters_fingerprint, params.fingerprint());
+
+        let (sk_back, metadata_back) =
+            SecretKey::from_keystore(&bytes, &params, b"passphrase").unwrap();
+        assert_eq!(sk, sk_back);
+        assert_eq!(metadata, metadata_back);
+
+        // save and load
+        let path = std::env::temp_dir().join(format!("bfv-keystore-{}", rng.next_u64()));
+        sk.save_keystore(&path, &params, b"passphrase", &test_options(), &mut rng)
+            .unwrap();
+        let (sk_back, _) = SecretKey::load_keystore(&path, &params, b"passphrase").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sk, sk_back);
+    }
+
+    #[test]
+    fn keystore_rejects_invalid_access() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let bytes = sk
+            .to_keystore(&params, b"passphrase", &test_options(), &mut rng)
+            .unwrap();
+
+        // wrong passphrase
+        assert!(matches!(
+            SecretKey::from_keystore(&bytes, &params, b"wrong"),
+            Err(KeystoreError::DecryptionFailed)
+        ));
+
+        // different parameters
+        let other_params = BfvParameters::default(4, 1 << 4);
+        assert!(matches!(
+            SecretKey::from_keystore(&bytes, &other_params, b"passphrase"),
+            Err(KeystoreError::ParametersMismatch)
+        ));
+
+        // tampered metadata
+        let mut keystore = KeystoreProto::decode(bytes.as_slice()).unwrap();
+        keystore.label = "someone-else".to_string();
+        assert!(matches!(
+            SecretKey::from_keystore(&keystore.encode_to_vec(), &params, b"passphrase"),
+            Err(KeystoreError::DecryptionFailed)
+        ));
+
+        // excessive kdf parameters are rejected before deriving the key
+        for (memory, iterations, parallelism) in
+            [(u32::MAX, 1, 1), (1024, u32::MAX, 1), (1024, 1, u32::MAX)]
+        {
+            let mut keystore = KeystoreProto::decode(bytes.as_slice()).unwrap();
+            keystore.kdf_memory_kib = memory;
+            keystore.kdf_iterations = iterations;
+            keystore.kdf_parallelism = parallelism;
+            assert!(matches!(
+                SecretKey::from_keystore(&keystore.encode_to_vec(), &params, b"passphrase"),
+                Err(KeystoreError::InvalidKdfParameters)
+            ));
+        }
+        let options = KeystoreOptions {
+            kdf_memory_kib: MAX_KDF_MEMORY_KIB + 1,
+            ..test_options()
+        };
+        assert!(matches!(
+            sk.to_keystore(&params, b"passphrase", &options, &mut rng),
+            Err(KeystoreError::InvalidKdfParameters)
+        ));
+
+        // garbage
+        assert!(matches!(
+            SecretKey::from_keystore(&[1, 2, 3], &params, b"passphrase"),
+            Err(KeystoreError::Malformed)
+        ));
+    }
+}
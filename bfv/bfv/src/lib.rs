@@ -1,38 +1,60 @@
 mod ciphertext;
+#[cfg(feature = "serialize")]
+mod compact;
 mod evaluation_key;
 mod evaluator;
 mod galois_key;
 mod key_switching_key;
+#[cfg(feature = "serialize")]
+mod key_stream;
 mod modulus;
 mod nb_theory;
 mod ntt;
 mod parameters;
 mod plaintext;
 mod poly;
+mod public_key;
 mod relinearization_key;
+mod scratch;
 mod secret_key;
+mod security;
+#[cfg(feature = "serde")]
+mod serde_support;
 mod utils;
 
+#[cfg(feature = "keystore")]
+mod keystore;
 #[cfg(feature = "serialize")]
 mod proto;
 #[cfg(feature = "serialize")]
 pub use proto::proto::{
-    Ciphertext as CiphertextProto, EvaluationKey as EvaluationKeyProto, SecretKey as SecretKeyProto,
+    Ciphertext as CiphertextProto, EvaluationKey as EvaluationKeyProto,
+    KeySwitchingKey as KeySwitchingKeyProto, PublicKey as PublicKeyProto,
+    SecretKey as SecretKeyProto,
 };
 
 pub use ciphertext::*;
 pub use evaluation_key::*;
 pub use evaluator::*;
+#[cfg(feature = "keystore")]
+pub use keystore::*;
 pub use galois_key::*;
+#[cfg(feature = "serialize")]
+pub use key_stream::*;
 pub use key_switching_key::*;
 pub use modulus::*;
 pub use nb_theory::*;
 pub use ntt::NttOperator;
-pub use parameters::{HybridKeySwitchingParameters, PolyType};
+pub use parameters::{BfvParametersBuilder, HybridKeySwitchingParameters, PolyType};
 pub use plaintext::*;
 pub use poly::{Poly, Representation, Substitution};
+pub use public_key::*;
 pub use relinearization_key::*;
+pub use scratch::*;
 pub use secret_key::*;
+pub use security::*;
+#[cfg(feature = "serde")]
+pub use serde_support::*;
 pub use utils::*;
 
 pub type BfvParameters = parameters::BfvParameters<NttOperator>;
@@ -441,14 +441,14 @@ impl Modulus {
     /// Modulus reduction of i64 values with small bound
     ///
     /// Assumes magnitude of all values is smaller than modulus
+    ///
+    /// Reduction is branch free since it is used to reduce secret key coefficients.
     pub fn reduce_vec_i64_small(&self, a: &[i64]) -> Vec<u64> {
         a.iter()
             .map(|v| {
-                if *v < 0 {
-                    ((self.modulus as i64) + *v) as u64
-                } else {
-                    *v as u64
-                }
+                // mask is all 1s if v is negative, 0 otherwise
+                let mask = *v >> 63;
+                (*v + (mask & self.modulus as i64)) as u64
             })
             .collect()
     }
@@ -7,23 +7,29 @@ pub fn generate_primes_vec(
     polynomial_degree: usize,
     skip_list: &[u64],
 ) -> Vec<u64> {
+    try_generate_primes_vec(sizes, polynomial_degree, skip_list).expect("Not enough primes")
+}
+
+/// Same as `generate_primes_vec` but returns `None` if there are not enough primes of `sizes`
+pub fn try_generate_primes_vec(
+    sizes: &[usize],
+    polynomial_degree: usize,
+    skip_list: &[u64],
+) -> Option<Vec<u64>> {
     let mut primes = vec![];
-    sizes.iter().for_each(|s| {
+    for s in sizes {
         let mut upper_bound = 1u64 << s;
         loop {
-            if let Some(p) = generate_prime(*s, (2 * polynomial_degree) as u64, upper_bound) {
-                if !primes.contains(&p) && !skip_list.contains(&p) {
-                    primes.push(p);
-                    break;
-                } else {
-                    upper_bound = p;
-                }
+            let p = generate_prime(*s, (2 * polynomial_degree) as u64, upper_bound)?;
+            if !primes.contains(&p) && !skip_list.contains(&p) {
+                primes.push(p);
+                break;
             } else {
-                panic!("Not enough primes");
+                upper_bound = p;
             }
         }
-    });
-    primes
+    }
+    Some(primes)
 }
 
 /// Finds prime such that prime % n == 1
@@ -1,15 +1,19 @@
 use crate::modulus::Modulus;
 use crate::nb_theory::generate_primes_vec;
+use crate::poly::poly_context::MAX_APPROX_SWITCH_MODULI;
+use crate::security::SecurityLevel;
 use crate::{mod_inverse_biguint, mod_inverse_biguint_u64};
 use crate::{poly::poly_context::PolyContext, Poly, Representation};
 use itertools::Itertools;
 use ndarray::Array2;
 use num_bigint::BigUint;
 use num_traits::{One, Pow, ToPrimitive};
+use sha2::{Digest, Sha256};
 use std::vec;
 use traits::Ntt;
 
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PolyType {
     Q,
     P,
@@ -90,7 +94,7 @@ pub struct BfvParameters<T: Ntt> {
     pub special_moduli_ops: Option<Vec<Modulus>>,
     pub special_moduli_ntt_ops: Option<Vec<T>>,
     pub dnum: Option<usize>,
-    pub alpha: Option<usize>, // fixed to 3
+    pub alpha: Option<usize>,
 
     // Hybrid key switching key parameters
     pub hybrid_ksk_parameters: Option<Vec<HybridKeySwitchingParameters>>,
@@ -134,7 +138,165 @@ where
             .log2() as usize
     }
 
+    /// Returns a conservative estimate of bits of noise in a freshly encrypted ciphertext.
+    ///
+    /// Error is sampled from centered binomial distribution, hence is bounded (with overwhelming probability)
+    /// by 6 * standard deviation.
+    pub fn fresh_noise_bits(&self) -> usize {
+        fresh_noise_bits(self.variance)
+    }
+
+    /// Returns a conservative estimate of bits of noise in a ciphertext freshly encrypted with a public key.
+    ///
+    /// Noise is e0 + u*e + e1*s, where ternary `u` and `s` have hamming weight hw.
+    pub fn public_key_noise_bits(&self) -> usize {
+        self.fresh_noise_bits() + self.hw.ilog2() as usize + 2
+    }
+
+    /// Returns estimated bits of noise in product of two ciphertexts with `lhs` and `rhs` bits of noise.
+    ///
+    /// Noise in product grows roughly by factor of t * n (expansion factor of ring is bounded by n), plus
+    /// a small constant to account for addition of noises in tensor product.
+    pub fn mul_noise_bits(&self, lhs: usize, rhs: usize) -> usize {
+        mul_noise_bits(lhs, rhs, self.plaintext_modulus_bits(), self.degree)
+    }
+
+    /// Returns estimated bits of noise in product of ciphertext with `noise` bits of noise and a plaintext.
+    pub fn mul_plaintext_noise_bits(&self, noise: usize) -> usize {
+        noise + self.plaintext_modulus_bits() + self.degree.ilog2() as usize
+    }
+
+    /// Returns estimated bits of noise added to a ciphertext at `level` by hybrid key switching.
+    ///
+    /// Key switching adds noise of approximately dnum * n * max(Qj) * e / P. If bits in P are more or less
+    /// equal to bits in max(Qj), then the noise is dominated by the rounding noise of division by P.
+    pub fn key_switching_noise_bits(&self, level: usize) -> usize {
+        let floor = self.mod_down_noise_floor_bits();
+        if self.special_moduli.is_none() || level >= self.max_level {
+            return floor;
+        }
+
+        let alpha = self.alpha.unwrap();
+        let max_qj_bits = self.ciphertext_moduli[..self.q_size - level]
+            .chunks(alpha)
+            .map(|qj| qj.iter().map(|qi| 64 - qi.leading_zeros() as usize).sum::<usize>())
+            .max()
+            .unwrap();
+        let p_bits = self
+            .special_moduli
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|pi| 64 - pi.leading_zeros() as usize)
+            .sum::<usize>();
+        let dnum = self.hybrid_key_switching_params_at_level(level).dnum;
+
+        let noise = (max_qj_bits + self.degree.ilog2() as usize + dnum.ilog2() as usize + 6)
+            .saturating_sub(p_bits);
+        std::cmp::max(noise, floor)
+    }
+
+    /// Returns estimated bits of noise added to a ciphertext at `level` by BV key switching.
+    ///
+    /// BV key switching adds noise of approximately l * n * max(qi) * e, where l is no. of ciphertext
+    /// moduli at `level`. Unlike hybrid key switching the noise is not divided by special modulus.
+    pub fn bv_key_switching_noise_bits(&self, level: usize) -> usize {
+        let moduli = &self.ciphertext_moduli[..self.q_size - level];
+        let max_qi_bits = moduli
+            .iter()
+            .map(|qi| 64 - qi.leading_zeros() as usize)
+            .max()
+            .unwrap();
+
+        let noise = max_qi_bits + self.degree.ilog2() as usize + moduli.len().ilog2() as usize + 6;
+        std::cmp::max(noise, self.mod_down_noise_floor_bits())
+    }
+
+    /// Returns bits of noise introduced by rounding in `mod_down_next`.
+    ///
+    /// Rounding error of each ciphertext polynomial is < 1 and is multiplied by secret key
+    /// polynomial with hamming weight hw.
+    pub fn mod_down_noise_floor_bits(&self) -> usize {
+        self.hw.ilog2() as usize + 2
+    }
+
+    /// Returns estimated bits of noise in a ciphertext with `noise` bits of noise at `level` after
+    /// `mod_down_next`.
+    ///
+    /// `mod_down_next` divides noise by the last modulus at `level` and adds rounding noise.
+    pub fn mod_down_noise_bits(&self, noise: usize, level: usize) -> usize {
+        let lastq = self.ciphertext_moduli[self.q_size - level - 1];
+        let lastq_bits = 64 - lastq.leading_zeros() as usize;
+        std::cmp::max(
+            noise.saturating_sub(lastq_bits),
+            self.mod_down_noise_floor_bits(),
+        ) + 1
+    }
+
+    /// Returns noise budget in bits of a ciphertext at `level` with `noise` bits of noise.
+    ///
+    /// Ciphertext at level decrypts correctly as long as noise < Ql / 2t. Thus remaining noise
+    /// budget is log(Ql) - log(t) - 1 - noise. Negative budget implies ciphertext will not decrypt correctly.
+    pub fn noise_budget_bits(&self, level: usize, noise: usize) -> isize {
+        self.ql[level].bits() as isize
+            - self.plaintext_modulus_bits() as isize
+            - 1
+            - noise as isize
+    }
+
+    fn plaintext_modulus_bits(&self) -> usize {
+        64 - self.plaintext_modulus.leading_zeros() as usize
+    }
+
+    /// Returns bits in Q * P, where Q is ciphertext modulus and P is special modulus used for
+    /// hybrid key switching. Extension modulus used for multiplication is not part of any key, hence
+    /// does not affect security.
+    pub fn log_qp(&self) -> usize {
+        let mut qp = self.ql[0].clone();
+        if let Some(special_moduli) = self.special_moduli.as_ref() {
+            special_moduli.iter().for_each(|pj| qp *= *pj);
+        }
+        qp.bits() as usize
+    }
+
+    /// Returns security level of parameters as per HE standard. Returns `None` if parameters
+    /// do not achieve even 128 bits of security.
+    pub fn security_level(&self) -> Option<SecurityLevel> {
+        SecurityLevel::estimate(self.degree, self.log_qp())
+    }
+
+    /// Returns SHA-256 fingerprint of parameters that keys and ciphertexts are bound to: degree,
+    /// plaintext modulus, ciphertext moduli, special moduli, digit size (ie alpha) of hybrid key switching,
+    /// and secret key and error distributions.
+    ///
+    /// Extension moduli are derived from ciphertext moduli, hence are not included. Fingerprints of v1
+    /// did not include digit size and differ from fingerprints of same parameters now.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"bfv-parameters-v2");
+        hasher.update((self.degree as u64).to_le_bytes());
+        hasher.update(self.plaintext_modulus.to_le_bytes());
+        hasher.update((self.ciphertext_moduli.len() as u64).to_le_bytes());
+        self.ciphertext_moduli
+            .iter()
+            .for_each(|qi| hasher.update(qi.to_le_bytes()));
+
+        let special_moduli = self.special_moduli.as_deref().unwrap_or(&[]);
+        hasher.update((special_moduli.len() as u64).to_le_bytes());
+        special_moduli
+            .iter()
+            .for_each(|pj| hasher.update(pj.to_le_bytes()));
+        hasher.update((self.alpha.unwrap_or(0) as u64).to_le_bytes());
+
+        hasher.update((self.hw as u64).to_le_bytes());
+        hasher.update((self.variance as u64).to_le_bytes());
+        hasher.finalize().into()
+    }
+
     /// creates new bfv parameteres with necessary values
+    ///
+    /// Parameters are not checked for security. Use `security_level` to check them, or
+    /// `BfvParametersBuilder` to choose secure parameters.
     pub fn new(
         ciphertext_moduli_sizes: &[usize],
         plaintext_modulus: u64,
@@ -530,7 +692,7 @@ where
             q_size,
             p_size,
 
-            variance: 10,
+            variance: DEFAULT_VARIANCE,
             hw,
 
             plaintext_modulus,
@@ -596,9 +758,31 @@ where
         self.hw = hw;
     }
 
-    pub fn enable_hybrid_key_switching(&mut self, specialp_bits: &[usize; 3]) {
-        const ALPHA: usize = 3;
-        let dnum = (self.ciphertext_moduli.len() as f64 / ALPHA as f64).ceil() as usize;
+    /// Enables hybrid key switching with special moduli of sizes `specialp_bits`. Digit size (ie alpha)
+    /// is set to no. of special moduli.
+    ///
+    /// See `enable_hybrid_key_switching_with_alpha`.
+    pub fn enable_hybrid_key_switching(&mut self, specialp_bits: &[usize]) {
+        self.enable_hybrid_key_switching_with_alpha(specialp_bits, specialp_bits.len());
+    }
+
+    /// Enables hybrid key switching with special moduli of sizes `specialp_bits` and digits of `alpha`
+    /// ciphertext moduli each. Ciphertext moduli are divided into dnum = ceil(moduli count / alpha) digits.
+    ///
+    /// Larger alpha results in fewer digits, thus smaller keys and faster key switching, but requires
+    /// more bits in special modulus P. Since noise of key switching is dominated by max(Qj) / P, this function
+    /// panics if bits in P are less than bits in the largest digit Qj. It also panics if alpha or no. of
+    /// special moduli exceed 64, the max. no. of moduli basis conversions support.
+    pub fn enable_hybrid_key_switching_with_alpha(
+        &mut self,
+        specialp_bits: &[usize],
+        alpha: usize,
+    ) {
+        if let Err(e) = self.check_hybrid_key_switching(specialp_bits, alpha) {
+            panic!("{}", e);
+        }
+
+        let dnum = self.ciphertext_moduli.len().div_ceil(alpha);
         let special_moduli =
             generate_primes_vec(specialp_bits, self.degree, &self.ciphertext_moduli);
         let special_moduli_ops = special_moduli
@@ -611,7 +795,7 @@ where
             .collect_vec();
 
         self.special_moduli = Some(special_moduli);
-        self.alpha = Some(ALPHA);
+        self.alpha = Some(alpha);
         self.dnum = Some(dnum);
         self.special_moduli_ntt_ops = Some(special_moduli_ntt_ops);
         self.special_moduli_ops = Some(special_moduli_ops);
@@ -622,13 +806,55 @@ where
             .map(|level| {
                 let ksk_ctx = self.poly_ctx(&PolyType::Q, level);
                 let specialp_ctx = self.poly_ctx(&PolyType::SpecialP, level);
-                HybridKeySwitchingParameters::new(&ksk_ctx, &specialp_ctx, ALPHA)
+                HybridKeySwitchingParameters::new(&ksk_ctx, &specialp_ctx, alpha)
             })
             .collect_vec();
 
         self.hybrid_ksk_parameters = Some(params);
     }
 
+    /// Checks arguments of `enable_hybrid_key_switching_with_alpha`
+    pub(crate) fn check_hybrid_key_switching(
+        &self,
+        specialp_bits: &[usize],
+        alpha: usize,
+    ) -> Result<(), String> {
+        if specialp_bits.is_empty() {
+            return Err("At least 1 special modulus is required".into());
+        }
+        if specialp_bits.len() > MAX_APPROX_SWITCH_MODULI {
+            return Err(format!(
+                "At most {} special moduli are supported",
+                MAX_APPROX_SWITCH_MODULI
+            ));
+        }
+        if alpha == 0 || alpha > MAX_APPROX_SWITCH_MODULI {
+            return Err(format!(
+                "Digit size must be between 1 and {}",
+                MAX_APPROX_SWITCH_MODULI
+            ));
+        }
+
+        let max_qj_bits = self
+            .ciphertext_moduli
+            .chunks(alpha)
+            .map(|qj| {
+                qj.iter()
+                    .map(|qi| 64 - qi.leading_zeros() as usize)
+                    .sum::<usize>()
+            })
+            .max()
+            .unwrap();
+        let p_bits = specialp_bits.iter().sum::<usize>();
+        if p_bits < max_qj_bits {
+            return Err(format!(
+                "Special moduli have {} bits but largest digit with alpha {} has {} bits",
+                p_bits, alpha, max_qj_bits
+            ));
+        }
+        Ok(())
+    }
+
     pub fn poly_ctx(&self, poly_type: &PolyType, level: usize) -> PolyContext<'_, T> {
         let level_index = self.q_size - level;
         match poly_type {
@@ -672,7 +898,11 @@ where
                             .as_slice(),
                         &[],
                     ),
-                    moduli_count: self.alpha.expect("SpecialP missing"),
+                    moduli_count: self
+                        .special_moduli_ops
+                        .as_ref()
+                        .expect("SpecialP missing")
+                        .len(),
                     degree: self.degree,
                 };
                 tmp
@@ -687,7 +917,8 @@ where
                         &self.ciphertext_ntt_ops[..level_index],
                         &self.special_moduli_ntt_ops.as_ref().expect("QP missing"),
                     ),
-                    moduli_count: level_index + self.alpha.expect("QP missing"),
+                    moduli_count: level_index
+                        + self.special_moduli_ops.as_ref().expect("QP missing").len(),
                     degree: self.degree,
                 };
                 tmp
@@ -695,6 +926,32 @@ where
         }
     }
 
+    /// Returns coefficients of plaintext polynomial with SIMD `slots`, ie SIMD encoding of `slots`
+    /// without scaling. Slots must be smaller than plaintext modulus. Missing slots are 0.
+    pub fn slots_to_coefficients_plain(&self, slots: &[u64]) -> Vec<u64> {
+        assert!(slots.len() <= self.degree);
+
+        let mut m = vec![0u64; self.degree];
+        slots.iter().enumerate().for_each(|(i, v)| {
+            m[self.matrix_reps_index_map[i]] = *v;
+        });
+        self.plaintext_ntt_op.backward(&mut m);
+        m
+    }
+
+    /// Returns SIMD slots of plaintext polynomial with `coefficients`. Inverse of
+    /// `slots_to_coefficients_plain`.
+    pub fn coefficients_to_slots_plain(&self, coefficients: &[u64]) -> Vec<u64> {
+        assert!(coefficients.len() <= self.degree);
+
+        let mut m = coefficients.to_vec();
+        m.resize(self.degree, 0);
+        self.plaintext_ntt_op.forward(&mut m);
+        (0..self.degree)
+            .map(|i| m[self.matrix_reps_index_map[i]])
+            .collect()
+    }
+
     pub fn hybrid_key_switching_params_at_level(
         &self,
         level: usize,
@@ -712,6 +969,207 @@ where
     }
 }
 
+/// Variance of error distribution of `BfvParameters::new`
+const DEFAULT_VARIANCE: usize = 10;
+
+/// See `BfvParameters::fresh_noise_bits`
+fn fresh_noise_bits(variance: usize) -> usize {
+    (6.0 * (variance as f64).sqrt()).log2().ceil() as usize + 1
+}
+
+/// See `BfvParameters::mul_noise_bits`
+fn mul_noise_bits(lhs: usize, rhs: usize, plaintext_modulus_bits: usize, degree: usize) -> usize {
+    std::cmp::max(lhs, rhs) + plaintext_modulus_bits + degree.ilog2() as usize + 2
+}
+
+/// Builder for `BfvParameters` that chooses polynomial degree and moduli chain for target
+/// security level, plaintext modulus and multiplicative depth.
+///
+/// Builder picks the smallest polynomial degree for which ciphertext moduli chain supporting `depth`
+/// multiplications, along with special moduli for hybrid key switching, fits within the bound of
+/// HE standard for the security level. `build` panics if no such degree exists or if degree set via `with_degree`
+/// is insecure, unless `insecure_for_testing` is set.
+#[derive(Debug, Clone)]
+pub struct BfvParametersBuilder {
+    plaintext_modulus: u64,
+    security_level: SecurityLevel,
+    depth: usize,
+    degree: Option<usize>,
+    min_degree: usize,
+    alpha: usize,
+    special_moduli_count: usize,
+    insecure_for_testing: bool,
+}
+
+impl BfvParametersBuilder {
+    const MAX_MODULUS_BITS: usize = 60;
+    const MIN_DEGREE: usize = 1024;
+    const MAX_DEGREE: usize = 32768;
+
+    pub fn new() -> BfvParametersBuilder {
+        BfvParametersBuilder {
+            plaintext_modulus: 65537,
+            security_level: SecurityLevel::default(),
+            depth: 1,
+            degree: None,
+            min_degree: BfvParametersBuilder::MIN_DEGREE,
+            alpha: 3,
+            special_moduli_count: 3,
+            insecure_for_testing: false,
+        }
+    }
+
+    pub fn with_plaintext_modulus(mut self, plaintext_modulus: u64) -> Self {
+        self.plaintext_modulus = plaintext_modulus;
+        self
+    }
+
+    pub fn with_security_level(mut self, security_level: SecurityLevel) -> Self {
+        self.security_level = security_level;
+        self
+    }
+
+    /// Sets number of sequential ciphertext-ciphertext multiplications parameters must support.
+    pub fn with_multiplicative_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Sets minimum polynomial degree (ie minimum no. of slots).
+    pub fn with_min_degree(mut self, min_degree: usize) -> Self {
+        assert!(min_degree.is_power_of_two());
+        self.min_degree = min_degree;
+        self
+    }
+
+    /// Fixes polynomial degree instead of letting the builder choose one.
+    pub fn with_degree(mut self, degree: usize) -> Self {
+        assert!(degree.is_power_of_two());
+        self.degree = Some(degree);
+        self
+    }
+
+    /// Sets no. of ciphertext moduli in a digit of hybrid key switching (ie alpha) and no. of special
+    /// moduli. Defaults to 3 and 3.
+    ///
+    /// Larger digits result in smaller evaluation keys and faster key switching, but require a larger
+    /// special modulus, which increases log(QP) and may force a larger polynomial degree.
+    pub fn with_hybrid_key_switching(mut self, alpha: usize, special_moduli_count: usize) -> Self {
+        assert!(alpha > 0 && special_moduli_count > 0);
+        self.alpha = alpha;
+        self.special_moduli_count = special_moduli_count;
+        self
+    }
+
+    /// Allows parameters that do not achieve the security level. Only use this for testing.
+    pub fn insecure_for_testing(mut self, insecure_for_testing: bool) -> Self {
+        self.insecure_for_testing = insecure_for_testing;
+        self
+    }
+
+    /// Returns sizes of ciphertext moduli that support `depth` multiplications for `degree`.
+    ///
+    /// Noise is estimated the same way as `BfvParameters::fresh_noise_bits` and
+    /// `BfvParameters::mul_noise_bits`. Decryption requires log(Q) > noise + log(t) + 1.
+    pub fn ciphertext_moduli_sizes(&self, degree: usize) -> Vec<usize> {
+        let t_bits = 64 - self.plaintext_modulus.leading_zeros() as usize;
+        let noise_bits = (0..self.depth).fold(fresh_noise_bits(DEFAULT_VARIANCE), |noise, _| {
+            mul_noise_bits(noise, noise, t_bits, degree)
+        });
+        let q_bits = noise_bits + t_bits + 1;
+
+        // At least 2 moduli are required for relinearization keys to exist
+        let moduli_count =
+            std::cmp::max(2, q_bits.div_ceil(BfvParametersBuilder::MAX_MODULUS_BITS));
+        let size = q_bits.div_ceil(moduli_count);
+        // Moduli must be big enough to have primes that are 1 mod 2n
+        let size = std::cmp::max(size, degree.ilog2() as usize + 10);
+        vec![size; moduli_count]
+    }
+
+    /// Returns sizes of special moduli for `degree`.
+    ///
+    /// Special moduli are at least as big as the largest ciphertext modulus and together have at least
+    /// as many bits as the largest digit.
+    pub fn special_moduli_sizes(&self, degree: usize) -> Vec<usize> {
+        let sizes = self.ciphertext_moduli_sizes(degree);
+        let max_digit_bits = sizes
+            .chunks(self.alpha)
+            .map(|qj| qj.iter().sum::<usize>())
+            .max()
+            .unwrap();
+        let size = std::cmp::max(
+            max_digit_bits.div_ceil(self.special_moduli_count),
+            *sizes.iter().max().unwrap(),
+        );
+        assert!(
+            size <= BfvParametersBuilder::MAX_MODULUS_BITS,
+            "{} special moduli cannot cover digits of {} ciphertext moduli",
+            self.special_moduli_count,
+            self.alpha
+        );
+        vec![size; self.special_moduli_count]
+    }
+
+    /// Returns bits in Q * P of parameters built for `degree`.
+    fn log_qp(&self, degree: usize) -> usize {
+        self.ciphertext_moduli_sizes(degree).iter().sum::<usize>()
+            + self.special_moduli_sizes(degree).iter().sum::<usize>()
+    }
+
+    /// Returns polynomial degree parameters will be built for.
+    pub fn degree(&self) -> usize {
+        if let Some(degree) = self.degree {
+            return degree;
+        }
+
+        let mut degree = std::cmp::max(self.min_degree, BfvParametersBuilder::MIN_DEGREE);
+        while degree <= BfvParametersBuilder::MAX_DEGREE {
+            if SecurityLevel::estimate(degree, self.log_qp(degree))
+                .is_some_and(|level| level >= self.security_level)
+            {
+                return degree;
+            }
+            degree *= 2;
+        }
+
+        assert!(
+            self.insecure_for_testing,
+            "No polynomial degree <= {} supports multiplicative depth {} at {} bits of security",
+            BfvParametersBuilder::MAX_DEGREE,
+            self.depth,
+            self.security_level.bits()
+        );
+        BfvParametersBuilder::MAX_DEGREE
+    }
+
+    pub fn build<T: Ntt>(&self) -> BfvParameters<T> {
+        let degree = self.degree();
+        let sizes = self.ciphertext_moduli_sizes(degree);
+
+        let security_level = SecurityLevel::estimate(degree, self.log_qp(degree));
+        let is_secure = security_level.is_some_and(|level| level >= self.security_level);
+        assert!(
+            is_secure || self.insecure_for_testing,
+            "Degree {} with log(QP) {} does not achieve {} bits of security. Set `insecure_for_testing` if this is intended.",
+            degree,
+            self.log_qp(degree),
+            self.security_level.bits()
+        );
+
+        let mut params = BfvParameters::new(&sizes, self.plaintext_modulus, degree);
+        params
+            .enable_hybrid_key_switching_with_alpha(&self.special_moduli_sizes(degree), self.alpha);
+        params
+    }
+}
+
+impl Default for BfvParametersBuilder {
+    fn default() -> Self {
+        BfvParametersBuilder::new()
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct HybridKeySwitchingParameters {
     pub(crate) dnum: usize,
@@ -846,7 +1304,11 @@ impl HybridKeySwitchingParameters {
                 p_hat_modq.push(((&p / modpi.modulus()) % modqj.modulus()).to_u64().unwrap());
             });
         });
-        let p_hat_modq = Array2::from_shape_vec((ksk_ctx.moduli_count, alpha), p_hat_modq).unwrap();
+        let p_hat_modq = Array2::from_shape_vec(
+            (ksk_ctx.moduli_count, specialp_ctx.moduli_count),
+            p_hat_modq,
+        )
+        .unwrap();
         let mut p_inv_modq = vec![];
         // Precompute for dividing values in basis Q by P (approx_mod_down)
         ksk_ctx.iter_moduli_ops().for_each(|modqi| {
@@ -875,11 +1337,151 @@ impl HybridKeySwitchingParameters {
             p_inv_modq,
         }
     }
+
+    /// Returns no. of digits ciphertext moduli are divided into
+    pub fn dnum(&self) -> usize {
+        self.dnum
+    }
+
+    /// Returns no. of ciphertext moduli in a digit. Last digit may have fewer moduli.
+    pub fn alpha(&self) -> usize {
+        self.alpha
+    }
+
+    /// Returns no. of special moduli
+    pub fn special_moduli_count(&self) -> usize {
+        self.p_hat_inv_modp.len()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::BfvParameters;
+    use super::BfvParametersBuilder;
+    use crate::{BfvParameters, Encoding, EvaluationKey, Evaluator, SecretKey, SecurityLevel};
+    use rand::thread_rng;
+
+    #[test]
+    fn builder_chooses_secure_parameters() {
+        for (depth, security_level) in [
+            (1, SecurityLevel::Bits128),
+            (5, SecurityLevel::Bits128),
+            (5, SecurityLevel::Bits256),
+            (10, SecurityLevel::Bits128),
+        ] {
+            let builder = BfvParametersBuilder::new()
+                .with_multiplicative_depth(depth)
+                .with_security_level(security_level);
+            let degree = builder.degree();
+            assert!(builder.log_qp(degree) <= security_level.max_log_qp(degree).unwrap());
+            // smaller degree must not be secure
+            if degree > 1024 {
+                assert!(
+                    builder.log_qp(degree / 2) > security_level.max_log_qp(degree / 2).unwrap()
+                );
+            }
+        }
+
+        let params: BfvParameters = BfvParametersBuilder::new()
+            .with_multiplicative_depth(1)
+            .build();
+        assert!(params.security_level().unwrap() >= SecurityLevel::Bits128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn builder_refuses_insecure_parameters() {
+        let _: BfvParameters = BfvParametersBuilder::new().with_degree(1 << 4).build();
+    }
+
+    #[test]
+    fn builder_parameters_support_depth() {
+        let mut rng = thread_rng();
+        let depth = 4;
+        let params: BfvParameters = BfvParametersBuilder::new()
+            .with_multiplicative_depth(depth)
+            .with_degree(1 << 4)
+            .insecure_for_testing(true)
+            .build();
+        assert!(params.security_level().is_none());
+
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let pt = evaluator.plaintext_encode(&m, Encoding::default());
+        let ct = evaluator.encrypt(&sk, &pt, &mut rng);
+
+        let mut res_ct = ct.clone();
+        let mut res_m = m.clone();
+        for _ in 0..depth {
+            res_ct = evaluator.relinearize(&evaluator.mul(&res_ct, &res_ct), &ek);
+            let tmp = res_m.clone();
+            evaluator
+                .params()
+                .plaintext_modulus_op
+                .mul_mod_fast_vec(&mut res_m, &tmp);
+        }
+
+        let rm = evaluator.plaintext_decode(&evaluator.decrypt(&sk, &res_ct), Encoding::default());
+        assert_eq!(rm, res_m);
+    }
+
+    #[test]
+    fn hybrid_key_switching_with_configurable_alpha() {
+        let mut rng = thread_rng();
+        for (alpha, specialp_bits) in [(1, vec![50]), (2, vec![50, 50]), (4, vec![60, 60, 60, 60])]
+        {
+            let mut params = BfvParameters::new(&[50; 5], 65537, 1 << 4);
+            params.enable_hybrid_key_switching_with_alpha(&specialp_bits, alpha);
+
+            let ksk_params = params.hybrid_key_switching_params_at_level(0);
+            assert_eq!(ksk_params.alpha(), alpha);
+            assert_eq!(ksk_params.dnum(), 5usize.div_ceil(alpha));
+            assert_eq!(ksk_params.special_moduli_count(), specialp_bits.len());
+
+            let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+            let ek = EvaluationKey::new(&params, &sk, &[0], &[0, 0], &[1, -1], &mut rng);
+
+            let m = params
+                .plaintext_modulus_op
+                .random_vec(params.degree, &mut rng);
+            let mut m_sq = m.clone();
+            params.plaintext_modulus_op.mul_mod_fast_vec(&mut m_sq, &m);
+            let evaluator = Evaluator::new(params);
+            let ct = evaluator.encrypt(
+                &sk,
+                &evaluator.plaintext_encode(&m, Encoding::default()),
+                &mut rng,
+            );
+
+            let ct_sq = evaluator.relinearize(&evaluator.mul(&ct, &ct), &ek);
+            let res =
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_sq), Encoding::default());
+            assert_eq!(res, m_sq);
+
+            let ct_rot = evaluator.rotate(&evaluator.rotate(&ct, 1, &ek), -1, &ek);
+            let res =
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_rot), Encoding::default());
+            assert_eq!(res, m);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn hybrid_key_switching_refuses_small_special_modulus() {
+        let mut params: BfvParameters = BfvParameters::new(&[50; 4], 65537, 1 << 4);
+        params.enable_hybrid_key_switching_with_alpha(&[50, 50], 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn hybrid_key_switching_refuses_large_alpha() {
+        let mut params: BfvParameters = BfvParameters::new(&[50; 4], 65537, 1 << 4);
+        params.enable_hybrid_key_switching_with_alpha(&[60; 60], MAX_APPROX_SWITCH_MODULI + 1);
+    }
 
     #[test]
     fn trial() {
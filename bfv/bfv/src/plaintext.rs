@@ -6,12 +6,14 @@ use num_traits::{AsPrimitive, FromPrimitive, Unsigned, Zero};
 use traits::{Ntt, TryDecodingWithParameters, TryEncodingWithParameters};
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EncodingType {
     Simd,
     Poly,
 }
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PolyCache {
     /// Supports scalar multiplications
     Mul(PolyType),
@@ -25,6 +27,7 @@ pub enum PolyCache {
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Encoding {
     pub(crate) encoding_type: EncodingType,
     pub(crate) poly_cache: PolyCache,
@@ -39,6 +42,16 @@ impl Encoding {
             level,
         }
     }
+
+    /// Coefficient encoding, ie message values are coefficients of plaintext polynomial. Products
+    /// of coefficient encoded plaintexts and ciphertexts are negacyclic convolutions of messages.
+    pub fn poly(level: usize, poly_cache: PolyCache) -> Encoding {
+        Encoding {
+            encoding_type: EncodingType::Poly,
+            poly_cache,
+            level,
+        }
+    }
 }
 
 impl Default for Encoding {
@@ -66,22 +79,27 @@ impl Plaintext {
     pub fn encode(m: &[u64], params: &BfvParameters, encoding: Encoding) -> Plaintext {
         assert!(m.len() <= params.degree);
 
-        let mut m1 = vec![0u64; params.degree];
         let mut m = m.to_vec();
+        params.plaintext_modulus_op.reduce_vec(&mut m);
 
-        m.iter().enumerate().for_each(|(i, v)| {
-            if encoding.encoding_type == EncodingType::Simd {
-                m1[params.matrix_reps_index_map[i]] = *v;
-            } else {
-                m1[i] = *v;
+        let m1 = match encoding.encoding_type {
+            EncodingType::Simd => params.slots_to_coefficients_plain(&m),
+            EncodingType::Poly => {
+                m.resize(params.degree, 0);
+                m
             }
-        });
-        params.plaintext_modulus_op.reduce_vec(&mut m1);
+        };
 
-        if encoding.encoding_type == EncodingType::Simd {
-            params.plaintext_ntt_op.backward(&mut m1);
-        }
+        Plaintext::from_coefficients(m1, params, encoding)
+    }
 
+    /// Creates plaintext from polynomial coefficients `m1` of already encoded message and caches
+    /// polynomials as per `encoding`.
+    pub(crate) fn from_coefficients(
+        m1: Vec<u64>,
+        params: &BfvParameters,
+        encoding: Encoding,
+    ) -> Plaintext {
         // convert m to polynomial with poly context at specific level
         let (mul_poly, add_sub_poly) = {
             match &encoding.poly_cache {
@@ -126,21 +144,12 @@ impl Plaintext {
     ) -> Vec<T> {
         assert!(self.encoding.is_none());
 
-        let mut m1 = self.m.clone();
-        if encoding.encoding_type == EncodingType::Simd {
-            params.plaintext_ntt_op.forward(&mut m1);
-        }
-
-        let mut m = vec![T::zero(); params.degree];
-        for i in (0..params.degree) {
-            if encoding.encoding_type == EncodingType::Simd {
-                m[i] = T::from_u64(m1[params.matrix_reps_index_map[i]]).unwrap();
-            } else {
-                m[i] = T::from_u64(m1[i]).unwrap();
-            }
-        }
+        let m1 = match encoding.encoding_type {
+            EncodingType::Simd => params.coefficients_to_slots_plain(&self.m),
+            EncodingType::Poly => self.m.clone(),
+        };
 
-        m
+        m1.iter().map(|v| T::from_u64(*v).unwrap()).collect()
     }
 
     /// Returns message polynomial `m` scaled by Q/t
@@ -1,11 +1,13 @@
 use crate::{convert_from_bytes, convert_to_bytes};
 use itertools::{izip, Itertools};
 use ndarray::Array2;
+use zeroize::Zeroize;
 pub mod poly_context;
 
 pub use poly_context::PolyContext;
 
 #[derive(Clone, PartialEq, Debug, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Representation {
     Evaluation,
     Coefficient,
@@ -87,6 +89,16 @@ impl Poly {
     }
 }
 
+impl Zeroize for Poly {
+    fn zeroize(&mut self) {
+        // coefficients are always stored in standard layout
+        self.coefficients
+            .as_slice_memory_order_mut()
+            .unwrap()
+            .zeroize();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -6,14 +6,65 @@ use crate::{
 };
 use crypto_bigint::U192;
 use itertools::{izip, Itertools};
-use ndarray::{azip, s, Array2, ArrayView2, Axis, IntoNdProducer};
+use ndarray::{azip, s, Array2, ArrayView2, ArrayViewMut2, Axis};
 use num_bigint::BigUint;
 use num_traits::{identities::One, ToPrimitive, Zero};
 use rand::{CryptoRng, RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use seq_macro::seq;
-use std::mem::{self, MaybeUninit};
+use std::mem::MaybeUninit;
 use traits::Ntt;
+use zeroize::Zeroizing;
+
+/// No. of coefficients processed by a single task in basis conversions if `parallel` feature is
+/// enabled. Must be a multiple of 8.
+#[cfg(feature = "parallel")]
+const COLUMN_CHUNK_SIZE: usize = 1 << 10;
+
+/// Max. no. of input moduli of `approx_switch_crt_basis`
+pub(crate) const MAX_APPROX_SWITCH_MODULI: usize = 64;
+
+/// Zips iterators over rows (ie RNS limbs) of polynomials and moduli or ntt operators (see
+/// `PolyContext::limb_moduli_ops` and `PolyContext::limb_ntt_ops`), and calls closure `$f` with each
+/// tuple. Limbs are processed in parallel if `parallel` feature is enabled.
+macro_rules! for_each_limb {
+    ($f:expr, $($iter:expr),+ $(,)?) => {{
+        #[cfg(feature = "parallel")]
+        ($($iter.into_par_iter(),)+).into_par_iter().for_each($f);
+        #[cfg(not(feature = "parallel"))]
+        izip!($($iter),+).for_each($f);
+    }};
+}
+
+/// Calls `f` with index of first column and view of each chunk of columns of `out`. Chunks are
+/// processed in parallel if `parallel` feature is enabled, otherwise `f` is called once with
+/// entire `out`.
+fn for_each_column_chunk<A: Send + Sync, F>(out: ArrayViewMut2<A>, f: F)
+where
+    F: Fn(usize, ArrayViewMut2<A>) + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    {
+        let mut out = out;
+        out.axis_chunks_iter_mut(Axis(1), COLUMN_CHUNK_SIZE)
+            .into_par_iter()
+            .enumerate()
+            .for_each(|(i, chunk)| f(i * COLUMN_CHUNK_SIZE, chunk));
+    }
+    #[cfg(not(feature = "parallel"))]
+    f(0, out);
+}
+
+/// Reinterprets view of initialised values as view of `MaybeUninit` values. This allows kernels that
+/// write their output to freshly allocated (uninitialised) buffers to also write to existing polynomials.
+fn as_uninit(mut view: ArrayViewMut2<'_, u64>) -> ArrayViewMut2<'_, MaybeUninit<u64>> {
+    assert!(view.is_standard_layout());
+    let dim = view.dim();
+    // `MaybeUninit<u64>` has the same layout as `u64` and kernels only ever write initialised values
+    unsafe { ArrayViewMut2::from_shape_ptr(dim, view.as_mut_ptr() as *mut MaybeUninit<u64>) }
+}
 
 #[derive(PartialEq)]
 pub struct PolyContext<'a, T: Ntt> {
@@ -35,6 +86,29 @@ where
         self.ntt_ops.0.iter().chain(self.ntt_ops.1.iter())
     }
 
+    #[cfg(not(feature = "parallel"))]
+    fn limb_moduli_ops(&self) -> impl Iterator<Item = &Modulus> {
+        self.iter_moduli_ops()
+    }
+
+    #[cfg(feature = "parallel")]
+    fn limb_moduli_ops(&self) -> impl IndexedParallelIterator<Item = &Modulus> {
+        self.moduli_ops
+            .0
+            .par_iter()
+            .chain(self.moduli_ops.1.par_iter())
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn limb_ntt_ops(&self) -> impl Iterator<Item = &T> {
+        self.iter_ntt_ops()
+    }
+
+    #[cfg(feature = "parallel")]
+    fn limb_ntt_ops(&self) -> impl IndexedParallelIterator<Item = &T> {
+        self.ntt_ops.0.par_iter().chain(self.ntt_ops.1.par_iter())
+    }
+
     pub fn big_q(&self) -> BigUint {
         let mut q = BigUint::one();
         self.iter_moduli_ops().for_each(|qi| {
@@ -135,16 +209,20 @@ where
     pub fn change_representation(&self, poly: &mut Poly, to: Representation) {
         if poly.representation == Representation::Evaluation {
             if to == Representation::Coefficient {
-                izip!(poly.coefficients.outer_iter_mut(), self.iter_ntt_ops()).for_each(
+                for_each_limb!(
                     |(mut coefficients, ntt)| ntt.backward(coefficients.as_slice_mut().unwrap()),
+                    poly.coefficients.outer_iter_mut(),
+                    self.limb_ntt_ops()
                 );
                 poly.representation = Representation::Coefficient;
             } else {
             }
         } else if poly.representation == Representation::Coefficient {
             if to == Representation::Evaluation {
-                izip!(poly.coefficients.outer_iter_mut(), self.iter_ntt_ops()).for_each(
+                for_each_limb!(
                     |(mut coefficients, ntt)| ntt.forward(coefficients.as_slice_mut().unwrap()),
+                    poly.coefficients.outer_iter_mut(),
+                    self.limb_ntt_ops()
                 );
                 poly.representation = Representation::Evaluation;
             } else {
@@ -158,9 +236,17 @@ where
     /// In Evaluation form i must be an odd integer not a multiple of 2*degree.
     /// In Coefficient form i must be an integer not a multiple of 2*degree.
     pub fn substitute(&self, poly: &Poly, subs: &Substitution) -> Poly {
+        let mut p = self.zero(poly.representation.clone());
+        self.substitute_into(poly, subs, &mut p);
+        p
+    }
+
+    /// Same as `substitute` but writes the result to `p`, which must be in the context.
+    pub fn substitute_into(&self, poly: &Poly, subs: &Substitution, p: &mut Poly) {
         debug_assert!(subs.exponent % (self.degree * 2) != 0);
         debug_assert!(self.degree == subs.degree);
-        let mut p = self.zero(poly.representation.clone());
+        debug_assert!(p.coefficients.shape() == [self.moduli_count, self.degree]);
+        p.representation = poly.representation.clone();
         if poly.representation == Representation::Evaluation {
             debug_assert!(subs.exponent & 1 == 1);
             izip!(
@@ -173,6 +259,8 @@ where
                 });
             });
         } else if poly.representation == Representation::Coefficient {
+            // coefficients are accumulated
+            p.coefficients.fill(0);
             let mut exponent = 0;
             let mask = self.degree - 1;
             for j in 0..self.degree {
@@ -194,8 +282,6 @@ where
         } else {
             panic!("Unknown polynomial representation!");
         }
-
-        p
     }
 
     pub fn scale_and_round_decryption(
@@ -265,6 +351,61 @@ where
         to_s_hat_inv_mods_divs_frachi: &[u64],
         to_s_hat_inv_mods_divs_fraclo: &[u64],
     ) -> Poly {
+        let mut o_coeffs = Array2::<u64>::uninit((out_context.moduli_count, out_context.degree));
+        self.scale_and_round_uninit(
+            pq_poly,
+            out_context,
+            p_context,
+            q_context,
+            to_s_hat_inv_mods_divs_modo,
+            to_s_hat_inv_mods_divs_frachi,
+            to_s_hat_inv_mods_divs_fraclo,
+            o_coeffs.view_mut(),
+        );
+
+        unsafe {
+            let o_coeffs = o_coeffs.assume_init();
+            return self.new(o_coeffs, Representation::Coefficient);
+        }
+    }
+
+    /// Same as `scale_and_round` but writes the result to `out`, which must be in `out_context`.
+    pub fn scale_and_round_into(
+        &self,
+        pq_poly: &Poly,
+        out_context: &PolyContext<'_, T>,
+        p_context: &PolyContext<'_, T>,
+        q_context: &PolyContext<'_, T>,
+        to_s_hat_inv_mods_divs_modo: &Array2<u64>,
+        to_s_hat_inv_mods_divs_frachi: &[u64],
+        to_s_hat_inv_mods_divs_fraclo: &[u64],
+        out: &mut Poly,
+    ) {
+        assert!(out.coefficients.shape() == [out_context.moduli_count, out_context.degree]);
+        self.scale_and_round_uninit(
+            pq_poly,
+            out_context,
+            p_context,
+            q_context,
+            to_s_hat_inv_mods_divs_modo,
+            to_s_hat_inv_mods_divs_frachi,
+            to_s_hat_inv_mods_divs_fraclo,
+            as_uninit(out.coefficients.view_mut()),
+        );
+        out.representation = Representation::Coefficient;
+    }
+
+    fn scale_and_round_uninit(
+        &self,
+        pq_poly: &Poly,
+        out_context: &PolyContext<'_, T>,
+        p_context: &PolyContext<'_, T>,
+        q_context: &PolyContext<'_, T>,
+        to_s_hat_inv_mods_divs_modo: &Array2<u64>,
+        to_s_hat_inv_mods_divs_frachi: &[u64],
+        to_s_hat_inv_mods_divs_fraclo: &[u64],
+        o_coeffs: ArrayViewMut2<MaybeUninit<u64>>,
+    ) {
         debug_assert!(pq_poly.representation == Representation::Coefficient);
         let mut input_offset = 0;
         let mut output_offset = 0;
@@ -283,9 +424,9 @@ where
         let degree = out_context.degree;
         let modos = out_context.moduli_ops();
 
-        let mut o_coeffs = Array2::<u64>::uninit((output_size, degree));
-        unsafe {
-            for ri in (0..degree).step_by(8) {
+        debug_assert!(o_coeffs.shape() == [output_size, degree]);
+        for_each_column_chunk(o_coeffs, |offset, mut o_coeffs| unsafe {
+            for ri in (offset..offset + o_coeffs.ncols()).step_by(8) {
                 seq!(N in 0..8 {
                     let mut frac~N = U192::ZERO;
                 });
@@ -330,16 +471,11 @@ where
                     seq!(N in 0..8 {
                         tmp~N += *pq_poly.coefficients.uget((j + output_offset, ri+N)) as u128 * op;
                         let pxj = modoj.barret_reduction_u128(tmp~N);
-                        o_coeffs.uget_mut((j, ri+N)).write(pxj);
+                        o_coeffs.uget_mut((j, ri - offset + N)).write(pxj);
                     });
                 }
             }
-        }
-
-        unsafe {
-            let o_coeffs = o_coeffs.assume_init();
-            return self.new(o_coeffs, Representation::Coefficient);
-        }
+        });
     }
 
     /// Given a polynomial in context with moduli Q returns a polynomial in context with moduli P by calculating [round(P/Q([poly]_Q))]_P
@@ -361,6 +497,35 @@ where
     ) -> Poly {
         debug_assert!(q_poly.representation == Representation::Coefficient);
 
+        let mut p_coeffs = Array2::<u64>::uninit((p_context.moduli_count, p_context.degree));
+        self.fast_conv_p_over_q_uninit(
+            q_poly.coefficients.view(),
+            p_context,
+            neg_pq_hat_inv_modq,
+            neg_pq_hat_inv_modq_shoup,
+            q_inv,
+            q_inv_modp,
+            p_coeffs.view_mut(),
+        );
+
+        unsafe {
+            let p_coeffs = p_coeffs.assume_init();
+            return p_context.new(p_coeffs, Representation::Coefficient);
+        }
+    }
+
+    /// `fast_conv_p_over_q` for coefficients `q_coeffs` in Coefficient representation. Writes
+    /// result to `p_coeffs`.
+    fn fast_conv_p_over_q_uninit(
+        &self,
+        q_coeffs: ArrayView2<u64>,
+        p_context: &PolyContext<'_, T>,
+        neg_pq_hat_inv_modq: &[u64],
+        neg_pq_hat_inv_modq_shoup: &[u64],
+        q_inv: &[f64],
+        q_inv_modp: &Array2<u64>,
+        p_coeffs: ArrayViewMut2<MaybeUninit<u64>>,
+    ) {
         let q_size = self.moduli_count;
         let p_size = p_context.moduli_count;
         let degree = self.degree;
@@ -368,10 +533,10 @@ where
         let modqs = self.moduli_ops();
         let modps = p_context.moduli_ops();
 
-        let mut p_coeffs = Array2::<u64>::uninit((p_size, degree));
-        unsafe {
-            //
-            for ri in (0..degree).step_by(8) {
+        debug_assert!(q_coeffs.shape() == [q_size, degree]);
+        debug_assert!(p_coeffs.shape() == [p_size, degree]);
+        for_each_column_chunk(p_coeffs, |offset, mut p_coeffs| unsafe {
+            for ri in (offset..offset + p_coeffs.ncols()).step_by(8) {
                 let mut xiv = Vec::with_capacity(q_size * 8);
                 let uninit_xiv = xiv.spare_capacity_mut();
 
@@ -385,7 +550,7 @@ where
                     let op_shoup = *neg_pq_hat_inv_modq_shoup.get_unchecked(i);
                     let qi_inv = q_inv.get_unchecked(i);
                     seq!(N in 0..8 {
-                        let tmp~N = modqi.mul_mod_shoup(*q_poly.coefficients.uget((i, ri+N)), op, op_shoup);
+                        let tmp~N = modqi.mul_mod_shoup(*q_coeffs.uget((i, ri+N)), op, op_shoup);
                         nu~N += tmp~N as f64 * qi_inv;
                         uninit_xiv.get_unchecked_mut(i*8+N).write(tmp~N);
                     });
@@ -410,17 +575,12 @@ where
 
                     let modpj = modps.get_unchecked(j);
                     seq!(N in 0..8 {
-                        let pxj = p_coeffs.uget_mut((j, ri+N)).write(modpj.barret_reduction_u128(tmp~N));
+                        let pxj = p_coeffs.uget_mut((j, ri - offset + N)).write(modpj.barret_reduction_u128(tmp~N));
                         *pxj = modpj.sub_mod_fast(*pxj, nu~N);
                     });
                 }
             }
-        }
-
-        unsafe {
-            let p_coeffs = p_coeffs.assume_init();
-            return p_context.new(p_coeffs, Representation::Coefficient);
-        }
+        });
     }
 
     pub fn switch_crt_basis(
@@ -435,6 +595,37 @@ where
     ) -> Poly {
         debug_assert!(q_poly.representation == Representation::Coefficient);
 
+        let mut p_coeffs = Array2::uninit((p_context.moduli_count, p_context.degree));
+        self.switch_crt_basis_uninit(
+            q_poly.coefficients.view(),
+            p_context,
+            q_hat_modp,
+            q_hat_inv_modq,
+            q_hat_inv_modq_shoup,
+            q_inv,
+            alpha_modp,
+            p_coeffs.view_mut(),
+        );
+
+        unsafe {
+            let p_coeffs = p_coeffs.assume_init();
+            return p_context.new(p_coeffs, Representation::Coefficient);
+        }
+    }
+
+    /// `switch_crt_basis` for coefficients `q_coeffs` in Coefficient representation. Writes
+    /// result to `p_coeffs`.
+    fn switch_crt_basis_uninit(
+        &self,
+        q_coeffs: ArrayView2<u64>,
+        p_context: &PolyContext<'_, T>,
+        q_hat_modp: &Array2<u64>,
+        q_hat_inv_modq: &[u64],
+        q_hat_inv_modq_shoup: &[u64],
+        q_inv: &[f64],
+        alpha_modp: &Array2<u64>,
+        p_coeffs: ArrayViewMut2<MaybeUninit<u64>>,
+    ) {
         let q_size = self.moduli_count;
         let p_size = p_context.moduli_count;
         let degree = self.degree;
@@ -442,10 +633,10 @@ where
         let modq_ops = self.moduli_ops();
         let modp_ops = p_context.moduli_ops();
 
-        let mut p_coeffs = Array2::uninit((p_size, degree));
-
-        unsafe {
-            for ri in (0..degree).step_by(8) {
+        debug_assert!(q_coeffs.shape() == [q_size, degree]);
+        debug_assert!(p_coeffs.shape() == [p_size, degree]);
+        for_each_column_chunk(p_coeffs, |offset, mut p_coeffs| unsafe {
+            for ri in (offset..offset + p_coeffs.ncols()).step_by(8) {
                 let mut xiq = Vec::with_capacity(q_size * 8);
                 let uninit = xiq.spare_capacity_mut();
 
@@ -461,7 +652,7 @@ where
                     let q_invi = q_inv.get_unchecked(i);
                     seq!(N in 0..8{
                         let tmp~N = mod_ref.mul_mod_shoup(
-                            *q_poly.coefficients.uget((i,ri + N)),
+                            *q_coeffs.uget((i,ri + N)),
                             *op,
                             *op_shoup
                         );
@@ -492,16 +683,11 @@ where
 
                     seq!(N in 0..8 {
                         let tmp = modpj.sub_mod_fast(modpj.barret_reduction_u128(tmp~N), *alpha_modp.uget((j,nu~N as usize)));
-                        p_coeffs.uget_mut((j, ri + N)).write(tmp);
+                        p_coeffs.uget_mut((j, ri - offset + N)).write(tmp);
                     });
                 }
             }
-        }
-
-        unsafe {
-            let p_coeffs = p_coeffs.assume_init();
-            return p_context.new(p_coeffs, Representation::Coefficient);
-        }
+        });
     }
 
     pub fn fast_expand_crt_basis_p_over_q(
@@ -519,51 +705,92 @@ where
         p_inv: &[f64],
         alpha_modq: &Array2<u64>,
     ) -> Poly {
-        // if self is not in coefficient, then convert it
-        let p = if q_poly.representation == Representation::Coefficient {
-            self.fast_conv_p_over_q(
-                q_poly,
+        let mut pq_poly = pq_context.zero(Representation::Coefficient);
+        self.fast_expand_crt_basis_p_over_q_into(
+            q_poly,
+            p_context,
+            pq_context,
+            neg_pq_hat_inv_modq,
+            neg_pq_hat_inv_modq_shoup,
+            q_inv,
+            q_inv_modp,
+            p_hat_modq,
+            p_hat_inv_modp,
+            p_hat_inv_modp_shoup,
+            p_inv,
+            alpha_modq,
+            &mut pq_poly,
+        );
+        pq_poly
+    }
+
+    /// Same as `fast_expand_crt_basis_p_over_q` but writes the result to `out`, which must be in `pq_context`.
+    /// Rows of `out` are used as intermediate buffers, hence no memory is allocated.
+    pub fn fast_expand_crt_basis_p_over_q_into(
+        &self,
+        q_poly: &Poly,
+        p_context: &PolyContext<'_, T>,
+        pq_context: &PolyContext<'_, T>,
+        neg_pq_hat_inv_modq: &[u64],
+        neg_pq_hat_inv_modq_shoup: &[u64],
+        q_inv: &[f64],
+        q_inv_modp: &Array2<u64>,
+        p_hat_modq: &Array2<u64>,
+        p_hat_inv_modp: &[u64],
+        p_hat_inv_modp_shoup: &[u64],
+        p_inv: &[f64],
+        alpha_modq: &Array2<u64>,
+        out: &mut Poly,
+    ) {
+        assert!(out.coefficients.shape() == [pq_context.moduli_count, pq_context.degree]);
+
+        let p_size = p_context.moduli_count;
+        let (mut p_coeffs, mut q_coeffs) = out.coefficients.view_mut().split_at(Axis(0), p_size);
+
+        // if q_poly is not in coefficient, then convert its copy in Q part of `out`. Q part is
+        // overwritten afterwards.
+        if q_poly.representation == Representation::Coefficient {
+            self.fast_conv_p_over_q_uninit(
+                q_poly.coefficients.view(),
                 p_context,
                 neg_pq_hat_inv_modq,
                 neg_pq_hat_inv_modq_shoup,
                 q_inv,
                 q_inv_modp,
-            )
+                as_uninit(p_coeffs.view_mut()),
+            );
         } else {
-            let mut q = q_poly.clone();
-            self.change_representation(&mut q, Representation::Coefficient);
-            self.fast_conv_p_over_q(
-                &q,
+            q_coeffs.assign(&q_poly.coefficients);
+            for_each_limb!(
+                |(mut coefficients, ntt)| ntt.backward(coefficients.as_slice_mut().unwrap()),
+                q_coeffs.outer_iter_mut(),
+                self.limb_ntt_ops()
+            );
+            self.fast_conv_p_over_q_uninit(
+                q_coeffs.view(),
                 p_context,
                 neg_pq_hat_inv_modq,
                 neg_pq_hat_inv_modq_shoup,
                 q_inv,
                 q_inv_modp,
-            )
-        };
+                as_uninit(p_coeffs.view_mut()),
+            );
+        }
 
         // switch p to q
-        let q = p_context.switch_crt_basis(
-            &p,
-            &self,
+        p_context.switch_crt_basis_uninit(
+            p_coeffs.view(),
+            self,
             p_hat_modq,
             p_hat_inv_modp,
             p_hat_inv_modp_shoup,
             p_inv,
             alpha_modq,
+            as_uninit(q_coeffs),
         );
 
-        let p_size = p_context.moduli_count;
         // output should always be in coefficient form
-        let mut pq_coeffs = Array2::zeros((pq_context.moduli_count, pq_context.degree));
-        pq_coeffs
-            .slice_mut(s![..p_size, ..])
-            .assign(&p.coefficients);
-        pq_coeffs
-            .slice_mut(s![p_size.., ..])
-            .assign(&q.coefficients);
-
-        pq_context.new(pq_coeffs, Representation::Coefficient)
+        out.representation = Representation::Coefficient;
     }
 
     pub fn expand_crt_basis(
@@ -577,41 +804,79 @@ where
         q_inv: &[f64],
         alpha_modp: &Array2<u64>,
     ) -> Poly {
-        let mut p = if q_poly.representation == Representation::Coefficient {
-            self.switch_crt_basis(
-                q_poly,
+        let mut pq_poly = pq_context.zero(q_poly.representation.clone());
+        self.expand_crt_basis_into(
+            q_poly,
+            pq_context,
+            p_context,
+            q_hat_modp,
+            q_hat_inv_modq,
+            q_hat_inv_modq_shoup,
+            q_inv,
+            alpha_modp,
+            &mut pq_poly,
+        );
+        pq_poly
+    }
+
+    /// Same as `expand_crt_basis` but writes the result to `out`, which must be in `pq_context`.
+    /// Rows of `out` are used as intermediate buffers, hence no memory is allocated.
+    pub fn expand_crt_basis_into(
+        &self,
+        q_poly: &Poly,
+        pq_context: &PolyContext<'_, T>,
+        p_context: &PolyContext<'_, T>,
+        q_hat_modp: &Array2<u64>,
+        q_hat_inv_modq: &[u64],
+        q_hat_inv_modq_shoup: &[u64],
+        q_inv: &[f64],
+        alpha_modp: &Array2<u64>,
+        out: &mut Poly,
+    ) {
+        assert!(out.coefficients.shape() == [pq_context.moduli_count, pq_context.degree]);
+
+        let p_size = p_context.moduli_count;
+        let (mut p_coeffs, mut q_coeffs) = out.coefficients.view_mut().split_at(Axis(0), p_size);
+
+        q_coeffs.assign(&q_poly.coefficients);
+        if q_poly.representation == Representation::Coefficient {
+            self.switch_crt_basis_uninit(
+                q_coeffs.view(),
                 p_context,
                 q_hat_modp,
                 q_hat_inv_modq,
                 q_hat_inv_modq_shoup,
                 q_inv,
                 alpha_modp,
-            )
+                as_uninit(p_coeffs.view_mut()),
+            );
         } else {
-            let mut q = q_poly.clone();
-            self.change_representation(&mut q, Representation::Coefficient);
-            self.switch_crt_basis(
-                &q,
+            // switch from copy of q_poly converted to coefficient form in Q part of `out`
+            // and restore Q part afterwards
+            for_each_limb!(
+                |(mut coefficients, ntt)| ntt.backward(coefficients.as_slice_mut().unwrap()),
+                q_coeffs.outer_iter_mut(),
+                self.limb_ntt_ops()
+            );
+            self.switch_crt_basis_uninit(
+                q_coeffs.view(),
                 p_context,
                 q_hat_modp,
                 q_hat_inv_modq,
                 q_hat_inv_modq_shoup,
                 q_inv,
                 alpha_modp,
-            )
-        };
-        p_context.change_representation(&mut p, q_poly.representation.clone());
+                as_uninit(p_coeffs.view_mut()),
+            );
+            q_coeffs.assign(&q_poly.coefficients);
+            for_each_limb!(
+                |(mut coefficients, ntt)| ntt.forward(coefficients.as_slice_mut().unwrap()),
+                p_coeffs.outer_iter_mut(),
+                p_context.limb_ntt_ops()
+            );
+        }
 
-        let p_size = p_context.moduli_count;
-        let mut pq_coeffs = Array2::zeros((pq_context.moduli_count, pq_context.degree));
-        pq_coeffs
-            .slice_mut(s![..p_size, ..])
-            .assign(&p.coefficients);
-        pq_coeffs
-            .slice_mut(s![p_size.., ..])
-            .assign(&q_poly.coefficients);
-
-        pq_context.new(pq_coeffs, q_poly.representation.clone())
+        out.representation = q_poly.representation.clone();
     }
 
     /// Switches CRT basis from Q to P approximately.
@@ -625,29 +890,74 @@ where
         q_hat_modp: &Array2<u64>,
         p_moduli_ops: &[Modulus],
     ) -> Array2<u64> {
-        debug_assert!(q_moduli_ops.len() == q_coefficients.shape()[0]);
-        debug_assert!(q_moduli_ops.len() <= 3);
-
         let mut p_coeffs = Array2::<u64>::uninit((p_moduli_ops.len(), degree));
+        PolyContext::<T>::approx_switch_crt_basis_uninit(
+            q_coefficients,
+            q_moduli_ops,
+            degree,
+            q_hat_inv_modq,
+            q_hat_modp,
+            p_moduli_ops,
+            p_coeffs.view_mut(),
+        );
+
+        unsafe {
+            return p_coeffs.assume_init();
+        }
+    }
+
+    /// Same as `approx_switch_crt_basis` but writes the result to `p_coeffs`.
+    pub fn approx_switch_crt_basis_into(
+        q_coefficients: &ArrayView2<u64>,
+        q_moduli_ops: &[Modulus],
+        degree: usize,
+        q_hat_inv_modq: &[u64],
+        q_hat_modp: &Array2<u64>,
+        p_moduli_ops: &[Modulus],
+        p_coeffs: &mut Array2<u64>,
+    ) {
+        assert!(p_coeffs.shape() == [p_moduli_ops.len(), degree]);
+        PolyContext::<T>::approx_switch_crt_basis_uninit(
+            q_coefficients,
+            q_moduli_ops,
+            degree,
+            q_hat_inv_modq,
+            q_hat_modp,
+            p_moduli_ops,
+            as_uninit(p_coeffs.view_mut()),
+        );
+    }
+
+    fn approx_switch_crt_basis_uninit(
+        q_coefficients: &ArrayView2<u64>,
+        q_moduli_ops: &[Modulus],
+        degree: usize,
+        q_hat_inv_modq: &[u64],
+        q_hat_modp: &Array2<u64>,
+        p_moduli_ops: &[Modulus],
+        mut p_coeffs: ArrayViewMut2<MaybeUninit<u64>>,
+    ) {
+        debug_assert!(q_moduli_ops.len() == q_coefficients.shape()[0]);
+        // Products of values < 2^64 are accumulated in u128 without reduction. Moduli are at most
+        // 61 bits, thus sum of up to 64 products does not overflow. The limit also bounds `tmp`, which
+        // is kept on stack to avoid an allocation per call.
+        assert!(q_moduli_ops.len() <= MAX_APPROX_SWITCH_MODULI);
 
         let p_size = p_moduli_ops.len();
         let q_size = q_coefficients.shape()[0];
+        let mut tmp = [0u64; MAX_APPROX_SWITCH_MODULI * 8];
         unsafe {
             for ri in (0..degree).step_by(8) {
-                let mut tmp: [MaybeUninit<u64>; 3 * 8] = MaybeUninit::uninit().assume_init();
-
                 for i in 0..q_size {
                     let modq = q_moduli_ops.get_unchecked(i);
                     let op = *q_hat_inv_modq.get_unchecked(i);
 
                     seq!(N in 0..8 {
-                        tmp.get_unchecked_mut(i*8+N)
-                        .write(modq.mul_mod_fast(*q_coefficients.uget((i, ri+N)), op));
+                        *tmp.get_unchecked_mut(i*8+N) =
+                            modq.mul_mod_fast(*q_coefficients.uget((i, ri+N)), op);
                     });
                 }
-                // tmp.set_len(q_size);
 
-                let tmp = mem::transmute::<_, [u64; 3 * 8]>(tmp);
                 for j in 0..p_size {
                     seq!(N in 0..8 {
                         let mut s~N = 0u128;
@@ -669,10 +979,6 @@ where
                 }
             }
         }
-
-        unsafe {
-            return p_coeffs.assume_init();
-        }
     }
 
     /// Approx mod down
@@ -689,6 +995,32 @@ where
         p_hat_modq: &Array2<u64>,
         p_inv_modq: &[u64],
     ) -> Poly {
+        let mut q_poly = q_context.zero(Representation::Evaluation);
+        self.approx_mod_down_into(
+            &mut qp_poly,
+            q_context,
+            p_context,
+            p_hat_inv_modp,
+            p_hat_modq,
+            p_inv_modq,
+            &mut q_poly,
+        );
+        q_poly
+    }
+
+    /// Same as `approx_mod_down` but writes the result to `q_poly`, which must be in `q_context`.
+    /// `qp_poly` is used as scratch space and its coefficients are arbitrary afterwards.
+    #[allow(clippy::too_many_arguments)]
+    pub fn approx_mod_down_into(
+        &self,
+        qp_poly: &mut Poly,
+        q_context: &PolyContext<'_, T>,
+        p_context: &PolyContext<'_, T>,
+        p_hat_inv_modp: &[u64],
+        p_hat_modq: &Array2<u64>,
+        p_inv_modq: &[u64],
+        q_poly: &mut Poly,
+    ) {
         debug_assert!(q_context.moduli_count + p_context.moduli_count == self.moduli_count);
         debug_assert!(qp_poly.representation == Representation::Evaluation);
 
@@ -703,40 +1035,40 @@ where
             },
         );
 
-        let mut p_to_q_coefficients = PolyContext::<T>::approx_switch_crt_basis(
+        PolyContext::<T>::approx_switch_crt_basis_into(
             &p_coefficients.view(),
             p_context.moduli_ops(),
             self.degree,
             p_hat_inv_modp,
             p_hat_modq,
             q_context.moduli_ops(),
+            &mut q_poly.coefficients,
         );
 
         // Change P switched to Q part from `Coefficient` to `Evaluation` representation
         // Reason to switch from coefficient to evaluation form becomes apparent in next step when we multiply all values by 1/P
         izip!(
-            p_to_q_coefficients.outer_iter_mut(),
+            q_poly.coefficients.outer_iter_mut(),
             q_context.iter_ntt_ops()
         )
         .for_each(|(mut v, ntt_op)| {
             ntt_op.forward(v.as_slice_mut().unwrap());
         });
 
-        let mut q_poly = qp_poly.coefficients.slice_move(s![..q_size, ..]);
-        debug_assert!(q_poly.shape()[0] == q_size);
-
         izip!(
-            q_poly.outer_iter_mut(),
-            p_to_q_coefficients.outer_iter(),
+            q_poly.coefficients.outer_iter_mut(),
+            qp_poly.coefficients.slice(s![..q_size, ..]).outer_iter(),
             q_context.iter_moduli_ops(),
             p_inv_modq.iter(),
         )
-        .for_each(|(mut v, switched_v, modqi, p_inv_modqi)| {
-            modqi.sub_mod_fast_vec(v.as_slice_mut().unwrap(), switched_v.as_slice().unwrap());
-            modqi.scalar_mul_mod_fast_vec(v.as_slice_mut().unwrap(), *p_inv_modqi);
+        .for_each(|(mut switched_v, v, modqi, p_inv_modqi)| {
+            modqi.sub_mod_fast_vec_reversed(
+                switched_v.as_slice_mut().unwrap(),
+                v.as_slice().unwrap(),
+            );
+            modqi.scalar_mul_mod_fast_vec(switched_v.as_slice_mut().unwrap(), *p_inv_modqi);
         });
-
-        Poly::new(q_poly, Representation::Evaluation)
+        q_poly.representation = Representation::Evaluation;
     }
 
     /// Switches polynomial from Q to Q' and scales by 1/qn where Q = q0*q1*q2...*qn and Q' = q0*q1*q2...*q(n-1).
@@ -767,24 +1099,24 @@ where
                 .unwrap()
                 .backward(p.as_slice_mut().unwrap());
         }
-        izip!(
-            coeffs.outer_iter_mut(),
-            self.iter_moduli_ops(),
-            self.iter_ntt_ops(),
-            last_qi_inv_modq.into_producer()
-        )
-        .for_each(|(mut ceoffs, modqi, nttop, last_qi_modqi)| {
-            let mut tmp = p.to_owned();
-            modqi.reduce_vec(tmp.as_slice_mut().unwrap());
-
-            if poly.representation == Representation::Evaluation {
-                //TODO can we make this lazy as well?
-                nttop.forward(tmp.as_slice_mut().unwrap());
-            }
+        for_each_limb!(
+            |(mut ceoffs, modqi, nttop, last_qi_modqi)| {
+                let mut tmp = p.to_owned();
+                modqi.reduce_vec(tmp.as_slice_mut().unwrap());
+
+                if poly.representation == Representation::Evaluation {
+                    //TODO can we make this lazy as well?
+                    nttop.forward(tmp.as_slice_mut().unwrap());
+                }
 
-            modqi.sub_mod_fast_vec(ceoffs.as_slice_mut().unwrap(), tmp.as_slice().unwrap());
-            modqi.scalar_mul_mod_fast_vec(ceoffs.as_slice_mut().unwrap(), *last_qi_modqi);
-        });
+                modqi.sub_mod_fast_vec(ceoffs.as_slice_mut().unwrap(), tmp.as_slice().unwrap());
+                modqi.scalar_mul_mod_fast_vec(ceoffs.as_slice_mut().unwrap(), *last_qi_modqi);
+            },
+            coeffs.outer_iter_mut(),
+            self.limb_moduli_ops(),
+            self.limb_ntt_ops(),
+            last_qi_inv_modq
+        );
         poly.coefficients.slice_collapse(s![..-1, ..]);
     }
 
@@ -909,36 +1241,21 @@ where
         assert!(lhs.representation == rhs.representation);
         assert!(lhs.representation == Representation::Evaluation);
 
-        izip!(
+        for_each_limb!(
+            |(mut p, p2, modqi)| {
+                modqi.mul_mod_fast_vec(p.as_slice_mut().unwrap(), p2.as_slice().unwrap());
+            },
             lhs.coefficients.outer_iter_mut(),
             rhs.coefficients.outer_iter(),
-            self.iter_moduli_ops()
-        )
-        .for_each(|(mut p, p2, modqi)| {
-            // let now = std::time::Instant::now();
-            modqi.mul_mod_fast_vec(p.as_slice_mut().unwrap(), p2.as_slice().unwrap());
-            // println!("Time: {:?}", now.elapsed());
-        });
+            self.limb_moduli_ops()
+        );
     }
 
     pub fn mul(&self, lhs: &Poly, rhs: &Poly) -> Poly {
         assert!(lhs.representation == rhs.representation);
 
         let mut coeffs = Array2::<u64>::uninit((self.moduli_count, self.degree));
-
-        izip!(
-            coeffs.outer_iter_mut(),
-            lhs.coefficients.outer_iter(),
-            rhs.coefficients.outer_iter(),
-            self.iter_moduli_ops()
-        )
-        .for_each(|(mut pr, p1, p2, q)| {
-            q.mul_mod_fast_vec_uninit(
-                pr.as_slice_mut().unwrap(),
-                p1.as_slice().unwrap(),
-                p2.as_slice().unwrap(),
-            );
-        });
+        self.mul_uninit(lhs, rhs, coeffs.view_mut());
 
         let coeffs = unsafe { coeffs.assume_init() };
         Poly {
@@ -946,6 +1263,31 @@ where
             representation: lhs.representation.clone(),
         }
     }
+
+    /// Same as `mul` but writes the result to `out`, which must be in the context.
+    pub fn mul_into(&self, lhs: &Poly, rhs: &Poly, out: &mut Poly) {
+        assert!(lhs.representation == rhs.representation);
+        assert!(out.coefficients.shape() == [self.moduli_count, self.degree]);
+
+        self.mul_uninit(lhs, rhs, as_uninit(out.coefficients.view_mut()));
+        out.representation = lhs.representation.clone();
+    }
+
+    fn mul_uninit(&self, lhs: &Poly, rhs: &Poly, mut coeffs: ArrayViewMut2<MaybeUninit<u64>>) {
+        for_each_limb!(
+            |(mut pr, p1, p2, q)| {
+                q.mul_mod_fast_vec_uninit(
+                    pr.as_slice_mut().unwrap(),
+                    p1.as_slice().unwrap(),
+                    p2.as_slice().unwrap(),
+                );
+            },
+            coeffs.outer_iter_mut(),
+            lhs.coefficients.outer_iter(),
+            rhs.coefficients.outer_iter(),
+            self.limb_moduli_ops()
+        );
+    }
 }
 
 impl<T> PolyContext<'_, T>
@@ -1002,7 +1344,7 @@ where
                 qi_values
                     .as_slice_mut()
                     .unwrap()
-                    .copy_from_slice(&qi.reduce_vec_i64_small(values).as_slice());
+                    .copy_from_slice(&Zeroizing::new(qi.reduce_vec_i64_small(values)));
             },
         );
         p
@@ -1,21 +1,145 @@
 use std::collections::HashMap;
 
+use crate::evaluation_key::exponent_to_rot;
+use crate::key_switching_key::SwitchingKey;
+use crate::nb_theory::try_generate_primes_vec;
+use crate::poly::poly_context::MAX_APPROX_SWITCH_MODULI;
 use crate::{
     convert_bytes_to_ternary, convert_from_bytes, convert_ternary_to_bytes, convert_to_bytes,
-    BfvParameters, Ciphertext, EvaluationKey, GaloisKey, HybridKeySwitchingKey, Poly, PolyContext,
-    PolyType, RelinearizationKey, Representation, SecretKey, Substitution,
+    BVKeySwitchingKey, BfvParameters, Ciphertext, EvaluationKey, GaloisKey, HybridKeySwitchingKey,
+    KeySwitchingKey, Poly, PolyContext, PolyType, PublicKey, RelinearizationKey, Representation,
+    SecretKey, Substitution,
 };
 use itertools::{izip, Itertools};
 use ndarray::Array2;
+use num_bigint_dig::{prime::probably_prime, BigUint};
+use prost::Message;
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
+use sha2::{Digest, Sha256};
 use traits::{TryFromWithParameters, TryFromWithPolyContext};
+use zeroize::Zeroize;
 
 // include protos
 pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/_.rs"));
 }
 
+// Parameters //
+/// Max. degree accepted when decoding `proto::Parameters`
+const MAX_DEGREE: usize = 1 << 17;
+
+/// Max. bits in a modulus accepted when decoding `proto::Parameters`
+const MAX_MODULUS_BITS: usize = 61;
+
+impl From<&BfvParameters> for proto::Parameters {
+    fn from(value: &BfvParameters) -> Self {
+        let special_moduli_sizes = value
+            .special_moduli
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .map(|pj| 64 - pj.leading_zeros())
+            .collect_vec();
+
+        proto::Parameters {
+            degree: value.degree as u32,
+            plaintext_modulus: value.plaintext_modulus,
+            ciphertext_moduli_sizes: value
+                .ciphertext_moduli_sizes
+                .iter()
+                .map(|s| *s as u32)
+                .collect_vec(),
+            special_moduli_sizes,
+            alpha: value.alpha.unwrap_or(0) as u32,
+            hw: value.hw as u32,
+            variance: value.variance as u32,
+        }
+    }
+}
+
+impl TryFrom<&proto::Parameters> for BfvParameters {
+    type Error = String;
+
+    fn try_from(value: &proto::Parameters) -> Result<Self, Self::Error> {
+        let degree = value.degree as usize;
+        if !degree.is_power_of_two() || !(16..=MAX_DEGREE).contains(&degree) {
+            return Err(format!(
+                "Degree must be a power of two between 16 and {}",
+                MAX_DEGREE
+            ));
+        }
+
+        let ciphertext_moduli_sizes = value
+            .ciphertext_moduli_sizes
+            .iter()
+            .map(|s| *s as usize)
+            .collect_vec();
+        let special_moduli_sizes = value
+            .special_moduli_sizes
+            .iter()
+            .map(|s| *s as usize)
+            .collect_vec();
+        if ciphertext_moduli_sizes.is_empty()
+            || ciphertext_moduli_sizes.len() > MAX_APPROX_SWITCH_MODULI
+        {
+            return Err(format!(
+                "No. of ciphertext moduli must be between 1 and {}",
+                MAX_APPROX_SWITCH_MODULI
+            ));
+        }
+        if ciphertext_moduli_sizes
+            .iter()
+            .chain(special_moduli_sizes.iter())
+            .any(|s| !(1..=MAX_MODULUS_BITS).contains(s))
+        {
+            return Err(format!(
+                "Moduli sizes must be between 1 and {} bits",
+                MAX_MODULUS_BITS
+            ));
+        }
+
+        // generation of Q, P and special moduli in `BfvParameters` panics if there are not enough primes
+        let ciphertext_moduli = try_generate_primes_vec(&ciphertext_moduli_sizes, degree, &[])
+            .ok_or("Not enough primes for ciphertext moduli")?;
+        try_generate_primes_vec(&ciphertext_moduli_sizes, degree, &ciphertext_moduli)
+            .ok_or("Not enough primes for extension moduli")?;
+        try_generate_primes_vec(&special_moduli_sizes, degree, &ciphertext_moduli)
+            .ok_or("Not enough primes for special moduli")?;
+
+        let t = value.plaintext_modulus;
+        if t >> MAX_MODULUS_BITS != 0
+            || t % (2 * degree as u64) != 1
+            || !probably_prime(&BigUint::from(t), 0)
+            || ciphertext_moduli.contains(&t)
+        {
+            return Err(format!(
+                "Plaintext modulus {} is not an NTT friendly prime coprime to ciphertext modulus",
+                t
+            ));
+        }
+
+        let hw = value.hw as usize;
+        if !(1..=degree).contains(&hw) {
+            return Err(format!("Hamming weight {} is out of range", hw));
+        }
+        let variance = value.variance as usize;
+        if !(1..=16).contains(&variance) {
+            return Err(format!("Variance {} is out of range", variance));
+        }
+
+        let mut params = BfvParameters::new(&ciphertext_moduli_sizes, t, degree);
+        if !special_moduli_sizes.is_empty() {
+            let alpha = value.alpha as usize;
+            params.check_hybrid_key_switching(&special_moduli_sizes, alpha)?;
+            params.enable_hybrid_key_switching_with_alpha(&special_moduli_sizes, alpha);
+        }
+        params.change_hamming_weight(hw);
+        params.variance = variance;
+        Ok(params)
+    }
+}
+
 // Poly //
 impl<'a> TryFromWithPolyContext<'a> for Poly {
     type Value = proto::Poly;
@@ -57,6 +181,12 @@ impl<'a> TryFromWithPolyContext<'a> for proto::Poly {
 }
 
 // SecretKey //
+impl Drop for proto::SecretKey {
+    fn drop(&mut self) {
+        self.coefficients.zeroize();
+    }
+}
+
 impl TryFromWithParameters for proto::SecretKey {
     type Value = SecretKey;
     type Parameters = BfvParameters;
@@ -74,10 +204,19 @@ impl TryFromWithParameters for SecretKey {
     type Value = proto::SecretKey;
 
     fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
-        let coefficients =
-            convert_bytes_to_ternary(&value.coefficients, parameters.degree).into_boxed_slice();
-
-        SecretKey { coefficients }
+        assert!(
+            value.coefficients.len() >= parameters.degree.div_ceil(4),
+            "Secret key has {} bytes, expected at least {}",
+            value.coefficients.len(),
+            parameters.degree.div_ceil(4)
+        );
+        let mut coefficients = convert_bytes_to_ternary(&value.coefficients, parameters.degree);
+        // copy to a new allocation since `into_boxed_slice` may reallocate without zeroizing
+        let sk = SecretKey {
+            coefficients: coefficients.as_slice().into(),
+        };
+        coefficients.zeroize();
+        sk
     }
 }
 
@@ -152,6 +291,7 @@ impl TryFromWithParameters for Ciphertext {
             poly_type: PolyType::Q,
             level,
             seed,
+            noise_estimate: None,
         }
     }
 }
@@ -191,7 +331,12 @@ impl<'a> TryFromWithPolyContext<'a> for proto::HybridKeySwitchingKey {
 
         let seed = value.seed.and_then(|s| Some(s.to_vec()));
 
-        proto::HybridKeySwitchingKey { c0s, c1s, seed }
+        proto::HybridKeySwitchingKey {
+            c0s,
+            c1s,
+            seed,
+            seed_version: HybridKeySwitchingKey::SEED_VERSION,
+        }
     }
 }
 
@@ -225,6 +370,11 @@ impl<'a> TryFromWithPolyContext<'a> for HybridKeySwitchingKey {
                     .collect_vec();
                 (c, None)
             } else {
+                assert!(
+                    value.seed_version == HybridKeySwitchingKey::SEED_VERSION,
+                    "Unsupported version {} of seeded key switching key",
+                    value.seed_version
+                );
                 let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
                 seed.copy_from_slice(value.seed());
                 // `generate_c1` returns c1s in `Coefficient` representation. Convert them to `Evaluation` representation.
@@ -244,21 +394,157 @@ impl<'a> TryFromWithPolyContext<'a> for HybridKeySwitchingKey {
     }
 }
 
+// BV Key Switching Key //
+impl<'a> TryFromWithPolyContext<'a> for proto::BvKeySwitchingKey {
+    type PolyContext = PolyContext<'a>;
+    type Value = BVKeySwitchingKey;
+    fn try_from_with_context(value: &Self::Value, poly_ctx: &'a Self::PolyContext) -> Self {
+        // c0s are always in `Evaluation` form. c1s are generated from seed, thus are not serialized.
+        let c0s = value
+            .c0s
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                poly_ctx.change_representation(&mut p, Representation::Coefficient);
+                proto::Poly::try_from_with_context(&p, &poly_ctx)
+            })
+            .collect_vec();
+
+        proto::BvKeySwitchingKey {
+            c0s,
+            seed: value.seed.to_vec(),
+        }
+    }
+}
+
+impl<'a> TryFromWithPolyContext<'a> for BVKeySwitchingKey {
+    type PolyContext = PolyContext<'a>;
+    type Value = proto::BvKeySwitchingKey;
+    fn try_from_with_context(value: &Self::Value, poly_ctx: &'a Self::PolyContext) -> Self {
+        assert!(value.c0s.len() == poly_ctx.moduli_count);
+        let c0s = value
+            .c0s
+            .iter()
+            .map(|p| {
+                let mut p = Poly::try_from_with_context(p, &poly_ctx);
+                poly_ctx.change_representation(&mut p, Representation::Evaluation);
+                p
+            })
+            .collect_vec();
+
+        let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+        seed.copy_from_slice(&value.seed);
+        // `generate_c1` returns c1s in `Evaluation` representation
+        let c1s = BVKeySwitchingKey::generate_c1(poly_ctx, seed);
+
+        BVKeySwitchingKey {
+            c0s: c0s.into_boxed_slice(),
+            c1s: c1s.into_boxed_slice(),
+            seed,
+        }
+    }
+}
+
+/// Converts switching key of relinearization or galois key at `level` to its proto. Returns hybrid
+/// and BV proto respectively, only one of which is set.
+fn switching_key_to_proto(
+    ksk: &SwitchingKey,
+    parameters: &BfvParameters,
+    level: usize,
+) -> (
+    Option<proto::HybridKeySwitchingKey>,
+    Option<proto::BvKeySwitchingKey>,
+) {
+    match ksk {
+        SwitchingKey::Hybrid(ksk) => {
+            let ctx = parameters.poly_ctx(&PolyType::QP, level);
+            (
+                Some(proto::HybridKeySwitchingKey::try_from_with_context(
+                    ksk, &ctx,
+                )),
+                None,
+            )
+        }
+        SwitchingKey::Bv(ksk) => {
+            let ctx = parameters.poly_ctx(&PolyType::Q, level);
+            (
+                None,
+                Some(proto::BvKeySwitchingKey::try_from_with_context(ksk, &ctx)),
+            )
+        }
+    }
+}
+
+fn switching_key_from_proto(
+    ksk: Option<&proto::HybridKeySwitchingKey>,
+    bv_ksk: Option<&proto::BvKeySwitchingKey>,
+    parameters: &BfvParameters,
+    level: usize,
+) -> SwitchingKey {
+    match (ksk, bv_ksk) {
+        (Some(ksk), None) => {
+            let ctx = parameters.poly_ctx(&PolyType::QP, level);
+            SwitchingKey::Hybrid(HybridKeySwitchingKey::try_from_with_context(ksk, &ctx))
+        }
+        (None, Some(bv_ksk)) => {
+            let ctx = parameters.poly_ctx(&PolyType::Q, level);
+            SwitchingKey::Bv(BVKeySwitchingKey::try_from_with_context(bv_ksk, &ctx))
+        }
+        _ => panic!("Exactly one of hybrid and BV key switching key must be set"),
+    }
+}
+
+// Public Key //
+impl TryFromWithParameters for proto::PublicKey {
+    type Value = PublicKey;
+    type Parameters = BfvParameters;
+
+    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+        let ctx = parameters.poly_ctx(&PolyType::QP, 0);
+        let mut c0 = value.c0.clone();
+        ctx.change_representation(&mut c0, Representation::Coefficient);
+
+        // c1 is generated from seed, thus is not serialized
+        proto::PublicKey {
+            c0: Some(proto::Poly::try_from_with_context(&c0, &ctx)),
+            seed: value.seed.to_vec(),
+        }
+    }
+}
+
+impl TryFromWithParameters for PublicKey {
+    type Value = proto::PublicKey;
+    type Parameters = BfvParameters;
+
+    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+        if let Err(e) = value.validate(parameters) {
+            panic!("Invalid public key: {}", e);
+        }
+
+        let ctx = parameters.poly_ctx(&PolyType::QP, 0);
+        let mut c0 = Poly::try_from_with_context(value.c0.as_ref().unwrap(), &ctx);
+        ctx.change_representation(&mut c0, Representation::Evaluation);
+
+        let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+        seed.copy_from_slice(&value.seed);
+        let c1 = PublicKey::generate_c1(parameters, seed);
+
+        PublicKey { c0, c1, seed }
+    }
+}
+
 // Galois Key //
 impl TryFromWithParameters for proto::GaloisKey {
     type Parameters = BfvParameters;
     type Value = GaloisKey;
 
     fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
-        let ctx = parameters.poly_ctx(&PolyType::QP, value.level);
-        let ksk = Some(proto::HybridKeySwitchingKey::try_from_with_context(
-            &value.ksk_key,
-            &ctx,
-        ));
+        let (ksk, bv_ksk) = switching_key_to_proto(&value.ksk_key, parameters, value.level);
         proto::GaloisKey {
             exponent: value.substitution.exponent as u32,
             ksk,
             level: value.level as u32,
+            bv_ksk,
         }
     }
 }
@@ -271,8 +557,8 @@ impl TryFromWithParameters for GaloisKey {
         let substitution = Substitution::new(value.exponent as usize, parameters.degree);
         let level = value.level as usize;
 
-        let ctx = parameters.poly_ctx(&PolyType::QP, level);
-        let ksk = HybridKeySwitchingKey::try_from_with_context(&value.ksk.as_ref().unwrap(), &ctx);
+        let ksk =
+            switching_key_from_proto(value.ksk.as_ref(), value.bv_ksk.as_ref(), parameters, level);
         GaloisKey {
             substitution,
             ksk_key: ksk,
@@ -287,18 +573,26 @@ impl TryFromWithParameters for proto::RelinearizationKey {
     type Value = RelinearizationKey;
     fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
         let level = value.level;
-        let ctx = parameters.poly_ctx(&PolyType::QP, level);
 
         // message types default to optional in proto3. For more info check this
         // answer https://github.com/tokio-rs/prost/discussions/679 and the one linked in it.
         // This is enforced by proto3, not something prost does.
-        let ksk = Some(proto::HybridKeySwitchingKey::try_from_with_context(
-            &value.ksk, &ctx,
-        ));
+        let (ksk, bv_ksk) = switching_key_to_proto(&value.ksk, parameters, level);
+
+        let mut higher_ksks = vec![];
+        let mut higher_bv_ksks = vec![];
+        value.higher_ksks.iter().for_each(|k| {
+            let (ksk, bv_ksk) = switching_key_to_proto(k, parameters, level);
+            higher_ksks.extend(ksk);
+            higher_bv_ksks.extend(bv_ksk);
+        });
 
         proto::RelinearizationKey {
             ksk,
             level: level as u32,
+            bv_ksk,
+            higher_ksks,
+            higher_bv_ksks,
         }
     }
 }
@@ -306,15 +600,59 @@ impl TryFromWithParameters for proto::RelinearizationKey {
 impl TryFromWithParameters for RelinearizationKey {
     type Parameters = BfvParameters;
     type Value = proto::RelinearizationKey;
+    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+        let level = value.level as usize;
+        let ksk =
+            switching_key_from_proto(value.ksk.as_ref(), value.bv_ksk.as_ref(), parameters, level);
+        let higher_ksks = value
+            .higher_ksks
+            .iter()
+            .map(|k| switching_key_from_proto(Some(k), None, parameters, level))
+            .chain(
+                value
+                    .higher_bv_ksks
+                    .iter()
+                    .map(|k| switching_key_from_proto(None, Some(k), parameters, level)),
+            )
+            .collect();
+
+        RelinearizationKey {
+            ksk,
+            higher_ksks,
+            level,
+        }
+    }
+}
+
+// Key Switching Key //
+impl TryFromWithParameters for proto::KeySwitchingKey {
+    type Parameters = BfvParameters;
+    type Value = KeySwitchingKey;
+    fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
+        let ctx = parameters.poly_ctx(&PolyType::QP, value.level);
+        let ksk = Some(proto::HybridKeySwitchingKey::try_from_with_context(
+            &value.ksk, &ctx,
+        ));
+
+        proto::KeySwitchingKey {
+            ksk,
+            level: value.level as u32,
+        }
+    }
+}
+
+impl TryFromWithParameters for KeySwitchingKey {
+    type Parameters = BfvParameters;
+    type Value = proto::KeySwitchingKey;
     fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
         let level = value.level as usize;
         let ctx = parameters.poly_ctx(&PolyType::QP, level);
         let ksk = HybridKeySwitchingKey::try_from_with_context(
-            value.ksk.as_ref().expect("Rlk missing"),
+            value.ksk.as_ref().expect("Ksk missing"),
             &ctx,
         );
 
-        RelinearizationKey { ksk, level }
+        KeySwitchingKey { ksk, level }
     }
 }
 
@@ -323,22 +661,30 @@ impl TryFromWithParameters for proto::EvaluationKey {
     type Parameters = BfvParameters;
     type Value = EvaluationKey;
     fn try_from_with_parameters(value: &Self::Value, parameters: &Self::Parameters) -> Self {
-        // since HashMap iterates over values in arbitrary order seralisation of same `EvaluationKey`
-        // twice can produce different `proto::EvaluationKey`s.
+        // HashMap iterates over values in arbitrary order. Thus keys are sorted by level, then exponent,
+        // so that serialization of same `EvaluationKey` always produces same bytes.
         let rlks = value
             .rlks
             .iter()
-            .map(|(i, k)| proto::RelinearizationKey::try_from_with_parameters(&k, parameters))
+            .sorted_by_key(|(level, _)| **level)
+            .map(|(_, k)| proto::RelinearizationKey::try_from_with_parameters(k, parameters))
             .collect_vec();
-        let mut rot_indices = vec![];
-        let rtgs = value
+        let sorted_rtgs = value
             .rtgs
             .iter()
-            .map(|(i, k)| {
-                rot_indices.push(i.0 as i32);
-                proto::GaloisKey::try_from_with_parameters(&k, parameters)
-            })
+            .sorted_by_key(|((exponent, level), _)| (*level, *exponent))
+            .collect_vec();
+        let rtgs = sorted_rtgs
+            .iter()
+            .map(|(_, k)| proto::GaloisKey::try_from_with_parameters(k, parameters))
             .collect_vec();
+        let rot_indices = sorted_rtgs
+            .iter()
+            .map(|((exponent, _), _)| {
+                exponent_to_rot(*exponent, parameters.degree).map(|index| index as i32)
+            })
+            .collect::<Option<Vec<i32>>>()
+            .unwrap_or_default();
 
         proto::EvaluationKey {
             rlks,
@@ -360,23 +706,205 @@ impl TryFromWithParameters for EvaluationKey {
         });
 
         let mut rtgs = HashMap::new();
-        value
-            .rtgs
-            .iter()
-            .zip(value.rot_indices.iter())
-            .for_each(|(gk, rot_index)| {
-                let v = GaloisKey::try_from_with_parameters(gk, parameters);
-                rtgs.insert((*rot_index as isize, v.level), v);
-            });
+        value.rtgs.iter().for_each(|v| {
+            let v = GaloisKey::try_from_with_parameters(v, parameters);
+            rtgs.insert((v.exponent(), v.level), v);
+        });
 
         EvaluationKey { rlks, rtgs }
     }
 }
 
+// Validation //
+/// Checks that `poly` has a limb of the packed byte length for every modulus of `ctx`
+fn validate_poly(poly: &proto::Poly, ctx: &PolyContext<'_>) -> Result<(), String> {
+    if poly.coefficients.len() != ctx.moduli_count() {
+        return Err(format!(
+            "Expected {} moduli, found {}",
+            ctx.moduli_count(),
+            poly.coefficients.len()
+        ));
+    }
+    izip!(poly.coefficients.iter(), ctx.iter_moduli_ops()).try_for_each(|(xi, modqi)| {
+        let bits = (64 - modqi.modulus().leading_zeros()) as usize;
+        if xi.len() != (bits * ctx.degree()).div_ceil(8) {
+            return Err("Invalid polynomial byte length".to_string());
+        }
+        Ok(())
+    })
+}
+
+fn validate_seed(seed: &[u8]) -> Result<(), String> {
+    if seed.len() != <ChaCha8Rng as SeedableRng>::Seed::default().len() {
+        return Err(format!("Invalid seed length {}", seed.len()));
+    }
+    Ok(())
+}
+
+/// Checks that switching key of relinearization or galois key at `level` decodes with
+/// `switching_key_from_proto`
+fn validate_switching_key(
+    ksk: Option<&proto::HybridKeySwitchingKey>,
+    bv_ksk: Option<&proto::BvKeySwitchingKey>,
+    parameters: &BfvParameters,
+    level: usize,
+) -> Result<(), String> {
+    if level > parameters.max_level {
+        return Err(format!("Invalid key level {}", level));
+    }
+    match (ksk, bv_ksk) {
+        (Some(ksk), None) => {
+            // hybrid keys require special moduli at level of the key
+            let ksk_levels = parameters
+                .hybrid_ksk_parameters
+                .as_ref()
+                .map_or(0, |ksk_params| ksk_params.len());
+            if level >= ksk_levels {
+                return Err("Key level not supported by parameters".into());
+            }
+            let dnum = parameters.hybrid_key_switching_params_at_level(level).dnum;
+            if ksk.c0s.len() != dnum {
+                return Err(format!("Expected {} c0s, found {}", dnum, ksk.c0s.len()));
+            }
+            match ksk.seed.as_deref() {
+                Some(seed) => {
+                    validate_seed(seed)?;
+                    if ksk.seed_version != HybridKeySwitchingKey::SEED_VERSION {
+                        return Err(format!(
+                            "Unsupported version {} of seeded key switching key",
+                            ksk.seed_version
+                        ));
+                    }
+                }
+                None => {
+                    if ksk.c1s.len() != dnum {
+                        return Err(format!("Expected {} c1s, found {}", dnum, ksk.c1s.len()));
+                    }
+                }
+            }
+            let ctx = parameters.poly_ctx(&PolyType::QP, level);
+            ksk.c0s
+                .iter()
+                .chain(ksk.c1s.iter())
+                .try_for_each(|p| validate_poly(p, &ctx))
+        }
+        (None, Some(bv_ksk)) => {
+            let ctx = parameters.poly_ctx(&PolyType::Q, level);
+            if bv_ksk.c0s.len() != ctx.moduli_count() {
+                return Err(format!(
+                    "Expected {} c0s, found {}",
+                    ctx.moduli_count(),
+                    bv_ksk.c0s.len()
+                ));
+            }
+            validate_seed(&bv_ksk.seed)?;
+            bv_ksk.c0s.iter().try_for_each(|p| validate_poly(p, &ctx))
+        }
+        _ => Err("Exactly one of hybrid and BV key switching key must be set".into()),
+    }
+}
+
+impl proto::RelinearizationKey {
+    /// Checks that the key decodes with `RelinearizationKey::try_from_with_parameters` without
+    /// panicking. Used to decode keys from untrusted sources.
+    pub(crate) fn validate(&self, parameters: &BfvParameters) -> Result<(), String> {
+        let level = self.level as usize;
+        validate_switching_key(self.ksk.as_ref(), self.bv_ksk.as_ref(), parameters, level)?;
+        self.higher_ksks
+            .iter()
+            .try_for_each(|k| validate_switching_key(Some(k), None, parameters, level))?;
+        self.higher_bv_ksks
+            .iter()
+            .try_for_each(|k| validate_switching_key(None, Some(k), parameters, level))
+    }
+}
+
+impl proto::GaloisKey {
+    /// Checks that the key decodes with `GaloisKey::try_from_with_parameters` without panicking.
+    /// Used to decode keys from untrusted sources.
+    pub(crate) fn validate(&self, parameters: &BfvParameters) -> Result<(), String> {
+        if self.exponent & 1 == 0 {
+            return Err(format!("Invalid galois exponent {}", self.exponent));
+        }
+        validate_switching_key(
+            self.ksk.as_ref(),
+            self.bv_ksk.as_ref(),
+            parameters,
+            self.level as usize,
+        )
+    }
+}
+
+impl proto::PublicKey {
+    /// Checks that the key decodes with `PublicKey::try_from_with_parameters` without panicking.
+    /// Call it before decoding keys from untrusted sources.
+    pub fn validate(&self, parameters: &BfvParameters) -> Result<(), String> {
+        let c0 = self.c0.as_ref().ok_or("c0 missing")?;
+        validate_poly(c0, &parameters.poly_ctx(&PolyType::QP, 0))?;
+        validate_seed(&self.seed)
+    }
+}
+
+// Serialized sizes //
+impl Ciphertext {
+    /// Returns size in bytes of serialized ciphertext. Seeded ciphertexts are serialized at
+    /// half the size (see `Ciphertext::is_seeded`).
+    pub fn serialized_size(&self, params: &BfvParameters) -> usize {
+        proto::Ciphertext::try_from_with_parameters(self, params).encoded_len()
+    }
+}
+
+impl PublicKey {
+    /// Returns size in bytes of serialized public key
+    pub fn serialized_size(&self, params: &BfvParameters) -> usize {
+        proto::PublicKey::try_from_with_parameters(self, params).encoded_len()
+    }
+}
+
+impl RelinearizationKey {
+    /// Returns size in bytes of serialized relinearization key
+    pub fn serialized_size(&self, params: &BfvParameters) -> usize {
+        proto::RelinearizationKey::try_from_with_parameters(self, params).encoded_len()
+    }
+}
+
+impl GaloisKey {
+    /// Returns size in bytes of serialized galois key
+    pub fn serialized_size(&self, params: &BfvParameters) -> usize {
+        proto::GaloisKey::try_from_with_parameters(self, params).encoded_len()
+    }
+}
+
+impl KeySwitchingKey {
+    /// Returns size in bytes of serialized key switching key
+    pub fn serialized_size(&self, params: &BfvParameters) -> usize {
+        proto::KeySwitchingKey::try_from_with_parameters(self, params).encoded_len()
+    }
+}
+
+impl EvaluationKey {
+    /// Returns size in bytes of serialized evaluation key
+    pub fn serialized_size(&self, params: &BfvParameters) -> usize {
+        proto::EvaluationKey::try_from_with_parameters(self, params).encoded_len()
+    }
+
+    /// Returns SHA-256 hash of canonical serialization of the evaluation key bound to fingerprint of
+    /// `params`.
+    ///
+    /// Serialization of `EvaluationKey` is deterministic, thus clients can compare the hash with the one
+    /// published along with the key to verify that the key they received is unmodified.
+    pub fn content_hash(&self, params: &BfvParameters) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"bfv-evaluation-key-v1");
+        hasher.update(params.fingerprint());
+        hasher.update(proto::EvaluationKey::try_from_with_parameters(self, params).encode_to_vec());
+        hasher.finalize().into()
+    }
+}
+
 mod tests {
     use super::*;
-    use crate::{Encoding, Evaluator, SecretKey};
-    use prost::Message;
+    use crate::{Encoding, Evaluator, KeySwitchingStrategy, SecretKey};
     use rand::thread_rng;
 
     #[test]
@@ -392,6 +920,75 @@ mod tests {
         assert_eq!(sk, sk_back);
     }
 
+    #[test]
+    #[should_panic]
+    fn deserialize_truncated_secret_key() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+
+        let mut sk_proto = proto::SecretKey::try_from_with_parameters(&sk, &params);
+        sk_proto.coefficients.truncate(1);
+        SecretKey::try_from_with_parameters(&sk_proto, &params);
+    }
+
+    #[test]
+    fn serialize_and_deserialize_parameters() {
+        let mut params = BfvParameters::new(&[50; 5], 65537, 1 << 4);
+        params.enable_hybrid_key_switching_with_alpha(&[60, 60], 2);
+        params.change_hamming_weight(32);
+
+        let params_proto = proto::Parameters::from(&params);
+        assert_eq!(params_proto.alpha, 2);
+        let params_back = BfvParameters::try_from(
+            &proto::Parameters::decode(params_proto.encode_to_vec().as_slice()).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(params_back.alpha, Some(2));
+        assert_eq!(params_back.dnum, Some(3));
+        assert_eq!(params_back.special_moduli, params.special_moduli);
+        assert_eq!(params_back.fingerprint(), params.fingerprint());
+
+        // without hybrid key switching
+        let params = BfvParameters::new(&[50; 3], 65537, 1 << 4);
+        let params_back = BfvParameters::try_from(&proto::Parameters::from(&params)).unwrap();
+        assert!(params_back.special_moduli.is_none());
+        assert_eq!(params_back.fingerprint(), params.fingerprint());
+    }
+
+    #[test]
+    fn deserialize_invalid_parameters() {
+        let mut params = BfvParameters::new(&[50; 5], 65537, 1 << 4);
+        params.enable_hybrid_key_switching_with_alpha(&[60, 60], 2);
+        let params_proto = proto::Parameters::from(&params);
+
+        let mut invalid = params_proto.clone();
+        invalid.alpha = 0;
+        assert!(BfvParameters::try_from(&invalid).is_err());
+
+        let mut invalid = params_proto.clone();
+        invalid.special_moduli_sizes = vec![30];
+        assert!(BfvParameters::try_from(&invalid).is_err());
+
+        let mut invalid = params_proto.clone();
+        invalid.degree = 17;
+        assert!(BfvParameters::try_from(&invalid).is_err());
+
+        // not enough 10 bit primes
+        let mut invalid = params_proto.clone();
+        invalid.ciphertext_moduli_sizes = vec![10; 5];
+        assert!(BfvParameters::try_from(&invalid).is_err());
+
+        let mut invalid = params_proto.clone();
+        invalid.plaintext_modulus = 65536;
+        assert!(BfvParameters::try_from(&invalid).is_err());
+
+        let mut invalid = params_proto;
+        invalid.hw = 0;
+        assert!(BfvParameters::try_from(&invalid).is_err());
+    }
+
     #[test]
     fn serialize_and_deserialize_ciphertexts() {
         let mut rng = thread_rng();
@@ -452,6 +1049,34 @@ mod tests {
         assert_eq!(ksk, ksk_back);
     }
 
+    #[test]
+    #[should_panic(expected = "Unsupported version 0 of seeded key switching key")]
+    fn unversioned_seeded_hybrid_ksk_is_rejected() {
+        let params = BfvParameters::default(3, 1 << 4);
+        let qp_ctx = params.poly_ctx(&PolyType::QP, 0);
+        let ksk_ctx = params.poly_ctx(&PolyType::Q, 0);
+
+        let mut rng = thread_rng();
+        let poly = ksk_ctx.random(Representation::Evaluation, &mut rng);
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+        let ksk = HybridKeySwitchingKey::new(
+            params.hybrid_key_switching_params_at_level(0),
+            &poly,
+            &sk,
+            &qp_ctx,
+            params.variance,
+            &mut rng,
+        );
+
+        let mut ksk_proto = proto::HybridKeySwitchingKey::try_from_with_context(&ksk, &qp_ctx);
+        assert!(ksk_proto.seed.is_some());
+        assert_eq!(ksk_proto.seed_version, HybridKeySwitchingKey::SEED_VERSION);
+
+        // keys written before c1s were sampled from a single stream have no version
+        ksk_proto.seed_version = 0;
+        HybridKeySwitchingKey::try_from_with_context(&ksk_proto, &qp_ctx);
+    }
+
     #[test]
     fn serialize_and_deserialize_rlk() {
         let params = BfvParameters::default(6, 1 << 4);
@@ -465,6 +1090,62 @@ mod tests {
         let rlk_back = RelinearizationKey::try_from_with_parameters(&rlk_proto, &params);
 
         assert_eq!(rlk, rlk_back);
+
+        // keys for s^3 and s^4 of higher degree key
+        let rlk = RelinearizationKey::new_with_degree(
+            &params,
+            &sk,
+            0,
+            4,
+            KeySwitchingStrategy::Hybrid,
+            &mut rng,
+        );
+        let rlk_proto = proto::RelinearizationKey::try_from_with_parameters(&rlk, &params);
+        assert_eq!(rlk_proto.higher_ksks.len(), 2);
+        let rlk_back = RelinearizationKey::try_from_with_parameters(&rlk_proto, &params);
+        assert_eq!(rlk_back.degree(), 4);
+        assert_eq!(rlk, rlk_back);
+    }
+
+    #[test]
+    fn serialize_and_deserialize_bv_keys() {
+        // BV keys do not require special moduli
+        let params = BfvParameters::new(&[50; 3], 65537, 1 << 4);
+
+        let mut rng = thread_rng();
+        let sk = SecretKey::random(params.degree, params.hw, &mut rng);
+
+        let ek = EvaluationKey::new_with_strategy(
+            &params,
+            &sk,
+            &[0, 1],
+            &[0, 1],
+            &[1, -1],
+            KeySwitchingStrategy::Bv,
+            &mut rng,
+        );
+
+        let rlk_proto = proto::RelinearizationKey::try_from_with_parameters(&ek.rlks[&1], &params);
+        assert!(rlk_proto.ksk.is_none());
+        let rlk_back = RelinearizationKey::try_from_with_parameters(&rlk_proto, &params);
+        assert_eq!(ek.rlks[&1], rlk_back);
+
+        let rlk = RelinearizationKey::new_with_degree(
+            &params,
+            &sk,
+            0,
+            3,
+            KeySwitchingStrategy::Bv,
+            &mut rng,
+        );
+        let rlk_proto = proto::RelinearizationKey::try_from_with_parameters(&rlk, &params);
+        assert_eq!(rlk_proto.higher_bv_ksks.len(), 1);
+        let rlk_back = RelinearizationKey::try_from_with_parameters(&rlk_proto, &params);
+        assert_eq!(rlk, rlk_back);
+
+        let ek_proto = proto::EvaluationKey::try_from_with_parameters(&ek, &params);
+        let ek_back = EvaluationKey::try_from_with_parameters(&ek_proto, &params);
+        assert_eq!(ek, ek_back);
     }
 
     #[test]
@@ -488,4 +1169,212 @@ mod tests {
 
         assert_eq!(ek, ek_back);
     }
+
+    #[test]
+    fn evaluation_key_serialization_is_canonical() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(6, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let ek = EvaluationKey::new(
+            &params,
+            &sk,
+            &[0, 1, 2],
+            &[0, 0, 1, 1, 2],
+            &[1, -1, 2, 1, 3],
+            &mut rng,
+        );
+
+        let bytes = proto::EvaluationKey::try_from_with_parameters(&ek, &params).encode_to_vec();
+        let ek_back = EvaluationKey::try_from_with_parameters(
+            &proto::EvaluationKey::decode(bytes.as_slice()).unwrap(),
+            &params,
+        );
+        // `HashMap`s of `ek_back` iterate in different order than of `ek`
+        assert_eq!(
+            bytes,
+            proto::EvaluationKey::try_from_with_parameters(&ek_back, &params).encode_to_vec()
+        );
+        assert_eq!(ek.content_hash(&params), ek_back.content_hash(&params));
+
+        // keys are sorted by level, then exponent
+        let ek_proto = proto::EvaluationKey::decode(bytes.as_slice()).unwrap();
+        assert_eq!(
+            ek_proto.rlks.iter().map(|k| k.level).collect_vec(),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            ek_proto.rtgs.iter().map(|k| k.exponent).collect_vec(),
+            [3, 11, 3, 9, 27]
+        );
+        assert_eq!(
+            ek_proto.rtgs.iter().map(|k| k.level).collect_vec(),
+            [0, 0, 1, 1, 2]
+        );
+        // rotation indices for older readers
+        assert_eq!(ek_proto.rot_indices, [1, -1, 1, 2, 3]);
+
+        // galois key that isn't a rotation
+        let mut ek = ek;
+        ek.insert_galois_key(GaloisKey::new(
+            2 * params.degree - 3,
+            &params,
+            0,
+            &sk,
+            &mut rng,
+        ));
+        let ek_proto = proto::EvaluationKey::try_from_with_parameters(&ek, &params);
+        assert!(ek_proto.rot_indices.is_empty());
+        assert_eq!(
+            ek,
+            EvaluationKey::try_from_with_parameters(&ek_proto, &params)
+        );
+
+        // hash changes with key and with parameters
+        let other_ek = EvaluationKey::new(&params, &sk, &[0, 1, 2], &[0], &[1], &mut rng);
+        assert_ne!(ek.content_hash(&params), other_ek.content_hash(&params));
+        let other_params = BfvParameters::default(6, 1 << 5);
+        let other_sk = SecretKey::random_with_params(&other_params, &mut rng);
+        let ek_other_params =
+            EvaluationKey::new(&other_params, &other_sk, &[0], &[], &[], &mut rng);
+        let ek_same = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+        assert_ne!(
+            ek_same.content_hash(&params),
+            ek_other_params.content_hash(&other_params)
+        );
+    }
+
+    #[test]
+    fn seeded_ciphertext_is_half_size() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let evaluator = Evaluator::new(params);
+        let pt = evaluator.plaintext_encode(
+            &m,
+            Encoding::simd(0, crate::PolyCache::AddSub(Representation::Coefficient)),
+        );
+        let ct = evaluator.encrypt(&sk, &pt, &mut rng);
+        assert!(ct.is_seeded());
+
+        let ct_proto = proto::Ciphertext::try_from_with_parameters(&ct, evaluator.params());
+        assert_eq!(ct_proto.c.len(), 1);
+        let ct_back = Ciphertext::try_from_with_parameters(
+            &proto::Ciphertext::decode(ct_proto.encode_to_vec().as_slice()).unwrap(),
+            evaluator.params(),
+        );
+        assert_eq!(ct, ct_back);
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &ct_back), Encoding::default()),
+            m
+        );
+
+        // seed remains valid after plaintext additions, but not after ciphertext additions
+        let ct_add = evaluator.add_plaintext(&ct, &pt);
+        assert!(ct_add.is_seeded());
+        let ct_sum = evaluator.add(&ct, &ct);
+        assert!(!ct_sum.is_seeded());
+        // c0 and 32 byte seed
+        assert!(
+            ct.serialized_size(evaluator.params())
+                <= ct_sum.serialized_size(evaluator.params()) / 2 + 40
+        );
+
+        let mut ct_mut = ct.clone();
+        evaluator.add_assign_plaintext(&mut ct_mut, &pt);
+        assert!(ct_mut.is_seeded());
+        assert_eq!(ct_mut, ct_add);
+        evaluator.sub_assign_plaintext(&mut ct_mut, &pt);
+        assert!(ct_mut.is_seeded());
+        assert!(evaluator.sub_plaintext(&ct_add, &pt).is_seeded());
+        ct_mut.c_ref_mut();
+        assert!(!ct_mut.is_seeded());
+    }
+
+    #[test]
+    fn serialize_and_deserialize_public_key() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let pk = PublicKey::new(&params, &sk, &mut rng);
+
+        let pk_proto = proto::PublicKey::try_from_with_parameters(&pk, &params);
+        let pk_back = PublicKey::try_from_with_parameters(
+            &proto::PublicKey::decode(pk_proto.encode_to_vec().as_slice()).unwrap(),
+            &params,
+        );
+        assert_eq!(pk, pk_back);
+
+        let mut invalid = pk_proto.clone();
+        invalid.seed.pop();
+        assert!(invalid.validate(&params).is_err());
+        let mut invalid = pk_proto.clone();
+        invalid.c0 = None;
+        assert!(invalid.validate(&params).is_err());
+
+        // only c0 is serialized
+        let qp_ctx = params.poly_ctx(&PolyType::QP, 0);
+        let mut c0 = pk.c0.clone();
+        qp_ctx.change_representation(&mut c0, Representation::Coefficient);
+        let c0_size = proto::Poly::try_from_with_context(&c0, &qp_ctx).encoded_len();
+        assert!(pk.serialized_size(&params) <= c0_size + 64);
+    }
+
+    #[test]
+    fn serialize_and_deserialize_key_switching_key() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let to_sk = SecretKey::random_with_params(&params, &mut rng);
+        let to_pk = PublicKey::new(&params, &to_sk, &mut rng);
+
+        let ksk = KeySwitchingKey::new(&params, &sk, &to_sk, 1, &mut rng);
+        let ksk_back = KeySwitchingKey::try_from_with_parameters(
+            &proto::KeySwitchingKey::try_from_with_parameters(&ksk, &params),
+            &params,
+        );
+        assert_eq!(ksk, ksk_back);
+
+        // key generated with public key is not seeded and is twice the size
+        let ksk_pk = KeySwitchingKey::new_with_public_key(&params, &sk, &to_pk, 1, &mut rng);
+        let ksk_pk_back = KeySwitchingKey::try_from_with_parameters(
+            &proto::KeySwitchingKey::try_from_with_parameters(&ksk_pk, &params),
+            &params,
+        );
+        assert_eq!(ksk_pk, ksk_pk_back);
+        assert!(ksk.serialized_size(&params) <= ksk_pk.serialized_size(&params) / 2 + 40);
+    }
+
+    #[test]
+    fn evaluation_keys_are_seeded() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(6, 1 << 4);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0, 1], &[0, 1], &[1, -1], &mut rng);
+
+        let ek_proto = proto::EvaluationKey::try_from_with_parameters(&ek, &params);
+        ek_proto
+            .rlks
+            .iter()
+            .map(|k| k.ksk.as_ref().unwrap())
+            .chain(ek_proto.rtgs.iter().map(|k| k.ksk.as_ref().unwrap()))
+            .for_each(|ksk| {
+                assert!(ksk.seed.is_some());
+                assert!(ksk.c1s.is_empty());
+            });
+
+        let ek_back = EvaluationKey::try_from_with_parameters(
+            &proto::EvaluationKey::decode(ek_proto.encode_to_vec().as_slice()).unwrap(),
+            &params,
+        );
+        assert_eq!(ek, ek_back);
+        assert_eq!(ek.serialized_size(&params), ek_proto.encoded_len());
+    }
 }
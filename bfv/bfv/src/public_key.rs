@@ -0,0 +1,155 @@
+use crate::{BfvParameters, Ciphertext, Plaintext, Poly, PolyType, Representation, SecretKey};
+use ndarray::Axis;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Public key `(-a*s + e, a)` of a secret key `s`.
+///
+/// Public key is defined over QP at level 0, so that it can be used to encrypt at any level and to
+/// generate key switching keys to the secret key without knowing the secret key. `a` is derived from
+/// seed, hence only `c0` and the seed are serialized.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PublicKey {
+    pub(crate) c0: Poly,
+    pub(crate) c1: Poly,
+    pub(crate) seed: <ChaCha8Rng as SeedableRng>::Seed,
+}
+
+impl PublicKey {
+    /// Panics if hybrid key switching is not enabled for `params`
+    pub fn new<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> PublicKey {
+        let qp_ctx = params.poly_ctx(&PolyType::QP, 0);
+
+        let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+        rng.fill_bytes(&mut seed);
+        let c1 = PublicKey::generate_c1(params, seed);
+
+        let mut c0 = qp_ctx.random_gaussian(Representation::Coefficient, params.variance, rng);
+        qp_ctx.change_representation(&mut c0, Representation::Evaluation);
+        // e - a*s
+        qp_ctx.sub_assign(&mut c0, &qp_ctx.mul(&sk.to_poly(&qp_ctx), &c1));
+
+        PublicKey { c0, c1, seed }
+    }
+
+    /// Generates `a` from seed in `Evaluation` representation
+    pub(crate) fn generate_c1(
+        params: &BfvParameters,
+        seed: <ChaCha8Rng as SeedableRng>::Seed,
+    ) -> Poly {
+        let qp_ctx = params.poly_ctx(&PolyType::QP, 0);
+        let mut c1 = qp_ctx.random_with_seed(seed);
+        qp_ctx.change_representation(&mut c1, Representation::Evaluation);
+        c1
+    }
+
+    /// Returns public key polynomials over `poly_type` (Q or QP) at `level` by dropping moduli
+    /// that are not present at `level`.
+    pub(crate) fn polys_at_level(
+        &self,
+        params: &BfvParameters,
+        poly_type: &PolyType,
+        level: usize,
+    ) -> (Poly, Poly) {
+        let q_count = params.q_size - level;
+        let indices = match poly_type {
+            PolyType::Q => (0..q_count).collect::<Vec<usize>>(),
+            PolyType::QP => (0..q_count)
+                .chain(params.q_size..self.c0.coefficients.shape()[0])
+                .collect::<Vec<usize>>(),
+            _ => panic!("Public key is only defined over Q and QP"),
+        };
+
+        let drop_moduli = |p: &Poly| {
+            Poly::new(
+                p.coefficients.select(Axis(0), &indices),
+                Representation::Evaluation,
+            )
+        };
+        (drop_moduli(&self.c0), drop_moduli(&self.c1))
+    }
+
+    /// Returns encryption of zero at `level`, ie `(u*pk0 + e0, u*pk1 + e1)` with ternary `u`.
+    pub fn encrypt_zero<R: CryptoRng + RngCore>(
+        &self,
+        params: &BfvParameters,
+        level: usize,
+        rng: &mut R,
+    ) -> Ciphertext {
+        let ctx = params.poly_ctx(&PolyType::Q, level);
+        let (pk0, pk1) = self.polys_at_level(params, &PolyType::Q, level);
+
+        let u = SecretKey::random(params.degree, params.hw, rng).to_poly(&ctx);
+
+        let c = [pk0, pk1]
+            .iter()
+            .map(|pk| {
+                let mut e = ctx.random_gaussian(Representation::Coefficient, params.variance, rng);
+                ctx.change_representation(&mut e, Representation::Evaluation);
+                ctx.add_assign(&mut e, &ctx.mul(&u, pk));
+                ctx.change_representation(&mut e, Representation::Coefficient);
+                e
+            })
+            .collect::<Vec<Poly>>();
+
+        Ciphertext {
+            c,
+            poly_type: PolyType::Q,
+            level,
+            seed: None,
+            noise_estimate: Some(params.public_key_noise_bits()),
+        }
+    }
+
+    /// Encrypts given plaintext with the public key
+    pub fn encrypt<R: CryptoRng + RngCore>(
+        &self,
+        params: &BfvParameters,
+        pt: &Plaintext,
+        rng: &mut R,
+    ) -> Ciphertext {
+        let encoding = pt.encoding.as_ref().expect("Plaintext encoding missing!");
+        let ctx = params.poly_ctx(&PolyType::Q, encoding.level);
+
+        let mut ct = self.encrypt_zero(params, encoding.level, rng);
+        ctx.add_assign(
+            &mut ct.c[0],
+            &pt.scale_plaintext(params, Representation::Coefficient),
+        );
+        ct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Encoding;
+    use rand::thread_rng;
+
+    #[test]
+    fn public_key_encryption_works() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let pk = PublicKey::new(&params, &sk, &mut rng);
+
+        for level in 0..params.max_level {
+            let m = params
+                .plaintext_modulus_op
+                .random_vec(params.degree, &mut rng);
+            let pt = Plaintext::encode(&m, &params, Encoding::simd(level, crate::PolyCache::None));
+            let ct = pk.encrypt(&params, &pt, &mut rng);
+            assert_eq!(ct.level, level);
+
+            let m_back: Vec<u64> = sk.decrypt(&ct, &params).decode(Encoding::default(), &params);
+            assert_eq!(m, m_back);
+
+            // noise estimate is conservative
+            assert!(sk.measure_noise(&ct, &params) as usize <= ct.noise_estimate.unwrap());
+        }
+    }
+}
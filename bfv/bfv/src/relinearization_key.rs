@@ -1,11 +1,18 @@
+use crate::key_switching_key::SwitchingKey;
 use crate::{
-    BfvParameters, Ciphertext, HybridKeySwitchingKey, PolyType, Representation, SecretKey,
+    BfvParameters, Ciphertext, KeySwitchingStrategy, Poly, PolyType, Representation, ScratchArena,
+    SecretKey,
 };
+use itertools::izip;
 use rand::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
 
 #[derive(PartialEq, Debug)]
 pub struct RelinearizationKey {
-    pub(crate) ksk: HybridKeySwitchingKey,
+    /// Key switching key for s^2
+    pub(crate) ksk: SwitchingKey,
+    /// Key switching keys for s^3, s^4, ... up to degree of the key
+    pub(crate) higher_ksks: Vec<SwitchingKey>,
     pub(crate) level: usize,
 }
 
@@ -16,58 +23,183 @@ impl RelinearizationKey {
         level: usize,
         rng: &mut R,
     ) -> RelinearizationKey {
+        RelinearizationKey::new_with_strategy(
+            params,
+            sk,
+            level,
+            KeySwitchingStrategy::default(),
+            rng,
+        )
+    }
+
+    /// Generates relinearization key for ciphertexts at `level` that uses `strategy` for key switching.
+    /// The key also relinearizes ciphertexts at any level higher than `level`.
+    pub fn new_with_strategy<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        level: usize,
+        strategy: KeySwitchingStrategy,
+        rng: &mut R,
+    ) -> RelinearizationKey {
+        RelinearizationKey::new_with_degree(params, sk, level, 2, strategy, rng)
+    }
+
+    /// Generates relinearization key that relinearizes ciphertexts of degree up to `degree`, ie with up
+    /// to `degree + 1` polynomials, to degree 1. The key consists of key switching keys for s^2, ..., s^degree.
+    ///
+    /// Products of ciphertexts that are not relinearized have higher degree (see `Evaluator::mul_lazy`).
+    /// Such products can be summed up and relinearized once, instead of relinearizing every operand.
+    pub fn new_with_degree<R: CryptoRng + RngCore>(
+        params: &BfvParameters,
+        sk: &SecretKey,
+        level: usize,
+        degree: usize,
+        strategy: KeySwitchingStrategy,
+        rng: &mut R,
+    ) -> RelinearizationKey {
+        assert!(
+            degree >= 2,
+            "Relinearization key must be at least of degree 2"
+        );
+
         let q_ctx = params.poly_ctx(&PolyType::Q, level);
-        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
 
-        let mut sk_poly =
-            q_ctx.try_convert_from_i64_small(&sk.coefficients, Representation::Coefficient);
-        q_ctx.change_representation(&mut sk_poly, Representation::Evaluation);
+        let sk_poly = sk.to_poly(&q_ctx);
 
         // sk^2
-        let sk_sq = q_ctx.mul(&sk_poly, &sk_poly);
+        let mut sk_power = Zeroizing::new(q_ctx.mul(&sk_poly, &sk_poly));
 
         // Key switching key
-        let ksk = HybridKeySwitchingKey::new(
-            params.hybrid_key_switching_params_at_level(level),
-            &sk_sq,
-            sk,
-            &qp_ctx,
-            params.variance,
-            rng,
-        );
+        let ksk = SwitchingKey::new(strategy, params, level, &sk_power, sk, rng);
 
-        RelinearizationKey { ksk, level }
+        // sk^3, ..., sk^degree
+        let higher_ksks = (3..=degree)
+            .map(|_| {
+                q_ctx.mul_assign(&mut sk_power, &sk_poly);
+                SwitchingKey::new(strategy, params, level, &sk_power, sk, rng)
+            })
+            .collect();
+
+        RelinearizationKey {
+            ksk,
+            higher_ksks,
+            level,
+        }
+    }
+
+    pub fn strategy(&self) -> KeySwitchingStrategy {
+        self.ksk.strategy()
+    }
+
+    /// Returns maximum degree of ciphertexts the key relinearizes
+    pub fn degree(&self) -> usize {
+        2 + self.higher_ksks.len()
     }
 
     pub fn relinearize(&self, ct: &Ciphertext, params: &BfvParameters) -> Ciphertext {
-        assert!(ct.c.len() == 3); // otherwise invalid relinerization
+        let mut out = Ciphertext::placeholder();
+        ScratchArena::with_thread_local(|scratch| {
+            self.relinearize_into(ct, params, scratch, &mut out)
+        });
+        out
+    }
+
+    /// Same as `relinearize` but writes the result to `out`. Intermediate polynomials are taken from
+    /// `scratch`. Polynomials of `out` are overwritten in place if they are in context Q at level of
+    /// `ct`, otherwise they are replaced with ones taken from `scratch`.
+    pub(crate) fn relinearize_into(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+        scratch: &ScratchArena,
+        out: &mut Ciphertext,
+    ) {
+        let q_ctx = params.poly_ctx(&PolyType::Q, ct.level);
+        scratch.prepare_ciphertext(out, &q_ctx, 2);
+        let (cs0, cs1) = out.c.split_at_mut(1);
+        let (cs0, cs1) = (&mut cs0[0], &mut cs1[0]);
+        self.switch_higher_degree_into(ct, params, scratch, cs0, cs1);
+
+        q_ctx.add_assign(cs0, &ct.c[0]);
+        q_ctx.add_assign(cs1, &ct.c[1]);
+
+        out.poly_type = PolyType::Q;
+        out.level = ct.level;
+        out.seed = None;
+        out.noise_estimate = self.noise_estimate(ct, params);
+    }
+
+    /// Relinearizes `ct` in place. Polynomials no longer needed by `ct` are returned to `scratch`.
+    pub(crate) fn relinearize_assign(
+        &self,
+        ct: &mut Ciphertext,
+        params: &BfvParameters,
+        scratch: &ScratchArena,
+    ) {
+        let q_ctx = params.poly_ctx(&PolyType::Q, ct.level);
+        let mut cs0 = scratch.take_poly(&q_ctx, Representation::Evaluation);
+        let mut cs1 = scratch.take_poly(&q_ctx, Representation::Evaluation);
+        self.switch_higher_degree_into(ct, params, scratch, &mut cs0, &mut cs1);
+
+        q_ctx.add_assign(&mut ct.c[0], &cs0);
+        q_ctx.add_assign(&mut ct.c[1], &cs1);
+        scratch.give(cs0);
+        scratch.give(cs1);
+
+        ct.noise_estimate = self.noise_estimate(ct, params);
+        ct.c.drain(2..).for_each(|p| scratch.give(p));
+        ct.poly_type = PolyType::Q;
+        ct.seed = None;
+    }
+
+    /// Key switches `c2, ..., ck` of `ct` with keys for `s^2, ..., s^k` respectively and writes sum of
+    /// the results to `cs0` and `cs1` in `Coefficient` representation. `cs0` and `cs1` must be in
+    /// context Q at level of `ct`.
+    fn switch_higher_degree_into(
+        &self,
+        ct: &Ciphertext,
+        params: &BfvParameters,
+        scratch: &ScratchArena,
+        cs0: &mut Poly,
+        cs1: &mut Poly,
+    ) {
+        assert!(ct.c.len() >= 3); // otherwise invalid relinerization
+        assert!(
+            ct.c.len() - 1 <= self.degree(),
+            "Relinearization key of degree {} cannot relinearize ciphertext of degree {}",
+            self.degree(),
+            ct.c.len() - 1
+        );
         assert!(ct.c[0].representation == Representation::Coefficient);
-        assert!(ct.level == self.level);
+        // key generated at a lower level can be used at higher levels
+        assert!(ct.level >= self.level);
 
         let level = ct.level;
         let q_ctx = params.poly_ctx(&PolyType::Q, level);
-        let qp_ctx = params.poly_ctx(&PolyType::QP, level);
-        let specialp_ctx = params.poly_ctx(&PolyType::SpecialP, level);
-
-        let (mut cs0, mut cs1) = self.ksk.switch(
-            params.hybrid_key_switching_params_at_level(self.level),
-            &ct.c[2],
-            &qp_ctx,
-            &q_ctx,
-            &specialp_ctx,
-        );
-        q_ctx.change_representation(&mut cs0, Representation::Coefficient);
-        q_ctx.change_representation(&mut cs1, Representation::Coefficient);
-
-        q_ctx.add_assign(&mut cs0, &ct.c[0]);
-        q_ctx.add_assign(&mut cs1, &ct.c[1]);
 
-        Ciphertext {
-            c: vec![cs0, cs1],
-            poly_type: PolyType::Q,
-            level: ct.level,
-            seed: None,
+        self.ksk
+            .switch_into(params, level, &ct.c[2], scratch, cs0, cs1);
+        if ct.c.len() > 3 {
+            let mut c0 = scratch.take_poly(&q_ctx, Representation::Evaluation);
+            let mut c1 = scratch.take_poly(&q_ctx, Representation::Evaluation);
+            izip!(self.higher_ksks.iter(), ct.c[3..].iter()).for_each(|(ksk, c)| {
+                ksk.switch_into(params, level, c, scratch, &mut c0, &mut c1);
+                q_ctx.add_assign(cs0, &c0);
+                q_ctx.add_assign(cs1, &c1);
+            });
+            scratch.give(c0);
+            scratch.give(c1);
         }
+        q_ctx.change_representation(cs0, Representation::Coefficient);
+        q_ctx.change_representation(cs1, Representation::Coefficient);
+    }
+
+    /// Returns noise estimate of `ct` after relinearization. Noise of each of the `ct.c.len() - 2` key
+    /// switches is added.
+    fn noise_estimate(&self, ct: &Ciphertext, params: &BfvParameters) -> Option<usize> {
+        let key_switches = ct.c.len() - 2;
+        let ks_noise = self.ksk.noise_bits(params, ct.level) + key_switches.ilog2() as usize;
+        ct.noise_estimate.map(|n| std::cmp::max(n, ks_noise) + 1)
     }
 }
 
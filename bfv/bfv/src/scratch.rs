@@ -0,0 +1,194 @@
+use crate::{Ciphertext, Poly, PolyContext, Representation};
+use ndarray::Array2;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Default no. of buffers of a single shape retained by `ScratchArena`.
+const DEFAULT_MAX_BUFFERS_PER_SHAPE: usize = 32;
+
+/// No. of buffers of a single shape retained by arena of each thread used by operations that are
+/// not given an arena (see `ScratchArena::with_thread_local`). Buffers are retained until the thread
+/// exits or `ScratchArena::clear_thread_local` is called.
+const THREAD_LOCAL_MAX_BUFFERS_PER_SHAPE: usize = 4;
+
+/// Buffers bucketed by shape, ie (no. of moduli, degree)
+type Buffers = HashMap<(usize, usize), Vec<Array2<u64>>>;
+
+thread_local! {
+    // arena is only accessed by its own thread, thus a single shard suffices
+    static THREAD_LOCAL_ARENA: ScratchArena =
+        ScratchArena::with_shards(THREAD_LOCAL_MAX_BUFFERS_PER_SHAPE, 1);
+}
+
+/// Pool of coefficient buffers that are reused across evaluator operations.
+///
+/// Intermediate polynomials of `Evaluator` operations (for ex, polynomials extended to PQ basis in
+/// `mul_lazy` or polynomials in QP basis during key switching) are taken from the arena and returned
+/// to it once the operation finishes. Buffers of ciphertexts that are no longer needed can be returned
+/// with `Evaluator::recycle`, which makes `*_into` variants of operations allocation free once the
+/// arena is warmed up.
+///
+/// Buffers are bucketed by shape, ie (no. of moduli, degree). At most `max_buffers_per_shape` buffers
+/// are retained per shape and shard, rest are dropped. Arena is safe to share between threads. With
+/// `parallel` feature the arena is sharded per thread of rayon's pool, so that threads do not contend
+/// on a single lock. A thread takes buffers from its own shard and only falls back to other shards
+/// that are not locked if its own is empty.
+#[derive(Debug)]
+pub struct ScratchArena {
+    shards: Box<[Mutex<Buffers>]>,
+    max_buffers_per_shape: usize,
+}
+
+impl Default for ScratchArena {
+    fn default() -> Self {
+        ScratchArena::new(DEFAULT_MAX_BUFFERS_PER_SHAPE)
+    }
+}
+
+impl ScratchArena {
+    pub fn new(max_buffers_per_shape: usize) -> ScratchArena {
+        // one shard for each thread of the pool and one for threads outside of it
+        #[cfg(feature = "parallel")]
+        let shard_count = rayon::current_num_threads() + 1;
+        #[cfg(not(feature = "parallel"))]
+        let shard_count = 1;
+
+        ScratchArena::with_shards(max_buffers_per_shape, shard_count)
+    }
+
+    fn with_shards(max_buffers_per_shape: usize, shard_count: usize) -> ScratchArena {
+        ScratchArena {
+            shards: (0..shard_count)
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+            max_buffers_per_shape,
+        }
+    }
+
+    /// Calls `f` with arena of the current thread. Used by operations that are not given an arena, so
+    /// that their intermediate buffers are reused across calls instead of being allocated every time.
+    pub(crate) fn with_thread_local<R>(f: impl FnOnce(&ScratchArena) -> R) -> R {
+        THREAD_LOCAL_ARENA.with(f)
+    }
+
+    /// Drops all buffers held by arena of the current thread, ie buffers of operations that were
+    /// called on this thread without an arena (for ex. `GaloisKey::rotate`). `Evaluator` operations
+    /// use `Evaluator::scratch_arena` instead.
+    pub fn clear_thread_local() {
+        ScratchArena::with_thread_local(|arena| arena.clear());
+    }
+
+    /// Returns shard of the current thread
+    fn shard_index(&self) -> usize {
+        #[cfg(feature = "parallel")]
+        let index = rayon::current_thread_index().map_or(0, |i| i + 1) % self.shards.len();
+        #[cfg(not(feature = "parallel"))]
+        let index = 0;
+        index
+    }
+
+    /// Returns buffer of `shape`. Values of a reused buffer are arbitrary.
+    pub(crate) fn take(&self, shape: (usize, usize)) -> Array2<u64> {
+        let index = self.shard_index();
+        if let Some(buffer) = self.shards[index]
+            .lock()
+            .unwrap()
+            .get_mut(&shape)
+            .and_then(|b| b.pop())
+        {
+            return buffer;
+        }
+
+        self.shards
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .find_map(|(_, shard)| shard.try_lock().ok()?.get_mut(&shape)?.pop())
+            .unwrap_or_else(|| Array2::zeros(shape))
+    }
+
+    /// Returns polynomial in context `ctx` with arbitrary coefficients and `representation`.
+    pub(crate) fn take_poly(&self, ctx: &PolyContext<'_>, representation: Representation) -> Poly {
+        Poly::new(self.take((ctx.moduli_count, ctx.degree)), representation)
+    }
+
+    /// Returns `poly`'s buffer to the arena.
+    pub(crate) fn give(&self, poly: Poly) {
+        let coefficients = poly.coefficients;
+        // Buffers of polynomials switched down with `mod_down_next` still own memory of dropped rows
+        // and are handed out for their current shape. Buffers with other layouts and empty buffers (for ex,
+        // of placeholders) are dropped.
+        if !coefficients.is_standard_layout() || coefficients.is_empty() {
+            return;
+        }
+
+        let mut buffers = self.shards[self.shard_index()].lock().unwrap();
+        let bucket = buffers.entry(coefficients.dim()).or_default();
+        if bucket.len() < self.max_buffers_per_shape {
+            bucket.push(coefficients);
+        }
+    }
+
+    /// Returns buffers of all polynomials of `ct` to the arena.
+    pub(crate) fn give_ciphertext(&self, ct: Ciphertext) {
+        ct.c.into_iter().for_each(|p| self.give(p));
+    }
+
+    /// Makes sure `ct` has `count` polynomials in context `ctx`. Existing polynomials are reused if
+    /// they are in `ctx`, otherwise they are returned to the arena and replaced. Coefficients and
+    /// representation of polynomials are arbitrary.
+    pub(crate) fn prepare_ciphertext(
+        &self,
+        ct: &mut Ciphertext,
+        ctx: &PolyContext<'_>,
+        count: usize,
+    ) {
+        let shape = (ctx.moduli_count, ctx.degree);
+        if ct.c.len() != count || ct.c.iter().any(|p| p.coefficients.dim() != shape) {
+            std::mem::take(&mut ct.c)
+                .into_iter()
+                .for_each(|p| self.give(p));
+            ct.c = (0..count)
+                .map(|_| self.take_poly(ctx, Representation::Unknown))
+                .collect();
+        }
+    }
+
+    /// Returns no. of buffers held by the arena.
+    pub fn buffers(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                shard
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .map(|b| b.len())
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// Returns total size in bytes of buffers held by the arena.
+    pub fn size_in_bytes(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                shard
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .flatten()
+                    .map(|b| b.len() * std::mem::size_of::<u64>())
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// Drops all buffers held by the arena.
+    pub fn clear(&self) {
+        self.shards
+            .iter()
+            .for_each(|shard| shard.lock().unwrap().clear());
+    }
+}
@@ -5,24 +5,68 @@ use itertools::Itertools;
 use rand::distributions::{Distribution, Uniform};
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
-
-#[derive(Clone, PartialEq, Debug)]
+use std::fmt::{Debug, Formatter};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Secret key with coefficients sampled from ternary distribution.
+///
+/// Coefficients are zeroized on drop. `SecretKey` intentionally does not implement `Clone` to avoid
+/// accidental copies of secret material, and `Debug` does not print coefficients. Equality is checked
+/// in constant time.
 pub struct SecretKey {
     pub(crate) coefficients: Box<[i64]>,
 }
 
+impl Debug for SecretKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretKey")
+            .field("coefficients", &"<redacted>")
+            .finish()
+    }
+}
+
+impl ConstantTimeEq for SecretKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.coefficients.ct_eq(&other.coefficients)
+    }
+}
+
+impl PartialEq for SecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Zeroize for SecretKey {
+    fn zeroize(&mut self) {
+        self.coefficients.zeroize();
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretKey {}
+
 impl SecretKey {
     /// Generates a random secret key with fixed hamming weight `hw`.
     ///
     /// The code is adapted from [Lattigo](https://github.com/tuneinsight/lattigo)
+    ///
+    /// Sign of each non-zero coefficient is set without branching on the sampled bit. All intermediate
+    /// buffers are zeroized.
     pub fn random<R: CryptoRng + RngCore>(degree: usize, hw: usize, rng: &mut R) -> SecretKey {
-        let mut sk = vec![0i64; degree];
+        let mut sk = vec![0i64; degree].into_boxed_slice();
 
         // Think of indices vec as a set from which we sample `hw` indices to either set 1 or -1.
-        let mut indices = (0..degree).into_iter().collect_vec();
+        let mut indices = Zeroizing::new((0..degree).collect_vec());
 
         // We need `hw` random bits.
-        let mut random_bytes = vec![0u8; (hw as f64 / 8.0).ceil() as usize];
+        let mut random_bytes = Zeroizing::new(vec![0u8; (hw as f64 / 8.0).ceil() as usize]);
         rng.fill_bytes(&mut random_bytes);
 
         let mut byte_index = 0;
@@ -32,13 +76,8 @@ impl SecretKey {
             // sample random index in range [0, indices.len())
             let sampled_index = rng.gen_range(0..degree - i);
 
-            match random_bytes[byte_index] & 1 {
-                0 => sk[indices[sampled_index]] = 1,
-                1 => sk[indices[sampled_index]] = -1,
-                _ => {
-                    panic!("Impossible!")
-                }
-            }
+            // map bit {0, 1} to {1, -1}
+            sk[indices[sampled_index]] = 1 - 2 * ((random_bytes[byte_index] & 1) as i64);
 
             random_bytes[byte_index] >>= 1;
             random_bit_pos += 1;
@@ -50,12 +89,11 @@ impl SecretKey {
 
             // removed the sampled index from `indices` set.
             indices[sampled_index] = *indices.last().unwrap();
-            indices.truncate(indices.len() - 1);
+            let len = indices.len();
+            indices.truncate(len - 1);
         }
 
-        SecretKey {
-            coefficients: sk.into_boxed_slice(),
-        }
+        SecretKey { coefficients: sk }
     }
 
     /// Convenience wrapper around `SecretKey::random` if `BfvParameters` happens to be already
@@ -72,20 +110,28 @@ impl SecretKey {
     /// Panics if coefficients length does not match with degree of given bfv parameter
     ///
     /// Panics if each value in coefficients does not belong to ternary distribution (ie {-1,0,1}).
-    pub fn new(coefficients: Vec<i64>, degree: usize) -> SecretKey {
+    ///
+    /// `coefficients` are zeroized.
+    pub fn new(mut coefficients: Vec<i64>, degree: usize) -> SecretKey {
         assert!(coefficients.len() == degree);
         coefficients.iter().for_each(|c| {
             assert!(-1 <= *c && 1 >= *c);
         });
 
-        SecretKey {
-            coefficients: coefficients.into_boxed_slice(),
-        }
+        // copy to a new allocation since `into_boxed_slice` may reallocate without zeroizing
+        let sk = SecretKey {
+            coefficients: coefficients.as_slice().into(),
+        };
+        coefficients.zeroize();
+        sk
     }
 
-    /// Returns secret key polynomial for polynomial context at given level in Evaluation form
-    fn to_poly(&self, ctx: &PolyContext<'_>) -> Poly {
-        let mut p = ctx.try_convert_from_i64_small(&self.coefficients, Representation::Coefficient);
+    /// Returns secret key polynomial for polynomial context at given level in Evaluation form.
+    /// Polynomial is zeroized on drop.
+    pub(crate) fn to_poly(&self, ctx: &PolyContext<'_>) -> Zeroizing<Poly> {
+        let mut p = Zeroizing::new(
+            ctx.try_convert_from_i64_small(&self.coefficients, Representation::Coefficient),
+        );
         ctx.change_representation(&mut p, Representation::Evaluation);
         p
     }
@@ -135,6 +181,7 @@ impl SecretKey {
             poly_type: PolyType::Q,
             level: encoding.level,
             seed: Some(seed),
+            noise_estimate: Some(params.fresh_noise_bits()),
         }
     }
 
@@ -241,7 +288,7 @@ mod tests {
         dbg!(sk.measure_noise(&ct, &params));
 
         let pt2 = sk.decrypt(&ct, &params);
-        let m2 = pt2.decode(Encoding::default(), &params);
+        let m2: Vec<u64> = pt2.decode(Encoding::default(), &params);
         assert_eq!(m, m2);
     }
 
@@ -263,4 +310,18 @@ mod tests {
 
         println!("ones: {ones}, nones: {nones}, zeros: {zeros}")
     }
+
+    #[test]
+    fn secret_key_is_redacted_and_zeroized() {
+        let mut rng = thread_rng();
+        let mut sk = SecretKey::random(1 << 4, 1 << 3, &mut rng);
+        assert_eq!(
+            format!("{:?}", sk),
+            "SecretKey { coefficients: \"<redacted>\" }"
+        );
+
+        assert_eq!(sk.coefficients.iter().filter(|c| **c != 0).count(), 1 << 3);
+        sk.zeroize();
+        assert!(sk.coefficients.iter().all(|c| *c == 0));
+    }
 }
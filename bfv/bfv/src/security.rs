@@ -0,0 +1,97 @@
+/// Security level in bits against classical attacks, as per the Homomorphic Encryption Standard
+/// (https://homomorphicencryption.org/standard/).
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default)]
+pub enum SecurityLevel {
+    #[default]
+    Bits128,
+    Bits192,
+    Bits256,
+}
+
+/// Maximum log2(QP) for ternary secret distribution (Table 1 of HE Standard).
+///
+/// Each row is (degree, [128 bits, 192 bits, 256 bits]).
+const HE_STANDARD_MAX_LOG_QP: [(usize, [usize; 3]); 6] = [
+    (1024, [27, 19, 14]),
+    (2048, [54, 37, 29]),
+    (4096, [109, 75, 58]),
+    (8192, [218, 152, 118]),
+    (16384, [438, 305, 237]),
+    (32768, [881, 611, 476]),
+];
+
+impl SecurityLevel {
+    pub fn bits(&self) -> usize {
+        match self {
+            SecurityLevel::Bits128 => 128,
+            SecurityLevel::Bits192 => 192,
+            SecurityLevel::Bits256 => 256,
+        }
+    }
+
+    /// Returns maximum bits in modulus (including special moduli used for key switching) for
+    /// polynomial `degree` at this security level. Returns `None` if `degree` is not in HE standard tables.
+    ///
+    /// Security only grows with degree, thus degrees larger than 32768 conservatively get the bound of 32768.
+    pub fn max_log_qp(&self, degree: usize) -> Option<usize> {
+        let index = match self {
+            SecurityLevel::Bits128 => 0,
+            SecurityLevel::Bits192 => 1,
+            SecurityLevel::Bits256 => 2,
+        };
+        let (max_degree, max) = HE_STANDARD_MAX_LOG_QP[HE_STANDARD_MAX_LOG_QP.len() - 1];
+        if degree > max_degree {
+            return Some(max[index]);
+        }
+        HE_STANDARD_MAX_LOG_QP
+            .iter()
+            .find(|(n, _)| *n == degree)
+            .map(|(_, max)| max[index])
+    }
+
+    /// Returns highest security level achieved by polynomial `degree` and modulus with `log_qp` bits.
+    /// Returns `None` if combination does not achieve even 128 bits of security.
+    pub fn estimate(degree: usize, log_qp: usize) -> Option<SecurityLevel> {
+        [
+            SecurityLevel::Bits256,
+            SecurityLevel::Bits192,
+            SecurityLevel::Bits128,
+        ]
+        .into_iter()
+        .find(|level| {
+            level
+                .max_log_qp(degree)
+                .is_some_and(|max_log_qp| log_qp <= max_log_qp)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_works() {
+        assert_eq!(
+            SecurityLevel::estimate(1 << 15, 881),
+            Some(SecurityLevel::Bits128)
+        );
+        assert_eq!(
+            SecurityLevel::estimate(1 << 15, 600),
+            Some(SecurityLevel::Bits192)
+        );
+        assert_eq!(
+            SecurityLevel::estimate(1 << 13, 100),
+            Some(SecurityLevel::Bits256)
+        );
+        assert_eq!(SecurityLevel::estimate(1 << 15, 882), None);
+        assert_eq!(SecurityLevel::estimate(1 << 4, 10), None);
+
+        // degrees beyond the table get the bound of largest degree
+        assert_eq!(
+            SecurityLevel::estimate(1 << 16, 881),
+            Some(SecurityLevel::Bits128)
+        );
+        assert_eq!(SecurityLevel::estimate(1 << 16, 882), None);
+    }
+}
@@ -0,0 +1,876 @@
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+use crate::key_switching_key::SwitchingKey;
+use crate::{
+    convert_bytes_to_ternary, convert_from_bytes, convert_ternary_to_bytes, convert_to_bytes,
+    BVKeySwitchingKey, BfvParameters, Ciphertext, Encoding, EvaluationKey, GaloisKey,
+    HybridKeySwitchingKey, Plaintext, Poly, PolyContext, PolyType, RelinearizationKey,
+    Representation, SecretKey, Substitution,
+};
+use itertools::Itertools;
+use ndarray::Array2;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::de::{DeserializeSeed, Error as _, SeqAccess, Visitor};
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroize;
+
+/// Serialization of values that can only be encoded with respect to `BfvParameters`, for ex. polynomial
+/// coefficients are packed using bit-width of their respective moduli.
+///
+/// Use `WithParameters` to pass the value to serde serializers, or to embed it in other `Serialize` types.
+pub trait SerializeWithParameters {
+    fn serialize_with_parameters<S: Serializer>(
+        &self,
+        params: &BfvParameters,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>;
+}
+
+/// Deserialization of values that can only be decoded with respect to `BfvParameters`.
+///
+/// Use `ParametersSeed` as a `DeserializeSeed` to deserialize the value with serde deserializers.
+pub trait DeserializeWithParameters: Sized {
+    fn deserialize_with_parameters<'de, D: Deserializer<'de>>(
+        params: &BfvParameters,
+        deserializer: D,
+    ) -> Result<Self, D::Error>;
+}
+
+/// Wraps reference to `value` along with `BfvParameters` to implement `Serialize`
+pub struct WithParameters<'a, T> {
+    value: &'a T,
+    params: &'a BfvParameters,
+}
+
+impl<'a, T: SerializeWithParameters> WithParameters<'a, T> {
+    pub fn new(value: &'a T, params: &'a BfvParameters) -> WithParameters<'a, T> {
+        WithParameters { value, params }
+    }
+}
+
+impl<'a, T: SerializeWithParameters> Serialize for WithParameters<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value
+            .serialize_with_parameters(self.params, serializer)
+    }
+}
+
+/// `DeserializeSeed` that deserializes `T` with respect to `BfvParameters`
+pub struct ParametersSeed<'a, T> {
+    params: &'a BfvParameters,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: DeserializeWithParameters> ParametersSeed<'a, T> {
+    pub fn new(params: &'a BfvParameters) -> ParametersSeed<'a, T> {
+        ParametersSeed {
+            params,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'de, T: DeserializeWithParameters> DeserializeSeed<'de> for ParametersSeed<'a, T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        T::deserialize_with_parameters(self.params, deserializer)
+    }
+}
+
+/// Byte string. Serialized as bytes for binary formats that support them. Deserializes from
+/// bytes or sequence of u8s (for ex. JSON arrays).
+#[derive(Default)]
+struct Bytes(Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("byte array")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Bytes, E> {
+                Ok(Bytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                Ok(Bytes(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    bytes.push(b);
+                }
+                Ok(Bytes(bytes))
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+fn seed_from_bytes(bytes: &Bytes) -> Result<<ChaCha8Rng as SeedableRng>::Seed, String> {
+    let mut seed = <ChaCha8Rng as SeedableRng>::Seed::default();
+    if bytes.0.len() != seed.len() {
+        return Err(format!("Invalid seed length {}", bytes.0.len()));
+    }
+    seed.copy_from_slice(&bytes.0);
+    Ok(seed)
+}
+
+// Poly //
+/// Polynomial coefficients in `Coefficient` representation packed per modulus
+#[derive(Serialize, Deserialize)]
+struct PolyRepr(Vec<Bytes>);
+
+impl PolyRepr {
+    fn new(poly: &Poly, ctx: &PolyContext<'_>) -> Result<PolyRepr, String> {
+        if poly.representation != Representation::Coefficient {
+            return Err("Polynomial must be in Coefficient representation".into());
+        }
+        Ok(PolyRepr(
+            poly.coefficients
+                .outer_iter()
+                .zip(ctx.iter_moduli_ops())
+                .map(|(xi, modqi)| Bytes(convert_to_bytes(xi.as_slice().unwrap(), modqi.modulus())))
+                .collect_vec(),
+        ))
+    }
+
+    fn to_poly(&self, ctx: &PolyContext<'_>) -> Result<Poly, String> {
+        if self.0.len() != ctx.moduli_count() {
+            return Err(format!(
+                "Expected {} moduli, found {}",
+                ctx.moduli_count(),
+                self.0.len()
+            ));
+        }
+
+        let mut coefficients = Vec::with_capacity(ctx.moduli_count() * ctx.degree());
+        for (xi, modqi) in self.0.iter().zip(ctx.iter_moduli_ops()) {
+            let q = modqi.modulus();
+            let bits = (64 - q.leading_zeros()) as usize;
+            if xi.0.len() < (bits * ctx.degree()).div_ceil(8) {
+                return Err("Invalid polynomial byte length".into());
+            }
+            let values = convert_from_bytes(&xi.0, q);
+            if values.len() != ctx.degree() || values.iter().any(|v| *v >= q) {
+                return Err("Invalid polynomial coefficients".into());
+            }
+            coefficients.extend(values);
+        }
+
+        Ok(Poly::new(
+            Array2::from_shape_vec((ctx.moduli_count(), ctx.degree()), coefficients).unwrap(),
+            Representation::Coefficient,
+        ))
+    }
+}
+
+// Ciphertext //
+#[derive(Serialize, Deserialize)]
+struct CiphertextRepr {
+    level: usize,
+    seed: Option<Bytes>,
+    c: Vec<PolyRepr>,
+}
+
+impl CiphertextRepr {
+    fn new(value: &Ciphertext, params: &BfvParameters) -> Result<CiphertextRepr, String> {
+        if value.poly_type != PolyType::Q {
+            return Err("Only ciphertexts over Q can be serialized".into());
+        }
+        let ctx = params.poly_ctx(&PolyType::Q, value.level);
+
+        // second polynomial of seeded ciphertext is derived from the seed
+        let count = if value.seed.is_some() {
+            1
+        } else {
+            value.c.len()
+        };
+        let c = value.c[..count]
+            .iter()
+            .map(|p| PolyRepr::new(p, &ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(CiphertextRepr {
+            level: value.level,
+            seed: value.seed.map(|s| Bytes(s.to_vec())),
+            c,
+        })
+    }
+
+    fn to_ciphertext(&self, params: &BfvParameters) -> Result<Ciphertext, String> {
+        if self.level > params.max_level {
+            return Err(format!("Invalid ciphertext level {}", self.level));
+        }
+        let ctx = params.poly_ctx(&PolyType::Q, self.level);
+
+        let mut c = self
+            .c
+            .iter()
+            .map(|p| p.to_poly(&ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let seed = self.seed.as_ref().map(seed_from_bytes).transpose()?;
+        if let Some(seed) = seed {
+            if c.len() != 1 {
+                return Err("Seeded ciphertext must have exactly one polynomial".into());
+            }
+            c.push(ctx.random_with_seed(seed));
+        } else if c.len() < 2 {
+            return Err("Ciphertext must have atleast two polynomials".into());
+        }
+
+        Ok(Ciphertext {
+            c,
+            poly_type: PolyType::Q,
+            level: self.level,
+            seed,
+            noise_estimate: None,
+        })
+    }
+}
+
+// Plaintext //
+#[derive(Serialize, Deserialize)]
+struct PlaintextRepr {
+    m: Vec<u64>,
+    encoding: Option<Encoding>,
+}
+
+impl PlaintextRepr {
+    fn new(value: &Plaintext) -> PlaintextRepr {
+        PlaintextRepr {
+            m: value.m.clone(),
+            encoding: value.encoding.clone(),
+        }
+    }
+
+    fn to_plaintext(&self, params: &BfvParameters) -> Result<Plaintext, String> {
+        if self.m.len() != params.degree || self.m.iter().any(|v| *v >= params.plaintext_modulus) {
+            return Err("Invalid plaintext coefficients".into());
+        }
+
+        match &self.encoding {
+            Some(encoding) => {
+                if encoding.level > params.max_level {
+                    return Err(format!("Invalid plaintext level {}", encoding.level));
+                }
+                // cached polynomials are not serialized, thus must be recomputed
+                Ok(Plaintext::from_coefficients(
+                    self.m.clone(),
+                    params,
+                    encoding.clone(),
+                ))
+            }
+            None => Ok(Plaintext {
+                m: self.m.clone(),
+                encoding: None,
+                mul_poly: None,
+                add_sub_poly: None,
+            }),
+        }
+    }
+}
+
+// SecretKey //
+#[derive(Serialize, Deserialize)]
+struct SecretKeyRepr {
+    coefficients: Bytes,
+}
+
+impl Drop for SecretKeyRepr {
+    fn drop(&mut self) {
+        self.coefficients.0.zeroize();
+    }
+}
+
+impl SecretKeyRepr {
+    fn new(value: &SecretKey) -> SecretKeyRepr {
+        SecretKeyRepr {
+            coefficients: Bytes(convert_ternary_to_bytes(&value.coefficients)),
+        }
+    }
+
+    fn to_secret_key(&self, params: &BfvParameters) -> Result<SecretKey, String> {
+        if self.coefficients.0.len() < params.degree.div_ceil(4) {
+            return Err("Invalid secret key length".into());
+        }
+        let mut coefficients = convert_bytes_to_ternary(&self.coefficients.0, params.degree);
+        // copy to a new allocation since `into_boxed_slice` may reallocate without zeroizing
+        let sk = SecretKey {
+            coefficients: coefficients.as_slice().into(),
+        };
+        coefficients.zeroize();
+        Ok(sk)
+    }
+}
+
+// Hybrid Key Switching Key //
+#[derive(Serialize, Deserialize)]
+struct HybridKeySwitchingKeyRepr {
+    seed: Option<Bytes>,
+    c0s: Vec<PolyRepr>,
+    c1s: Vec<PolyRepr>,
+}
+
+impl HybridKeySwitchingKeyRepr {
+    fn new(
+        value: &HybridKeySwitchingKey,
+        params: &BfvParameters,
+        level: usize,
+    ) -> Result<HybridKeySwitchingKeyRepr, String> {
+        let ctx = params.poly_ctx(&PolyType::QP, level);
+
+        // c0s and c1s are stored in `Evaluation` representation
+        let to_repr = |p: &Poly| {
+            let mut p = p.clone();
+            ctx.change_representation(&mut p, Representation::Coefficient);
+            PolyRepr::new(&p, &ctx)
+        };
+
+        let c0s = value
+            .c0s
+            .iter()
+            .map(to_repr)
+            .collect::<Result<Vec<_>, _>>()?;
+        let c1s = if value.seed.is_none() {
+            value
+                .c1s
+                .iter()
+                .map(to_repr)
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            vec![]
+        };
+
+        Ok(HybridKeySwitchingKeyRepr {
+            seed: value.seed.map(|s| Bytes(s.to_vec())),
+            c0s,
+            c1s,
+        })
+    }
+
+    fn to_ksk(
+        &self,
+        params: &BfvParameters,
+        level: usize,
+    ) -> Result<HybridKeySwitchingKey, String> {
+        let valid_level = params
+            .hybrid_ksk_parameters
+            .as_ref()
+            .is_some_and(|ksk_params| level < ksk_params.len());
+        if !valid_level {
+            return Err(format!("Key switching is not supported at level {level}"));
+        }
+        if self.c0s.len() != params.hybrid_key_switching_params_at_level(level).dnum {
+            return Err("Invalid key switching key length".into());
+        }
+
+        let ctx = params.poly_ctx(&PolyType::QP, level);
+        let to_poly = |p: &PolyRepr| {
+            let mut p = p.to_poly(&ctx)?;
+            ctx.change_representation(&mut p, Representation::Evaluation);
+            Ok::<_, String>(p)
+        };
+
+        let c0s = self
+            .c0s
+            .iter()
+            .map(to_poly)
+            .collect::<Result<Vec<_>, _>>()?;
+        let seed = self.seed.as_ref().map(seed_from_bytes).transpose()?;
+        let c1s = match seed {
+            Some(seed) => {
+                let mut c1s = HybridKeySwitchingKey::generate_c1(c0s.len(), &ctx, seed);
+                c1s.iter_mut()
+                    .for_each(|p| ctx.change_representation(p, Representation::Evaluation));
+                c1s
+            }
+            None => {
+                if self.c1s.len() != c0s.len() {
+                    return Err("Invalid key switching key length".into());
+                }
+                self.c1s
+                    .iter()
+                    .map(to_poly)
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        Ok(HybridKeySwitchingKey {
+            seed,
+            c0s: c0s.into_boxed_slice(),
+            c1s: c1s.into_boxed_slice(),
+        })
+    }
+}
+
+// BVKeySwitchingKey //
+/// c1s are generated from seed, thus are not serialized
+#[derive(Serialize, Deserialize)]
+struct BVKeySwitchingKeyRepr {
+    seed: Bytes,
+    c0s: Vec<PolyRepr>,
+}
+
+impl BVKeySwitchingKeyRepr {
+    fn new(
+        value: &BVKeySwitchingKey,
+        params: &BfvParameters,
+        level: usize,
+    ) -> Result<BVKeySwitchingKeyRepr, String> {
+        let ctx = params.poly_ctx(&PolyType::Q, level);
+
+        // c0s are stored in `Evaluation` representation
+        let c0s = value
+            .c0s
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                ctx.change_representation(&mut p, Representation::Coefficient);
+                PolyRepr::new(&p, &ctx)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(BVKeySwitchingKeyRepr {
+            seed: Bytes(value.seed.to_vec()),
+            c0s,
+        })
+    }
+
+    fn to_ksk(&self, params: &BfvParameters, level: usize) -> Result<BVKeySwitchingKey, String> {
+        // key switching at last level, with a single ciphertext modulus, is not supported
+        if level >= params.max_level {
+            return Err(format!("Key switching is not supported at level {level}"));
+        }
+
+        let ctx = params.poly_ctx(&PolyType::Q, level);
+        if self.c0s.len() != ctx.moduli_count {
+            return Err("Invalid key switching key length".into());
+        }
+
+        let c0s = self
+            .c0s
+            .iter()
+            .map(|p| {
+                let mut p = p.to_poly(&ctx)?;
+                ctx.change_representation(&mut p, Representation::Evaluation);
+                Ok::<_, String>(p)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let seed = seed_from_bytes(&self.seed)?;
+        let c1s = BVKeySwitchingKey::generate_c1(&ctx, seed);
+
+        Ok(BVKeySwitchingKey {
+            c0s: c0s.into_boxed_slice(),
+            c1s: c1s.into_boxed_slice(),
+            seed,
+        })
+    }
+}
+
+/// Switching key of relinearization and galois keys, tagged with its key switching strategy
+#[derive(Serialize, Deserialize)]
+enum SwitchingKeyRepr {
+    Hybrid(HybridKeySwitchingKeyRepr),
+    Bv(BVKeySwitchingKeyRepr),
+}
+
+impl SwitchingKeyRepr {
+    fn new(
+        value: &SwitchingKey,
+        params: &BfvParameters,
+        level: usize,
+    ) -> Result<SwitchingKeyRepr, String> {
+        Ok(match value {
+            SwitchingKey::Hybrid(ksk) => {
+                SwitchingKeyRepr::Hybrid(HybridKeySwitchingKeyRepr::new(ksk, params, level)?)
+            }
+            SwitchingKey::Bv(ksk) => {
+                SwitchingKeyRepr::Bv(BVKeySwitchingKeyRepr::new(ksk, params, level)?)
+            }
+        })
+    }
+
+    fn to_ksk(&self, params: &BfvParameters, level: usize) -> Result<SwitchingKey, String> {
+        Ok(match self {
+            SwitchingKeyRepr::Hybrid(ksk) => SwitchingKey::Hybrid(ksk.to_ksk(params, level)?),
+            SwitchingKeyRepr::Bv(ksk) => SwitchingKey::Bv(ksk.to_ksk(params, level)?),
+        })
+    }
+}
+
+// RelinearizationKey //
+#[derive(Serialize, Deserialize)]
+struct RelinearizationKeyRepr {
+    level: usize,
+    ksk: SwitchingKeyRepr,
+    /// Keys for s^3, s^4, ... of keys of degree > 2. Missing in keys serialized before higher
+    /// degree keys were supported.
+    #[serde(default)]
+    higher_ksks: Vec<SwitchingKeyRepr>,
+}
+
+impl RelinearizationKeyRepr {
+    fn new(
+        value: &RelinearizationKey,
+        params: &BfvParameters,
+    ) -> Result<RelinearizationKeyRepr, String> {
+        Ok(RelinearizationKeyRepr {
+            level: value.level,
+            ksk: SwitchingKeyRepr::new(&value.ksk, params, value.level)?,
+            higher_ksks: value
+                .higher_ksks
+                .iter()
+                .map(|k| SwitchingKeyRepr::new(k, params, value.level))
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+
+    fn to_rlk(&self, params: &BfvParameters) -> Result<RelinearizationKey, String> {
+        Ok(RelinearizationKey {
+            ksk: self.ksk.to_ksk(params, self.level)?,
+            higher_ksks: self
+                .higher_ksks
+                .iter()
+                .map(|k| k.to_ksk(params, self.level))
+                .collect::<Result<Vec<_>, _>>()?,
+            level: self.level,
+        })
+    }
+}
+
+// GaloisKey //
+#[derive(Serialize, Deserialize)]
+struct GaloisKeyRepr {
+    exponent: usize,
+    level: usize,
+    ksk: SwitchingKeyRepr,
+}
+
+impl GaloisKeyRepr {
+    fn new(value: &GaloisKey, params: &BfvParameters) -> Result<GaloisKeyRepr, String> {
+        Ok(GaloisKeyRepr {
+            exponent: value.substitution.exponent,
+            level: value.level,
+            ksk: SwitchingKeyRepr::new(&value.ksk_key, params, value.level)?,
+        })
+    }
+
+    fn to_galois_key(&self, params: &BfvParameters) -> Result<GaloisKey, String> {
+        if self.exponent & 1 != 1 {
+            return Err(format!("Invalid galois exponent {}", self.exponent));
+        }
+        Ok(GaloisKey {
+            substitution: Substitution::new(self.exponent, params.degree),
+            ksk_key: self.ksk.to_ksk(params, self.level)?,
+            level: self.level,
+        })
+    }
+}
+
+// EvaluationKey //
+/// Keys are sorted by level, then exponent, so that serialization is deterministic
+#[derive(Serialize, Deserialize)]
+struct EvaluationKeyRepr {
+    rlks: Vec<RelinearizationKeyRepr>,
+    rtgs: Vec<GaloisKeyRepr>,
+}
+
+impl EvaluationKeyRepr {
+    fn new(value: &EvaluationKey, params: &BfvParameters) -> Result<EvaluationKeyRepr, String> {
+        let rlks = value
+            .rlks
+            .iter()
+            .sorted_by_key(|(level, _)| **level)
+            .map(|(_, rlk)| RelinearizationKeyRepr::new(rlk, params))
+            .collect::<Result<Vec<_>, _>>()?;
+        let rtgs = value
+            .rtgs
+            .iter()
+            .sorted_by_key(|((exponent, level), _)| (*level, *exponent))
+            .map(|(_, gk)| GaloisKeyRepr::new(gk, params))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(EvaluationKeyRepr { rlks, rtgs })
+    }
+
+    fn to_evaluation_key(&self, params: &BfvParameters) -> Result<EvaluationKey, String> {
+        let mut rlks = HashMap::new();
+        for rlk in self.rlks.iter() {
+            let rlk = rlk.to_rlk(params)?;
+            rlks.insert(rlk.level, rlk);
+        }
+
+        let mut rtgs = HashMap::new();
+        for gk in self.rtgs.iter() {
+            let gk = gk.to_galois_key(params)?;
+            rtgs.insert((gk.exponent(), gk.level), gk);
+        }
+
+        Ok(EvaluationKey { rlks, rtgs })
+    }
+}
+
+macro_rules! impl_serde_with_parameters {
+    ($ty:ty, $repr:ty, |$value:ident, $params:ident| $to_repr:expr, |$r:ident| $from_repr:expr) => {
+        impl SerializeWithParameters for $ty {
+            fn serialize_with_parameters<S: Serializer>(
+                &self,
+                params: &BfvParameters,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                let $value = self;
+                #[allow(unused_variables)]
+                let $params = params;
+                let repr: Result<$repr, String> = $to_repr;
+                repr.map_err(S::Error::custom)?.serialize(serializer)
+            }
+        }
+
+        impl DeserializeWithParameters for $ty {
+            fn deserialize_with_parameters<'de, D: Deserializer<'de>>(
+                params: &BfvParameters,
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                let $r = <$repr>::deserialize(deserializer)?;
+                let $params = params;
+                let value: Result<$ty, String> = $from_repr;
+                value.map_err(D::Error::custom)
+            }
+        }
+    };
+}
+
+impl_serde_with_parameters!(
+    Ciphertext,
+    CiphertextRepr,
+    |value, params| CiphertextRepr::new(value, params),
+    |repr| repr.to_ciphertext(params)
+);
+impl_serde_with_parameters!(
+    Plaintext,
+    PlaintextRepr,
+    |value, params| Ok(PlaintextRepr::new(value)),
+    |repr| repr.to_plaintext(params)
+);
+impl_serde_with_parameters!(
+    SecretKey,
+    SecretKeyRepr,
+    |value, params| Ok(SecretKeyRepr::new(value)),
+    |repr| repr.to_secret_key(params)
+);
+impl_serde_with_parameters!(
+    RelinearizationKey,
+    RelinearizationKeyRepr,
+    |value, params| RelinearizationKeyRepr::new(value, params),
+    |repr| repr.to_rlk(params)
+);
+impl_serde_with_parameters!(
+    GaloisKey,
+    GaloisKeyRepr,
+    |value, params| GaloisKeyRepr::new(value, params),
+    |repr| repr.to_galois_key(params)
+);
+impl_serde_with_parameters!(
+    EvaluationKey,
+    EvaluationKeyRepr,
+    |value, params| EvaluationKeyRepr::new(value, params),
+    |repr| repr.to_evaluation_key(params)
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Evaluator, KeySwitchingStrategy, PolyCache};
+    use rand::thread_rng;
+
+    fn to_bincode<T: SerializeWithParameters>(value: &T, params: &BfvParameters) -> Vec<u8> {
+        bincode::serialize(&WithParameters::new(value, params)).unwrap()
+    }
+
+    fn from_bincode<T: DeserializeWithParameters>(
+        bytes: &[u8],
+        params: &BfvParameters,
+    ) -> bincode::Result<T> {
+        use bincode::Options;
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .deserialize_seed(ParametersSeed::new(params), bytes)
+    }
+
+    #[test]
+    fn serde_ciphertext_in_json_envelope() {
+        #[derive(Serialize)]
+        struct Order<'a> {
+            id: u64,
+            trader: &'a str,
+            ct: WithParameters<'a, Ciphertext>,
+        }
+
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+
+        let evaluator = Evaluator::new(params);
+        let pt = evaluator.plaintext_encode(&m, Encoding::default());
+        let ct = evaluator.encrypt(&sk, &pt, &mut rng);
+        assert!(ct.is_seeded());
+
+        let json = serde_json::to_string(&Order {
+            id: 7,
+            trader: "alice",
+            ct: WithParameters::new(&ct, evaluator.params()),
+        })
+        .unwrap();
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["id"], 7);
+        assert_eq!(value["trader"], "alice");
+        let ct_back: Ciphertext = ParametersSeed::new(evaluator.params())
+            .deserialize(&value["ct"])
+            .unwrap();
+        assert_eq!(ct, ct_back);
+
+        // unseeded ciphertext
+        let ct = evaluator.add(&ct, &ct_back);
+        let json = serde_json::to_string(&WithParameters::new(&ct, evaluator.params())).unwrap();
+        let ct_back: Ciphertext = ParametersSeed::new(evaluator.params())
+            .deserialize(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        assert_eq!(ct, ct_back);
+    }
+
+    #[test]
+    fn serde_keys_and_plaintext_bincode_round_trip() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+
+        let sk_back: SecretKey = from_bincode(&to_bincode(&sk, &params), &params).unwrap();
+        assert_eq!(sk, sk_back);
+
+        let rlk = RelinearizationKey::new(&params, &sk, 0, &mut rng);
+        let rlk_back: RelinearizationKey =
+            from_bincode(&to_bincode(&rlk, &params), &params).unwrap();
+        assert_eq!(rlk, rlk_back);
+
+        let rlk = RelinearizationKey::new_with_degree(
+            &params,
+            &sk,
+            0,
+            4,
+            KeySwitchingStrategy::Hybrid,
+            &mut rng,
+        );
+        let rlk_back: RelinearizationKey =
+            from_bincode(&to_bincode(&rlk, &params), &params).unwrap();
+        assert_eq!(rlk_back.degree(), 4);
+        assert_eq!(rlk, rlk_back);
+
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[0, 0], &[1, -1], &mut rng);
+        let bytes = to_bincode(&ek, &params);
+        let ek_back: EvaluationKey = from_bincode(&bytes, &params).unwrap();
+        assert_eq!(ek, ek_back);
+        // serialization is deterministic
+        assert_eq!(bytes, to_bincode(&ek_back, &params));
+
+        let gk = ek.get_rtg(1, 0).unwrap();
+        let gk_back: GaloisKey = from_bincode(&to_bincode(gk, &params), &params).unwrap();
+        assert_eq!(gk, &gk_back);
+
+        let ek = EvaluationKey::new_with_strategy(
+            &params,
+            &sk,
+            &[0],
+            &[0],
+            &[1],
+            KeySwitchingStrategy::Bv,
+            &mut rng,
+        );
+        let ek_back: EvaluationKey = from_bincode(&to_bincode(&ek, &params), &params).unwrap();
+        assert_eq!(ek, ek_back);
+
+        let m = params
+            .plaintext_modulus_op
+            .random_vec(params.degree, &mut rng);
+        let pt = Plaintext::encode(
+            &m,
+            &params,
+            Encoding::simd(1, PolyCache::All(PolyType::Q, Representation::Evaluation)),
+        );
+        let pt_back: Plaintext = from_bincode(&to_bincode(&pt, &params), &params).unwrap();
+        assert!(pt.m == pt_back.m);
+        assert!(pt.mul_poly == pt_back.mul_poly);
+        assert!(pt.add_sub_poly == pt_back.add_sub_poly);
+    }
+
+    #[test]
+    fn serde_rlk_without_higher_ksks() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let rlk = RelinearizationKey::new(&params, &sk, 0, &mut rng);
+
+        // relinearization keys serialized before higher degree keys were supported
+        let mut value = serde_json::to_value(WithParameters::new(&rlk, &params)).unwrap();
+        assert!(value
+            .as_object_mut()
+            .unwrap()
+            .remove("higher_ksks")
+            .is_some());
+        let rlk_back: RelinearizationKey =
+            ParametersSeed::new(&params).deserialize(&value).unwrap();
+        assert_eq!(rlk, rlk_back);
+    }
+
+    #[test]
+    fn serde_rejects_malformed_values() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(5, 1 << 4);
+        let other_params = BfvParameters::default(3, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+
+        // wrong parameters
+        assert!(from_bincode::<EvaluationKey>(&to_bincode(&ek, &params), &other_params).is_err());
+
+        // truncated secret key
+        let mut bytes = to_bincode(&sk, &params);
+        bytes.truncate(bytes.len() - 1);
+        assert!(from_bincode::<SecretKey>(&bytes, &params).is_err());
+
+        // ciphertext at invalid level
+        let json = r#"{"level":10,"seed":null,"c":[]}"#;
+        assert!(ParametersSeed::<Ciphertext>::new(&params)
+            .deserialize(&mut serde_json::Deserializer::from_str(json))
+            .is_err());
+
+        // ciphertext polynomials must be in `Coefficient` representation
+        let evaluator = Evaluator::new(params);
+        let pt = evaluator.plaintext_encode(&[1, 2, 3], Encoding::default());
+        let mut ct = evaluator.encrypt(&sk, &pt, &mut rng);
+        evaluator.ciphertext_change_representation(&mut ct, Representation::Evaluation);
+        assert!(serde_json::to_string(&WithParameters::new(&ct, evaluator.params())).is_err());
+    }
+}
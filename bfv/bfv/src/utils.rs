@@ -4,6 +4,7 @@ use num_bigint::BigUint;
 use num_bigint_dig::{BigUint as BigUintDig, ModInverse};
 use num_traits::{FromPrimitive, ToPrimitive};
 use rand::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
 
 #[macro_export]
 macro_rules! warn {
@@ -15,6 +16,20 @@ macro_rules! warn {
     };
 }
 
+/// Runs `a` and `b` in parallel if `parallel` feature is enabled, otherwise one after another.
+pub(crate) fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    #[cfg(feature = "parallel")]
+    return rayon::join(a, b);
+    #[cfg(not(feature = "parallel"))]
+    (a(), b())
+}
+
 /// Retursn galois element correponding to desired rotation by i.
 ///
 /// Galois element: 3^i % M is will rotate left by i
@@ -55,17 +70,23 @@ pub fn mod_inverse_biguint(a: &BigUint, m: &BigUint) -> BigUint {
     )
 }
 
+/// Packs ternary values into bytes.
+///
+/// Since it is used to serialize secret keys, mapping is branch free and intermediate buffers are zeroized.
+/// Returned bytes are allocated once, hence are not left behind in reallocated memory.
 pub fn convert_ternary_to_bytes(values: &[i64]) -> Vec<u8> {
     // map ternary distrubtion {-1,0,1} to {2,0,1}
-    let values = values
-        .iter()
-        .map(|v| if *v == -1 { 2u64 } else { *v as u64 })
-        .collect_vec();
+    let values = Zeroizing::new(
+        values
+            .iter()
+            .map(|v| ((*v & 3) - ((*v >> 1) & 1)) as u64)
+            .collect_vec(),
+    );
 
     let bits = 2;
     let mask = (1u64 << bits) - 1;
 
-    let mut bytes = vec![];
+    let mut bytes = Vec::with_capacity(values.len() / 4 + 1);
 
     let mut value_index = 0;
     let mut curr_byte = 0u8;
@@ -90,23 +111,20 @@ pub fn convert_ternary_to_bytes(values: &[i64]) -> Vec<u8> {
     bytes
 }
 
+/// Unpacks `length` ternary values from bytes.
+///
+/// Mapping is branch free and returned values are allocated once.
 pub fn convert_bytes_to_ternary(bytes: &[u8], length: usize) -> Vec<i64> {
-    // extract 4 2 bits value from each byte
-    let mut values = vec![];
+    let mut values = vec![0i64; length];
     let bits = 2;
     let mask = (1u8 << bits) - 1;
-    bytes.iter().for_each(|v| {
-        for i in 0..4 {
-            values.push((*v >> (i * 2)) & mask);
-        }
-    });
 
-    // map {2,0,1} to {-1,0,1}
-    let values = values
-        .iter()
-        .take(length)
-        .map(|v| if *v == 2 { -1i64 } else { *v as i64 })
-        .collect_vec();
+    // extract 4 2 bits value from each byte
+    values.iter_mut().enumerate().for_each(|(index, value)| {
+        let v = ((bytes[index / 4] >> ((index % 4) * 2)) & mask) as i64;
+        // map {2,0,1} to {-1,0,1}
+        *value = v - 3 * (v >> 1);
+    });
 
     values
 }
@@ -250,7 +268,7 @@ pub fn sample_vec_cbd<R: RngCore + CryptoRng>(
 
 #[cfg(test)]
 mod tests {
-    use rand::thread_rng;
+    use rand::{thread_rng, Rng};
 
     use crate::generate_prime;
 
@@ -279,4 +297,16 @@ mod tests {
             assert_eq!(values, values_res);
         }
     }
+
+    #[test]
+    fn convert_ternary_to_and_from_bytes() {
+        let mut rng = thread_rng();
+        for length in [16, 17, 18, 19, 1 << 8] {
+            let values = (0..length)
+                .map(|_| rng.gen_range(-1i64..=1))
+                .collect_vec();
+            let bytes = convert_ternary_to_bytes(&values);
+            assert_eq!(convert_bytes_to_ternary(&bytes, length), values);
+        }
+    }
 }
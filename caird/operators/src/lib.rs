@@ -1,12 +1,17 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use bfv::{
     Ciphertext, Encoding, EvaluationKey, Evaluator, Modulus, Plaintext, PolyCache, PolyType,
     Representation, SecretKey,
 };
+use planner::Requirements;
 use rand::thread_rng;
-use utils::{decrypt_and_print, read_values, store_values};
+use utils::{decrypt_and_print, map_range, map_reduce_range, read_values, store_values};
 
+pub mod linear_transform;
+pub mod packing;
+pub mod planner;
 pub mod utils;
 
 pub fn powers_of_x(
@@ -16,50 +21,43 @@ pub fn powers_of_x(
     sk: &SecretKey,
     ek: &EvaluationKey,
 ) -> Vec<Ciphertext> {
-    let dummy = Ciphertext::new(vec![], PolyType::Q, 0);
-    let mut values = vec![dummy; max];
-    let mut calculated = vec![0u64; max];
-    values[0] = x.clone();
-    calculated[0] = 1;
-    // let mut mul_count = 0;
-
-    for i in (2..(max + 1)).rev() {
-        let mut exp = i;
-        let mut base_deg = 1;
-        let mut res_deg = 0;
-
-        while exp > 0 {
-            if exp & 1 == 1 {
-                let p_res_deg = res_deg;
-                res_deg += base_deg;
-                if res_deg != base_deg && calculated[res_deg - 1] == 0 {
-                    let tmp = evaluator.mul(&values[p_res_deg - 1], &values[base_deg - 1]);
-                    values[res_deg - 1] = evaluator.relinearize(&tmp, ek);
-                    // println!("Res deg time: {:?}", now.elapsed());
-                    calculated[res_deg - 1] = 1;
-                    // mul_count += 1;
-                }
-            }
-            exp >>= 1;
-            if exp != 0 {
-                let p_base_deg = base_deg;
-                base_deg *= 2;
-                if calculated[base_deg - 1] == 0 {
-                    let tmp = evaluator.mul(&values[p_base_deg - 1], &values[p_base_deg - 1]);
-                    values[base_deg - 1] = evaluator.relinearize(&tmp, ek);
-
-                    calculated[base_deg - 1] = 1;
-
-                    // mul_count += 1;
-                }
-            }
-        }
+    let mut values = Vec::with_capacity(max);
+    values.push(x.clone());
+
+    // Powers are computed in layers. Every x^i in layer (h, 2h] is x^(i-h) * x^h, where h is a
+    // power of two. Since operands are in preceding layers, powers within a layer are
+    // independent and are evaluated concurrently.
+    let mut h = 1;
+    while h < max {
+        let layer = map_range((h + 1)..(std::cmp::min(2 * h, max) + 1), |i| {
+            // x^2h is a square, which is cheaper than a product
+            let tmp = if i == 2 * h {
+                evaluator.square(&values[h - 1])
+            } else {
+                evaluator.mul(&values[i - h - 1], &values[h - 1])
+            };
+            evaluator.relinearize(&tmp, ek)
+        });
+        values.extend(layer);
+        h *= 2;
     }
-    // dbg!(mul_count);
 
     values
 }
 
+/// Requirements of `powers_of_x` for `max`. Panics if `max` is 0.
+pub fn powers_of_x_requirements(max: usize) -> Requirements {
+    assert!(max > 0, "At least x^1 must be computed");
+    Requirements {
+        // x^max is at depth ceil(log(max))
+        depth: max.next_power_of_two().trailing_zeros() as usize,
+        // every power except x^1 is computed exactly once
+        ct_ct_muls: max - 1,
+        relinearizations: max - 1,
+        rotations: vec![],
+    }
+}
+
 pub fn sort(
     evaluator: &Evaluator,
     values: &[Ciphertext],
@@ -68,67 +66,101 @@ pub fn sort(
 ) -> Vec<Ciphertext> {
     let mut ht = vec![Ciphertext::placeholder(); values.len()];
 
-    let one = evaluator.plaintext_encode(
-        &vec![1; evaluator.params().degree],
-        Encoding::simd(0, PolyCache::AddSub(Representation::Coefficient)),
-    );
-
     println!("Sorting ciphertext ~~~~~~~~~");
 
-    for i in 0..values.len() {
-        for j in 0..values.len() {
-            if i < j {
-                let lt = univariate_less_than(evaluator, &values[i], &values[j], ek, sk);
+    // pairwise comparisons are independent of each other
+    let pairs = (0..values.len())
+        .flat_map(|i| ((i + 1)..values.len()).map(move |j| (i, j)))
+        .collect::<Vec<(usize, usize)>>();
+    let comparisons = map_range(0..pairs.len(), |index| {
+        let (i, j) = pairs[index];
+        univariate_less_than(evaluator, &values[i], &values[j], ek, sk)
+    });
 
-                let mut one_minus_lt = evaluator.negate(&lt);
-                evaluator.add_assign_plaintext(&mut one_minus_lt, &one);
+    // `lt` can be at any level if evaluator switches modulus automatically, thus `1` is encoded
+    // once for every level of comparisons
+    let mut ones = HashMap::new();
+    comparisons.iter().for_each(|lt| {
+        ones.entry(lt.level()).or_insert_with(|| {
+            evaluator.plaintext_encode(
+                &vec![1; evaluator.params().degree],
+                Encoding::simd(lt.level(), PolyCache::AddSub(Representation::Coefficient)),
+            )
+        });
+    });
 
-                // add lt to ht[i]
-                if ht[i].c_ref().len() != 0 {
-                    evaluator.add_assign(&mut ht[i], &lt);
-                } else {
-                    ht[i] = lt;
-                }
+    // accumulate in order of pairs so that output does not depend on scheduling
+    for ((i, j), lt) in pairs.into_iter().zip(comparisons) {
+        let mut one_minus_lt = evaluator.negate(&lt);
+        evaluator.add_assign_plaintext(&mut one_minus_lt, &ones[&lt.level()]);
 
-                // add 1 - lt to ht[j]
-                if ht[j].c_ref().len() != 0 {
-                    evaluator.add_assign(&mut ht[j], &one_minus_lt);
-                } else {
-                    ht[j] = one_minus_lt;
-                }
-            }
+        // add lt to ht[i]
+        if !ht[i].c_ref().is_empty() {
+            evaluator.add_assign(&mut ht[i], &lt);
+        } else {
+            ht[i] = lt;
+        }
+
+        // add 1 - lt to ht[j]
+        if !ht[j].c_ref().is_empty() {
+            evaluator.add_assign(&mut ht[j], &one_minus_lt);
+        } else {
+            ht[j] = one_minus_lt;
         }
     }
 
     // equality checks
 
     // precompute powers
-    let mut ht_powers = vec![];
-    ht.iter().for_each(|c| {
+    let mut ht_powers = map_range(0..ht.len(), |i| {
+        powers_of_x(evaluator, &ht[i], 65536, sk, ek)
+    });
+
+    // Powers are summed up after plaintext multiplication, hence must be at the same level
+    let level = ht_powers
+        .iter()
+        .flat_map(|powers| powers.iter().map(|c| c.level()))
+        .max()
+        .unwrap();
+    ht_powers.iter_mut().for_each(|powers| {
         // change ciphertexts to Evaluation representation for plaintext multiplication
-        let mut powers = powers_of_x(evaluator, c, 65536, sk, ek);
         powers.iter_mut().for_each(|c| {
+            evaluator.mod_down_level(c, level);
             evaluator.ciphertext_change_representation(c, Representation::Evaluation);
         });
-        ht_powers.push(powers);
     });
 
     println!("Equality checks ~~~~~~~~~");
 
-    let mut sorted_values = vec![];
-
-    for i in 0..values.len() {
-        // get `i_th` ciphertext in descending order
-        sorted_values.push(sort_equality_subroutine(
-            evaluator, i, &ht_powers, values, sk, ek,
-        ));
-    }
+    // get `i_th` ciphertext in descending order
+    let sorted_values = map_range(0..values.len(), |i| {
+        sort_equality_subroutine(evaluator, i, &ht_powers, values, sk, ek)
+    });
 
     println!("Sorting ciphertext done!! ~~~~~~~~~");
 
     sorted_values
 }
 
+/// Requirements of `sort` for `n` values
+pub fn sort_requirements(n: usize) -> Requirements {
+    // less than for every pair
+    let less_than = univariate_less_than_requirements().times(n * n.saturating_sub(1) / 2);
+    // x^1..x^(p-1) of hamming weight of every value
+    let powers = powers_of_x_requirements(65536).times(n);
+    // `sort_equality_subroutine` for every index: plaintext multiplications followed by
+    // lazy multiplication with every value and single relinearization
+    let equality = Requirements {
+        depth: 2,
+        ct_ct_muls: n,
+        relinearizations: 1,
+        rotations: vec![],
+    }
+    .times(n);
+
+    less_than.then(&powers).then(&equality)
+}
+
 /// Returns ciphertext with hamming weight = `i`
 pub fn sort_equality_subroutine(
     evaluator: &Evaluator,
@@ -141,9 +173,12 @@ pub fn sort_equality_subroutine(
     let p = 65537;
     let modp = &evaluator.params().plaintext_modulus_op;
 
+    // all powers are at the same level
+    let level = ht_powers[0][0].level();
+
     let one_pt = evaluator.plaintext_encode(
         &vec![1; evaluator.params().degree],
-        Encoding::simd(0, PolyCache::AddSub(Representation::Evaluation)),
+        Encoding::simd(level, PolyCache::AddSub(Representation::Evaluation)),
     );
 
     let n = values.len();
@@ -171,7 +206,7 @@ pub fn sort_equality_subroutine(
             if i != 0 {
                 let pt = evaluator.plaintext_encode(
                     &vec![i_pow_k; evaluator.params().degree],
-                    Encoding::simd(0, PolyCache::Mul(PolyType::Q)),
+                    Encoding::simd(level, PolyCache::Mul(PolyType::Q)),
                 );
                 for j in 0..n {
                     evaluator.add_assign(
@@ -221,38 +256,36 @@ pub fn univariate_less_than(
     sk: &SecretKey,
 ) -> Ciphertext {
     let z = evaluator.sub(x, y);
-    let z_sq = evaluator.relinearize(&evaluator.mul(&z, &z), ek);
+    let z_sq = evaluator.relinearize(&evaluator.square(&z), ek);
 
     // z^2..(z^2)^181
     let mut m_powers = powers_of_x(evaluator, &z_sq, 181, sk, ek);
     // (z^2)^181..((z^2)^181)^181
-    let k_powers = powers_of_x(evaluator, &m_powers[180], 181, sk, ek);
+    let mut k_powers = powers_of_x(evaluator, &m_powers[180], 181, sk, ek);
 
     // decrypt_and_print(evaluator, &m_powers[180], sk, "m_powers[180]");
     // decrypt_and_print(evaluator, &k_powers[180], sk, "k_powers[180]");
 
-    // ((z^2)^181)^181 * (z^2)^7 = z^65536; z^{p-1}
-    let mut z_max_lazy = evaluator.mul_lazy(&k_powers[180], &m_powers[6]);
-    {
-        // coefficient for z^65536 = (p+1)/2
-        let pt = evaluator.plaintext_encode(
-            &vec![32769; evaluator.params().degree],
-            Encoding::simd(0, PolyCache::Mul(PolyType::PQ)),
-        );
-        evaluator.mul_poly_assign(&mut z_max_lazy, pt.mul_poly_ref());
-    }
+    // With automatic modulus switching powers end up at different levels. Products of m and k powers
+    // are summed up lazily, hence must be at the same level. k_powers[180] is the deepest of all.
+    let level = k_powers[180].level();
 
     // change m_powers to Evaluation representation for plaintext multiplications
     m_powers.iter_mut().for_each(|x| {
+        evaluator.mod_down_level(x, level);
         evaluator.ciphertext_change_representation(x, Representation::Evaluation);
     });
+    k_powers
+        .iter_mut()
+        .for_each(|x| evaluator.mod_down_level(x, level));
 
     let coefficients = read_values("less_than.bin");
 
     // evaluate g(x), where x = z^2
-    let mut left_over = Ciphertext::placeholder();
-    let mut sum_k = Ciphertext::placeholder();
-    for k_index in 0..182 {
+    //
+    // Giant-step block `k_index` is (x^0 + x + ... + x^180) * (x^181)^k_index with alpha
+    // coefficients. Blocks are independent of each other, hence are evaluated concurrently.
+    let block = |k_index: usize| {
         // m loop calculates x^0 + x + ... + x^181
         let mut x_0_pt = None;
         let mut sum_m = Ciphertext::placeholder();
@@ -265,13 +298,13 @@ pub fn univariate_less_than(
                 if m_index == 0 {
                     let pt_alpha = evaluator.plaintext_encode(
                         &vec![alpha; evaluator.params().degree],
-                        Encoding::simd(0, PolyCache::AddSub(Representation::Evaluation)),
+                        Encoding::simd(level, PolyCache::AddSub(Representation::Evaluation)),
                     );
                     x_0_pt = Some(pt_alpha);
                 } else {
                     let pt_alpha = evaluator.plaintext_encode(
                         &vec![alpha; evaluator.params().degree],
-                        Encoding::simd(0, PolyCache::Mul(PolyType::Q)),
+                        Encoding::simd(level, PolyCache::Mul(PolyType::Q)),
                     );
                     if m_index == 1 {
                         sum_m = evaluator.mul_poly(&m_powers[m_index - 1], pt_alpha.mul_poly_ref());
@@ -290,19 +323,24 @@ pub fn univariate_less_than(
             evaluator.add_assign_plaintext(&mut sum_m, &x_0_pt.unwrap());
         }
 
-        if k_index == 0 {
-            evaluator.ciphertext_change_representation(&mut sum_m, Representation::Coefficient);
-            left_over = sum_m;
-        } else {
+        sum_m
+    };
+
+    let mut left_over = block(0);
+    evaluator.ciphertext_change_representation(&mut left_over, Representation::Coefficient);
+
+    let mut sum_k = map_reduce_range(
+        1..182,
+        |k_index| {
             // `sum_m` is in Evaluation representation and k_powers is in Coefficient  so pass `sum_m` is first operand
-            let product = evaluator.mul_lazy(&sum_m, &k_powers[k_index - 1]);
-            if k_index == 1 {
-                sum_k = product;
-            } else {
-                evaluator.add_assign(&mut sum_k, &product);
-            }
-        }
-    }
+            evaluator.mul_lazy(&block(k_index), &k_powers[k_index - 1])
+        },
+        |mut sum, product| {
+            evaluator.add_assign(&mut sum, &product);
+            sum
+        },
+    )
+    .unwrap();
 
     let mut sum_k = evaluator.relinearize(&evaluator.scale_and_round(&mut sum_k), ek);
     evaluator.add_assign(&mut sum_k, &left_over);
@@ -310,6 +348,27 @@ pub fn univariate_less_than(
     // z * g(z^2)
     let z_gx = evaluator.mul_lazy(&sum_k, &z);
 
+    // ((z^2)^181)^181 * (z^2)^7 = z^65536; z^{p-1}
+    //
+    // `sum_k` may have been switched to lower level during relinearization and lazy products
+    // can only be summed at the same level. Hence switch operands down to level of `z_gx`.
+    let mut z_max_lazy = {
+        let mut k_power = k_powers[180].clone();
+        let mut m_power = m_powers[6].clone();
+        evaluator.mod_down_level(&mut k_power, z_gx.level());
+        evaluator.mod_down_level(&mut m_power, z_gx.level());
+        // `m_power` is in Evaluation representation so pass it as the first operand
+        evaluator.mul_lazy(&m_power, &k_power)
+    };
+    {
+        // coefficient for z^65536 = (p+1)/2
+        let pt = evaluator.plaintext_encode(
+            &vec![32769; evaluator.params().degree],
+            Encoding::simd(z_gx.level(), PolyCache::Mul(PolyType::PQ)),
+        );
+        evaluator.mul_poly_assign(&mut z_max_lazy, pt.mul_poly_ref());
+    }
+
     // ((p+1)/2)z + z * g(z^2)
     evaluator.add_assign(&mut z_max_lazy, &z_gx);
 
@@ -319,6 +378,31 @@ pub fn univariate_less_than(
     res
 }
 
+/// Requirements of `univariate_less_than`
+pub fn univariate_less_than_requirements() -> Requirements {
+    // z^2
+    let square = Requirements {
+        depth: 1,
+        ct_ct_muls: 1,
+        relinearizations: 1,
+        rotations: vec![],
+    };
+    // m_powers (z^2)^1..(z^2)^181 followed by k_powers ((z^2)^181)^1..((z^2)^181)^181
+    let powers = square
+        .then(&powers_of_x_requirements(181))
+        .then(&powers_of_x_requirements(181));
+    // g(z^2): products of m_powers (multiplied by plaintexts) and k_powers are summed lazily and relinearized.
+    // Followed by multiplication with z and summation with ((p+1)/2)z^(p-1), which costs another
+    // multiplication and relinearization.
+    let evaluate = Requirements {
+        depth: 2,
+        ct_ct_muls: 181 + 1 + 1,
+        relinearizations: 2,
+        rotations: vec![],
+    };
+    powers.then(&evaluate)
+}
+
 /// \alpha_i = \sum_{a = 1}^{\frac{p-1}{2}} a^{p - 1 - i}
 pub fn compute_lt_coefficients(t: u64) -> Vec<u64> {
     let modt = Modulus::new(t);
@@ -398,6 +482,69 @@ mod tests {
         assert_eq!(res_m, expected);
     }
 
+    #[test]
+    fn less_than_works_with_auto_mod_switching() {
+        let mut rng = thread_rng();
+
+        let mut params = BfvParameters::new(&[60; 10], 65537, 1 << 4);
+        params.enable_hybrid_key_switching(&[60; 3]);
+
+        let modt_by_2 = Modulus::new(params.plaintext_modulus / 2);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let mx = modt_by_2.random_vec(params.degree, &mut rng);
+        let my = modt_by_2.random_vec(params.degree, &mut rng);
+
+        let rlk_levels = (0..params.max_level).collect::<Vec<usize>>();
+        let ek = EvaluationKey::new(&params, &sk, &rlk_levels, &[], &[], &mut rng);
+
+        let mut evaluator = Evaluator::new(params);
+        evaluator.enable_auto_mod_switching();
+
+        let ptx = evaluator.plaintext_encode(&mx, Encoding::default());
+        let pty = evaluator.plaintext_encode(&my, Encoding::default());
+        let x = evaluator.encrypt(&sk, &ptx, &mut rng);
+        let y = evaluator.encrypt(&sk, &pty, &mut rng);
+        let res_ct = univariate_less_than(&evaluator, &x, &y, &ek, &sk);
+        assert!(res_ct.level() > 0);
+
+        let res_m =
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &res_ct), Encoding::default());
+        let expected = mx
+            .iter()
+            .zip(my.iter())
+            .map(|(x, y)| if x < y { 1 } else { 0 })
+            .collect::<Vec<u64>>();
+        assert_eq!(res_m, expected);
+    }
+
+    #[test]
+    fn powers_of_x_works() {
+        let mut rng = thread_rng();
+
+        let mut params = BfvParameters::new(&[60; 10], 65537, 1 << 4);
+        params.enable_hybrid_key_switching(&[60; 3]);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let ek = EvaluationKey::new(&params, &sk, &[0], &[], &[], &mut rng);
+        let evaluator = Evaluator::new(params);
+
+        let modt = Modulus::new(evaluator.params().plaintext_modulus);
+        let m = modt.random_vec(evaluator.params().degree, &mut rng);
+        let pt = evaluator.plaintext_encode(&m, Encoding::default());
+        let x = evaluator.encrypt(&sk, &pt, &mut rng);
+
+        let max = 13;
+        let powers = powers_of_x(&evaluator, &x, max, &sk, &ek);
+        assert_eq!(powers.len(), max);
+
+        for (i, c) in powers.iter().enumerate() {
+            let res_m = evaluator.plaintext_decode(&evaluator.decrypt(&sk, c), Encoding::default());
+            let expected = m.iter().map(|v| modt.exp(*v, i + 1)).collect::<Vec<u64>>();
+            assert_eq!(res_m, expected, "x^{} mismatch", i + 1);
+        }
+    }
+
     // #[test]
     // fn sort_univariate_works() {
     //     let mut rng = thread_rng();
@@ -0,0 +1,546 @@
+use crate::planner::Requirements;
+use crate::utils::map_reduce_range;
+use bfv::{
+    Ciphertext, Encoding, EvaluationKey, Evaluator, Plaintext, PolyCache, PolyType, Representation,
+};
+
+/// Plaintext matrix that multiplies encrypted vectors with diagonal method of Halevi and Shoup.
+///
+/// Vector of dimension `d` is packed in SIMD slots of each row, where `d` must divide no. of
+/// slots in a row (ie `degree / 2`). If `d` is smaller than row, vector must be repeated along the
+/// row. Matrix is applied to both rows independently and the output is packed the same way.
+///
+/// Matrices over all `degree` slots (see `from_slot_matrix`) mix both rows. Their diagonals are
+/// additionally split by whether they apply to `v` or to `v` with rows swapped.
+///
+/// Non-zero diagonals are encoded once and are applied with baby-step giant-step rotations, ie
+/// `Mv = \sum_k rot_{k * b}(\sum_j rot_{-k * b}(diag_{k * b + j}) * rot_j(v))`, where `b` is no. of
+/// baby steps. Zero diagonals are skipped, thus sparse matrices (for ex. permutations) need fewer
+/// rotations.
+pub struct LinearTransform {
+    dimension: usize,
+    degree: usize,
+    level: usize,
+    baby_steps: usize,
+    /// Non-zero diagonals grouped by giant step. Diagonal `k * b + j` is at
+    /// `(k, [(j, row_swap, diagonal)])`.
+    giant_steps: Vec<(usize, Vec<(usize, bool, Plaintext)>)>,
+}
+
+impl LinearTransform {
+    /// Precomputes diagonals of square `matrix` for ciphertexts at `level`. Entries must be smaller
+    /// than plaintext modulus.
+    ///
+    /// Panics if `matrix` is not square, its dimension does not divide row size or it's zero.
+    pub fn new(evaluator: &Evaluator, matrix: &[Vec<u64>], level: usize) -> LinearTransform {
+        let dimension = matrix.len();
+        let row = evaluator.params().degree / 2;
+        assert!(matrix.iter().all(|r| r.len() == dimension));
+        assert!(
+            dimension != 0 && row % dimension == 0,
+            "Dimension must divide no. of slots in a row"
+        );
+
+        LinearTransform::with_diagonals(
+            evaluator,
+            dimension,
+            dimension,
+            level,
+            false,
+            |_, i, slot| {
+                let r = slot % row % dimension;
+                matrix[r][(r + i) % dimension]
+            },
+        )
+    }
+
+    /// Precomputes `degree x degree` `matrix` over all slots for ciphertexts at `level`. Unlike
+    /// `new`, output slots of a row may depend on slots of the other row. Entries must be smaller
+    /// than plaintext modulus.
+    ///
+    /// Dense matrices have `degree` non-zero diagonals, which are all cached as plaintexts at
+    /// `level`, and `apply` multiplies with each of them. Memory and runtime grow with `degree^2`,
+    /// thus dense slot matrices are only practical for toy degrees (ie up to 2^11 or so).
+    ///
+    /// Panics if `matrix` is not `degree x degree` or it's zero.
+    pub fn from_slot_matrix(
+        evaluator: &Evaluator,
+        matrix: &[Vec<u64>],
+        level: usize,
+    ) -> LinearTransform {
+        let degree = evaluator.params().degree;
+        assert!(matrix.len() == degree && matrix.iter().all(|r| r.len() == degree));
+
+        LinearTransform::from_slot_entries(evaluator, level, |i, j| matrix[i][j])
+    }
+
+    /// Precomputes transform that moves coefficients of a coefficient encoded (see
+    /// `Encoding::poly`) message to slots, ie output decoded with SIMD encoding equals input
+    /// decoded with coefficient encoding.
+    ///
+    /// Transform is the dense matrix `U^{-1}`, where `U` evaluates coefficients at the roots of
+    /// slots. Its diagonals are computed directly from the roots without materialising the matrix,
+    /// but they are as many and as costly as those of `from_slot_matrix`. Only use it with toy
+    /// degrees.
+    pub fn coefficients_to_slots(evaluator: &Evaluator, level: usize) -> LinearTransform {
+        // input slots are `U * m` for coefficients `m`, thus the transform is `U^{-1}`. Roots of
+        // slots are all primitive `2 * degree`-th roots, hence
+        // `U^{-1}[i][j] = degree^{-1} * root_j^{-i}`.
+        let params = evaluator.params();
+        let modt = &params.plaintext_modulus_op;
+        let degree_inv = modt.inv(params.degree as u64);
+        let roots_inv = slot_roots(evaluator)
+            .iter()
+            .map(|root| modt.inv(*root))
+            .collect::<Vec<u64>>();
+        LinearTransform::from_slot_entries(evaluator, level, |i, j| {
+            modt.mul_mod_fast(degree_inv, modt.exp(roots_inv[j], i))
+        })
+    }
+
+    /// Precomputes transform that moves slots to coefficients, ie output decoded with coefficient
+    /// encoding equals input decoded with SIMD encoding. Inverse of `coefficients_to_slots` with
+    /// same costs and limits.
+    pub fn slots_to_coefficients(evaluator: &Evaluator, level: usize) -> LinearTransform {
+        // `U[i][j] = root_i^j`
+        let modt = &evaluator.params().plaintext_modulus_op;
+        let roots = slot_roots(evaluator);
+        LinearTransform::from_slot_entries(evaluator, level, |i, j| modt.exp(roots[i], j))
+    }
+
+    /// Precomputes `degree x degree` matrix over all slots with `entry(i, j)` at row `i` and
+    /// column `j`. Diagonals are computed one at a time from entries.
+    fn from_slot_entries<F: Fn(usize, usize) -> u64>(
+        evaluator: &Evaluator,
+        level: usize,
+        entry: F,
+    ) -> LinearTransform {
+        let degree = evaluator.params().degree;
+        let row = degree / 2;
+
+        // diagonal `(row_swap, i)` at slot `(r, a)` is the entry of matrix for input slot
+        // `(r ^ row_swap, a + i)`
+        LinearTransform::with_diagonals(evaluator, degree, row, level, true, |row_swap, i, slot| {
+            let (r, a) = (slot / row, slot % row);
+            entry(slot, (r ^ row_swap as usize) * row + (a + i) % row)
+        })
+    }
+
+    /// Encodes non-zero diagonals `diagonal(row_swap, i, slot)` for `i < steps`. Diagonals with
+    /// `row_swap` are only considered if `row_swaps` is set.
+    fn with_diagonals<F: Fn(bool, usize, usize) -> u64>(
+        evaluator: &Evaluator,
+        dimension: usize,
+        steps: usize,
+        level: usize,
+        row_swaps: bool,
+        diagonal: F,
+    ) -> LinearTransform {
+        let degree = evaluator.params().degree;
+        let row = degree / 2;
+
+        let baby_steps = (steps as f64).sqrt().ceil() as usize;
+        let mut giant_steps: Vec<(usize, Vec<(usize, bool, Plaintext)>)> = vec![];
+        for i in 0..steps {
+            for row_swap in [false, true] {
+                if row_swap && !row_swaps {
+                    continue;
+                }
+
+                // diagonal `i` pre-rotated right by `k * b`, so that rotation of the giant step
+                // undoes it
+                let (k, j) = (i / baby_steps, i % baby_steps);
+                let shift = (k * baby_steps) % row;
+                let values = (0..degree)
+                    .map(|slot| {
+                        let a = (slot % row + row - shift) % row;
+                        diagonal(row_swap, i, (slot / row) * row + a)
+                    })
+                    .collect::<Vec<u64>>();
+                if values.iter().all(|v| *v == 0) {
+                    continue;
+                }
+
+                let pt = evaluator
+                    .plaintext_encode(&values, Encoding::simd(level, PolyCache::Mul(PolyType::Q)));
+                match giant_steps.last_mut() {
+                    Some((last_k, diagonals)) if *last_k == k => diagonals.push((j, row_swap, pt)),
+                    _ => giant_steps.push((k, vec![(j, row_swap, pt)])),
+                }
+            }
+        }
+        assert!(!giant_steps.is_empty(), "Matrix must not be zero");
+
+        LinearTransform {
+            dimension,
+            degree,
+            level,
+            baby_steps,
+            giant_steps,
+        }
+    }
+
+    /// Precomputes slot permutation that maps vector `v` to `w` with `w[i] = v[permutation[i]]`.
+    ///
+    /// Panics if `permutation` is not a permutation of `0..permutation.len()`.
+    pub fn from_permutation(
+        evaluator: &Evaluator,
+        permutation: &[usize],
+        level: usize,
+    ) -> LinearTransform {
+        let dimension = permutation.len();
+        let mut seen = vec![false; dimension];
+        permutation.iter().for_each(|p| {
+            assert!(*p < dimension && !seen[*p], "Invalid permutation");
+            seen[*p] = true;
+        });
+
+        let matrix = permutation
+            .iter()
+            .map(|p| {
+                let mut r = vec![0; dimension];
+                r[*p] = 1;
+                r
+            })
+            .collect::<Vec<Vec<u64>>>();
+        LinearTransform::new(evaluator, &matrix, level)
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Returns rotations applied to the input ciphertext (baby steps)
+    pub fn baby_step_rotations(&self) -> Vec<isize> {
+        let mut rotations = self.baby_step_rotations_of(false);
+        rotations.extend(self.baby_step_rotations_of(true));
+        rotations.sort();
+        rotations.dedup();
+        rotations
+    }
+
+    /// Returns baby steps of diagonals with `row_swap`
+    fn baby_step_rotations_of(&self, row_swap: bool) -> Vec<isize> {
+        let mut rotations = self
+            .giant_steps
+            .iter()
+            .flat_map(|(_, diagonals)| {
+                diagonals
+                    .iter()
+                    .filter(|(j, swap, _)| *j != 0 && *swap == row_swap)
+                    .map(|(j, _, _)| *j as isize)
+            })
+            .collect::<Vec<isize>>();
+        rotations.sort();
+        rotations.dedup();
+        rotations
+    }
+
+    /// Returns whether any diagonal applies to input with rows swapped
+    fn has_row_swap(&self) -> bool {
+        self.giant_steps
+            .iter()
+            .any(|(_, diagonals)| diagonals.iter().any(|(_, swap, _)| *swap))
+    }
+
+    /// Returns rotations applied to sums of products with diagonals (giant steps)
+    pub fn giant_step_rotations(&self) -> Vec<isize> {
+        self.giant_steps
+            .iter()
+            .map(|(k, _)| (k * self.baby_steps) as isize)
+            .filter(|r| *r != 0)
+            .collect()
+    }
+
+    /// Requirements of `apply`. Giant step rotations are on ciphertexts multiplied with plaintext
+    /// diagonals, hence are at depth 1.
+    pub fn requirements(&self) -> Requirements {
+        let mut rotations = self
+            .baby_step_rotations()
+            .into_iter()
+            .map(|r| (r, 0))
+            .collect::<Vec<(isize, usize)>>();
+        if self.has_row_swap() {
+            rotations.push(((2 * self.degree - 1) as isize, 0));
+        }
+        rotations.extend(self.giant_step_rotations().into_iter().map(|r| (r, 1)));
+
+        Requirements {
+            depth: 1,
+            rotations,
+            ..Default::default()
+        }
+    }
+
+    /// Multiplies matrix with vector encrypted in `ct`. `ek` must have galois keys for rotations in
+    /// `requirements`. Output is in same representation as `ct`.
+    ///
+    /// Panics if `ct` is not at level of the transform.
+    pub fn apply(&self, evaluator: &Evaluator, ct: &Ciphertext, ek: &EvaluationKey) -> Ciphertext {
+        assert!(ct.level() == self.level);
+        let representation = ct.c_ref()[0].representation().clone();
+
+        // diagonals are in Evaluation representation
+        let mut ct_eval = ct.clone();
+        evaluator.ciphertext_change_representation(&mut ct_eval, Representation::Evaluation);
+
+        // baby steps share decomposition of `ct` (resp. `ct` with rows swapped)
+        let swapped = self
+            .has_row_swap()
+            .then(|| evaluator.rotate(&ct_eval, (2 * self.degree - 1) as isize, ek));
+        let bases = [false, true].map(|row_swap| {
+            let base = if row_swap {
+                swapped.as_ref()
+            } else {
+                Some(&ct_eval)
+            };
+            let rotations = self.baby_step_rotations_of(row_swap);
+            let rotated = match base {
+                Some(base) if !rotations.is_empty() => evaluator.rotate_many(base, &rotations, ek),
+                _ => vec![],
+            };
+            (base, rotations, rotated)
+        });
+        let baby_step = |j: usize, row_swap: bool| {
+            let (base, rotations, rotated) = &bases[row_swap as usize];
+            if j == 0 {
+                base.unwrap()
+            } else {
+                &rotated[rotations.binary_search(&(j as isize)).unwrap()]
+            }
+        };
+
+        // Giant steps are independent of each other, hence are evaluated concurrently.
+        let mut res = map_reduce_range(
+            0..self.giant_steps.len(),
+            |index| {
+                let (k, diagonals) = &self.giant_steps[index];
+                let (j, row_swap, pt) = &diagonals[0];
+                let mut sum = evaluator.mul_poly(baby_step(*j, *row_swap), pt.mul_poly_ref());
+                diagonals[1..].iter().for_each(|(j, row_swap, pt)| {
+                    evaluator.fma_poly(&mut sum, baby_step(*j, *row_swap), pt.mul_poly_ref());
+                });
+
+                if *k != 0 {
+                    evaluator.rotate_assign(&mut sum, (k * self.baby_steps) as isize, ek);
+                }
+                sum
+            },
+            |mut sum, ct| {
+                evaluator.add_assign(&mut sum, &ct);
+                sum
+            },
+        )
+        .unwrap();
+
+        evaluator.ciphertext_change_representation(&mut res, representation);
+        res
+    }
+}
+
+/// Returns roots of unity that slots of SIMD encoding evaluate plaintext polynomials at, ie slots
+/// of polynomial `X`.
+fn slot_roots(evaluator: &Evaluator) -> Vec<u64> {
+    evaluator.params().coefficients_to_slots_plain(&[0, 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::Planner;
+    use bfv::{BfvParameters, SecretKey};
+    use rand::{thread_rng, Rng};
+
+    fn unit_vector(len: usize, i: usize) -> Vec<u64> {
+        let mut v = vec![0; len];
+        v[i] = 1;
+        v
+    }
+
+    fn matrix_vector_product(matrix: &[Vec<u64>], v: &[u64], t: u64) -> Vec<u64> {
+        // vector is repeated along both rows
+        (0..v.len())
+            .map(|i| {
+                let r = &matrix[i % matrix.len()];
+                r.iter()
+                    .enumerate()
+                    .map(|(j, m)| (*m as u128 * v[j] as u128) % t as u128)
+                    .sum::<u128>() as u64
+                    % t
+            })
+            .collect()
+    }
+
+    #[test]
+    fn linear_transform_works() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let t = params.plaintext_modulus;
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let evaluator = Evaluator::new(params);
+
+        // full row and a smaller dimension repeated along the row
+        for dimension in [8, 4] {
+            let matrix = (0..dimension)
+                .map(|_| (0..dimension).map(|_| rng.gen_range(0..t)).collect())
+                .collect::<Vec<Vec<u64>>>();
+            let lt = LinearTransform::new(&evaluator, &matrix, 0);
+
+            let ek = Planner::new(lt.requirements())
+                .evaluation_key_spec(evaluator.params(), false)
+                .generate(evaluator.params(), &sk, &mut rng);
+
+            let v = (0..dimension)
+                .map(|_| rng.gen_range(0..t))
+                .collect::<Vec<u64>>();
+            let m = (0..evaluator.params().degree)
+                .map(|i| v[i % dimension])
+                .collect::<Vec<u64>>();
+            let ct = evaluator.encrypt(
+                &sk,
+                &evaluator.plaintext_encode(&m, Encoding::default()),
+                &mut rng,
+            );
+
+            let res = lt.apply(&evaluator, &ct, &ek);
+            let res_m =
+                evaluator.plaintext_decode(&evaluator.decrypt(&sk, &res), Encoding::default());
+            assert_eq!(res_m, matrix_vector_product(&matrix, &m, t));
+        }
+    }
+
+    #[test]
+    fn permutation_works() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let evaluator = Evaluator::new(params);
+
+        // rotation by 3 needs a single diagonal
+        let rotation = (0..8).map(|i| (i + 3) % 8).collect::<Vec<usize>>();
+        let lt = LinearTransform::from_permutation(&evaluator, &rotation, 0);
+        assert_eq!(lt.giant_steps.len(), 1);
+        assert_eq!(lt.requirements().rotations.len(), 1);
+
+        let permutation = [3, 0, 7, 1, 6, 2, 5, 4];
+        let lt = LinearTransform::from_permutation(&evaluator, &permutation, 0);
+        let ek = Planner::new(lt.requirements())
+            .evaluation_key_spec(evaluator.params(), false)
+            .generate(evaluator.params(), &sk, &mut rng);
+
+        let m = evaluator
+            .params()
+            .plaintext_modulus_op
+            .random_vec(evaluator.params().degree, &mut rng);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        let res = lt.apply(&evaluator, &ct, &ek);
+        let res_m = evaluator.plaintext_decode(&evaluator.decrypt(&sk, &res), Encoding::default());
+
+        let expected = (0..m.len())
+            .map(|i| m[(i / 8) * 8 + permutation[i % 8]])
+            .collect::<Vec<u64>>();
+        assert_eq!(res_m, expected);
+    }
+
+    #[test]
+    fn slot_matrix_works() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let evaluator = Evaluator::new(params);
+
+        // reverses all slots, which mixes rows
+        let matrix = (0..degree)
+            .map(|i| unit_vector(degree, degree - 1 - i))
+            .collect::<Vec<Vec<u64>>>();
+        let lt = LinearTransform::from_slot_matrix(&evaluator, &matrix, 0);
+        let ek = Planner::new(lt.requirements())
+            .evaluation_key_spec(evaluator.params(), false)
+            .generate(evaluator.params(), &sk, &mut rng);
+
+        let m = evaluator
+            .params()
+            .plaintext_modulus_op
+            .random_vec(degree, &mut rng);
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        let res_m = evaluator.plaintext_decode(
+            &evaluator.decrypt(&sk, &lt.apply(&evaluator, &ct, &ek)),
+            Encoding::default(),
+        );
+        assert_eq!(res_m, m.iter().rev().copied().collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn slot_roots_evaluate_coefficients() {
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+        let modt = params.plaintext_modulus_op.clone();
+        let evaluator = Evaluator::new(params);
+
+        // column `j` of `U` are the slots of `X^j`
+        let roots = slot_roots(&evaluator);
+        for j in 0..degree {
+            let slots = evaluator
+                .params()
+                .coefficients_to_slots_plain(&unit_vector(degree, j));
+            let expected = roots.iter().map(|r| modt.exp(*r, j)).collect::<Vec<u64>>();
+            assert_eq!(slots, expected);
+        }
+    }
+
+    #[test]
+    fn coefficients_to_slots_and_back() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let evaluator = Evaluator::new(params);
+
+        let c2s = LinearTransform::coefficients_to_slots(&evaluator, 0);
+        let s2c = LinearTransform::slots_to_coefficients(&evaluator, 0);
+        let ek = Planner::new(c2s.requirements().and(&s2c.requirements()))
+            .evaluation_key_spec(evaluator.params(), false)
+            .generate(evaluator.params(), &sk, &mut rng);
+
+        let poly_encoding = Encoding::poly(0, PolyCache::None);
+        let m = evaluator
+            .params()
+            .plaintext_modulus_op
+            .random_vec(degree, &mut rng);
+
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, poly_encoding.clone()),
+            &mut rng,
+        );
+        let res = c2s.apply(&evaluator, &ct, &ek);
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &res), Encoding::default()),
+            m
+        );
+
+        let ct = evaluator.encrypt(
+            &sk,
+            &evaluator.plaintext_encode(&m, Encoding::default()),
+            &mut rng,
+        );
+        let res = s2c.apply(&evaluator, &ct, &ek);
+        assert_eq!(
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &res), poly_encoding),
+            m
+        );
+    }
+}
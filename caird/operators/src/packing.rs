@@ -0,0 +1,246 @@
+use crate::planner::Requirements;
+use crate::utils::map_range;
+use bfv::{Ciphertext, Encoding, EvaluationKey, Evaluator, PolyCache, PolyType, Representation};
+
+/// Multiplies `ct` with plaintext that is 1 at `slot` and 0 elsewhere. Output is in same
+/// representation as `ct`.
+fn mask_slot(evaluator: &Evaluator, ct: &Ciphertext, slot: usize) -> Ciphertext {
+    let mut m = vec![0; evaluator.params().degree];
+    m[slot] = 1;
+    let pt =
+        evaluator.plaintext_encode(&m, Encoding::simd(ct.level(), PolyCache::Mul(PolyType::Q)));
+
+    let representation = ct.c_ref()[0].representation().clone();
+    let mut res = ct.clone();
+    evaluator.ciphertext_change_representation(&mut res, Representation::Evaluation);
+    evaluator.mul_plaintext_assign(&mut res, &pt);
+    evaluator.ciphertext_change_representation(&mut res, representation);
+    res
+}
+
+/// Merges ciphertexts with a value in slot 0 into single ciphertext with value of `cts[i]` in
+/// slot `i`. Other slots of `cts` are masked out, thus need not be 0.
+///
+/// Ciphertexts are merged pairwise in a tree, `ct_{2i} + rot_{-s}(ct_{2i + 1})` with `s`
+/// doubling every round, which requires `log(k)` rotation keys.
+///
+/// Panics if `cts` is empty or there are more ciphertexts than slots in a row.
+pub fn pack(evaluator: &Evaluator, cts: &[Ciphertext], ek: &EvaluationKey) -> Ciphertext {
+    assert!(!cts.is_empty());
+    assert!(cts.len() <= evaluator.params().degree / 2);
+
+    let mut packed = map_range(0..cts.len(), |i| mask_slot(evaluator, &cts[i], 0));
+    let mut step = 1;
+    while packed.len() > 1 {
+        packed = map_range(0..packed.len().div_ceil(2), |i| {
+            let mut ct = packed[2 * i].clone();
+            if let Some(next) = packed.get(2 * i + 1) {
+                evaluator.add_assign(&mut ct, &evaluator.rotate(next, -(step as isize), ek));
+            }
+            ct
+        });
+        step *= 2;
+    }
+    packed.pop().unwrap()
+}
+
+/// Requirements of `pack` for `k` ciphertexts
+pub fn pack_requirements(k: usize) -> Requirements {
+    let rotations = (0..)
+        .map(|j| 1usize << j)
+        .take_while(|step| *step < k)
+        .map(|step| (-(step as isize), 1))
+        .collect();
+
+    Requirements {
+        // masking is a plaintext multiplication
+        depth: 1,
+        rotations,
+        ..Default::default()
+    }
+}
+
+/// Returns ciphertext with value of `slot` of `ct` in slot 0 and 0 elsewhere.
+///
+/// Slots of second row (ie `slot >= degree / 2`) are moved to first row with row swap.
+pub fn extract_slot(
+    evaluator: &Evaluator,
+    ct: &Ciphertext,
+    slot: usize,
+    ek: &EvaluationKey,
+) -> Ciphertext {
+    let degree = evaluator.params().degree;
+    let row = degree / 2;
+    assert!(slot < degree);
+
+    // rotations are applied before masking, so that they are on a fresh ciphertext
+    let mut res = ct.clone();
+    if slot >= row {
+        evaluator.rotate_assign(&mut res, (2 * degree - 1) as isize, ek);
+    }
+    if slot % row != 0 {
+        evaluator.rotate_assign(&mut res, (slot % row) as isize, ek);
+    }
+    mask_slot(evaluator, &res, 0)
+}
+
+/// Requirements of `extract_slot` for `slot` and polynomial `degree`
+pub fn extract_slot_requirements(slot: usize, degree: usize) -> Requirements {
+    let row = degree / 2;
+    let mut rotations = vec![];
+    if slot >= row {
+        rotations.push(((2 * degree - 1) as isize, 0));
+    }
+    if slot % row != 0 {
+        rotations.push(((slot % row) as isize, 0));
+    }
+
+    Requirements {
+        depth: 1,
+        rotations,
+        ..Default::default()
+    }
+}
+
+/// Returns ciphertext with sum of first `k` slots of `ct` in slot 0. Other slots are not meaningful.
+///
+/// Slots are summed with rotations by powers of two, thus slots from `k` up to the next power of
+/// two must be 0 (for ex. as in output of `pack`).
+///
+/// Panics if `k` is 0 or more than slots in a row.
+pub fn sum_slots(
+    evaluator: &Evaluator,
+    ct: &Ciphertext,
+    k: usize,
+    ek: &EvaluationKey,
+) -> Ciphertext {
+    assert!(k != 0 && k <= evaluator.params().degree / 2);
+
+    let mut res = ct.clone();
+    let mut step = 1;
+    while step < k {
+        let rotated = evaluator.rotate(&res, step as isize, ek);
+        evaluator.add_assign(&mut res, &rotated);
+        step *= 2;
+    }
+    res
+}
+
+/// Requirements of `sum_slots` for `k` slots
+pub fn sum_slots_requirements(k: usize) -> Requirements {
+    let rotations = (0..)
+        .map(|j| 1usize << j)
+        .take_while(|step| *step < k)
+        .map(|step| (step as isize, 0))
+        .collect();
+
+    Requirements {
+        rotations,
+        ..Default::default()
+    }
+}
+
+/// Returns ciphertext with value of `slot` of `ct` in every slot.
+///
+/// Masked slot is summed with its rotations by powers of two, which fills its row, and then with
+/// the row swap of the sum.
+pub fn replicate_slot(
+    evaluator: &Evaluator,
+    ct: &Ciphertext,
+    slot: usize,
+    ek: &EvaluationKey,
+) -> Ciphertext {
+    let degree = evaluator.params().degree;
+    assert!(slot < degree);
+
+    let mut res = mask_slot(evaluator, ct, slot);
+    let mut step = 1;
+    while step < degree / 2 {
+        let rotated = evaluator.rotate(&res, -(step as isize), ek);
+        evaluator.add_assign(&mut res, &rotated);
+        step *= 2;
+    }
+    let swapped = evaluator.rotate(&res, (2 * degree - 1) as isize, ek);
+    evaluator.add_assign(&mut res, &swapped);
+    res
+}
+
+/// Requirements of `replicate_slot` for polynomial `degree`. Rotations are same as of `pack` for
+/// `degree / 2` ciphertexts along with row swap.
+pub fn replicate_slot_requirements(degree: usize) -> Requirements {
+    let mut requirements = pack_requirements(degree / 2);
+    requirements.rotations.push(((2 * degree - 1) as isize, 1));
+    requirements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::Planner;
+    use bfv::{BfvParameters, SecretKey};
+    use rand::thread_rng;
+
+    #[test]
+    fn pack_extract_and_replicate_work() {
+        let mut rng = thread_rng();
+        let params = BfvParameters::default(3, 1 << 4);
+        let degree = params.degree;
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let evaluator = Evaluator::new(params);
+
+        let k = 5;
+        let slots = [3, 11];
+        let requirements = pack_requirements(k)
+            .and(&extract_slot_requirements(slots[0], degree))
+            .and(&extract_slot_requirements(slots[1], degree))
+            .and(&replicate_slot_requirements(degree))
+            .and(&pack_requirements(k).then(&sum_slots_requirements(k)));
+        let ek = Planner::new(requirements)
+            .evaluation_key_spec(evaluator.params(), false)
+            .generate(evaluator.params(), &sk, &mut rng);
+
+        let modt = &evaluator.params().plaintext_modulus_op;
+        let messages = (0..k)
+            .map(|_| modt.random_vec(degree, &mut rng))
+            .collect::<Vec<Vec<u64>>>();
+        let cts = messages
+            .iter()
+            .map(|m| {
+                evaluator.encrypt(
+                    &sk,
+                    &evaluator.plaintext_encode(m, Encoding::default()),
+                    &mut rng,
+                )
+            })
+            .collect::<Vec<Ciphertext>>();
+        let decrypt = |ct: &Ciphertext| {
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, ct), Encoding::default())
+        };
+
+        // only slot 0 of every ciphertext is packed
+        let packed = pack(&evaluator, &cts, &ek);
+        let mut expected = vec![0; degree];
+        messages
+            .iter()
+            .enumerate()
+            .for_each(|(i, m)| expected[i] = m[0]);
+        assert_eq!(decrypt(&packed), expected);
+
+        let sum = expected.iter().fold(0, |acc, v| modt.add_mod_fast(acc, *v));
+        assert_eq!(decrypt(&sum_slots(&evaluator, &packed, k, &ek))[0], sum);
+
+        for slot in slots {
+            let mut expected = vec![0; degree];
+            expected[0] = messages[0][slot];
+            assert_eq!(
+                decrypt(&extract_slot(&evaluator, &cts[0], slot, &ek)),
+                expected
+            );
+
+            assert_eq!(
+                decrypt(&replicate_slot(&evaluator, &cts[0], slot, &ek)),
+                vec![messages[0][slot]; degree]
+            );
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use bfv::{BfvParameters, BfvParametersBuilder, EvaluationKey, SecretKey, SecurityLevel};
+use rand::{CryptoRng, RngCore};
+use std::cmp::max;
+
+/// Cost and key requirements of an operator circuit.
+///
+/// `depth` counts ciphertext-plaintext multiplications as well, since they grow the noise
+/// by about as much as ciphertext-ciphertext multiplications.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Requirements {
+    /// Multiplicative depth of the circuit
+    pub depth: usize,
+    /// No. of ciphertext-ciphertext multiplications (including lazy ones)
+    pub ct_ct_muls: usize,
+    /// No. of relinearizations
+    pub relinearizations: usize,
+    /// Rotations as (rotation index, multiplicative depth of the rotated ciphertext)
+    pub rotations: Vec<(isize, usize)>,
+}
+
+impl Requirements {
+    /// Requirements of circuit that evaluates `self` and then `next` on output of `self`.
+    pub fn then(&self, next: &Requirements) -> Requirements {
+        let mut rotations = self.rotations.clone();
+        next.rotations.iter().for_each(|(index, depth)| {
+            rotations.push((*index, self.depth + depth));
+        });
+
+        Requirements {
+            depth: self.depth + next.depth,
+            ct_ct_muls: self.ct_ct_muls + next.ct_ct_muls,
+            relinearizations: self.relinearizations + next.relinearizations,
+            rotations,
+        }
+    }
+
+    /// Requirements of circuit that evaluates `self` and `other` on independent inputs.
+    pub fn and(&self, other: &Requirements) -> Requirements {
+        let mut rotations = self.rotations.clone();
+        rotations.extend_from_slice(&other.rotations);
+
+        Requirements {
+            depth: max(self.depth, other.depth),
+            ct_ct_muls: self.ct_ct_muls + other.ct_ct_muls,
+            relinearizations: self.relinearizations + other.relinearizations,
+            rotations,
+        }
+    }
+
+    /// Requirements of circuit that evaluates `self` `n` times on independent inputs.
+    pub fn times(&self, n: usize) -> Requirements {
+        Requirements {
+            depth: self.depth,
+            ct_ct_muls: self.ct_ct_muls * n,
+            relinearizations: self.relinearizations * n,
+            rotations: self.rotations.clone(),
+        }
+    }
+}
+
+/// Relinearization and rotation keys to generate for an `EvaluationKey`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvaluationKeySpec {
+    pub rlk_levels: Vec<usize>,
+    pub rtg_indices: Vec<isize>,
+    pub rtg_levels: Vec<usize>,
+}
+
+impl EvaluationKeySpec {
+    pub fn generate<R: CryptoRng + RngCore>(
+        &self,
+        params: &BfvParameters,
+        sk: &SecretKey,
+        rng: &mut R,
+    ) -> EvaluationKey {
+        EvaluationKey::new(
+            params,
+            sk,
+            &self.rlk_levels,
+            &self.rtg_levels,
+            &self.rtg_indices,
+            rng,
+        )
+    }
+}
+
+/// Plans parameters and evaluation keys for a circuit described by `Requirements`.
+pub struct Planner {
+    requirements: Requirements,
+}
+
+impl Planner {
+    pub fn new(requirements: Requirements) -> Planner {
+        Planner { requirements }
+    }
+
+    pub fn requirements(&self) -> &Requirements {
+        &self.requirements
+    }
+
+    /// Returns parameter builder configured for circuit's depth. Call `build` on it to generate
+    /// parameters. Degree, or `insecure_for_testing`, can be set on the returned builder.
+    pub fn recommend_parameters(
+        &self,
+        security_level: SecurityLevel,
+        plaintext_modulus: u64,
+    ) -> BfvParametersBuilder {
+        BfvParametersBuilder::new()
+            .with_security_level(security_level)
+            .with_plaintext_modulus(plaintext_modulus)
+            .with_multiplicative_depth(self.requirements.depth)
+    }
+
+    /// Returns level of ciphertext at multiplicative `depth`.
+    ///
+    /// Without automatic modulus switching ciphertexts remain at level 0. With automatic modulus switching
+    /// the level is found by tracking noise estimate of a ciphertext squared `depth` times, the same way
+    /// evaluator does.
+    pub fn level_at_depth(params: &BfvParameters, depth: usize, auto_mod_switching: bool) -> usize {
+        if !auto_mod_switching {
+            return 0;
+        }
+
+        // keys do not exist at max level
+        let max_key_level = params.max_level.saturating_sub(1);
+        let floor = params.mod_down_noise_floor_bits();
+
+        let mut noise = params.fresh_noise_bits();
+        let mut level = 0;
+        for _ in 0..depth {
+            noise = params.mul_noise_bits(noise, noise);
+            while level < max_key_level {
+                let noise_next = params.mod_down_noise_bits(noise, level);
+                if noise_next <= floor + 1 {
+                    break;
+                }
+                noise = noise_next;
+                level += 1;
+            }
+        }
+        level
+    }
+
+    /// Returns minimal `EvaluationKeySpec` for the circuit.
+    ///
+    /// Keys are usable at their level and at any higher level (see `EvaluationKey::get_rlk`), hence
+    /// a single relinearization key at level 0 suffices. Each rotation key is generated once, at the
+    /// lowest level of ciphertexts it rotates.
+    pub fn evaluation_key_spec(
+        &self,
+        params: &BfvParameters,
+        auto_mod_switching: bool,
+    ) -> EvaluationKeySpec {
+        let mut spec = EvaluationKeySpec::default();
+
+        if self.requirements.relinearizations > 0 {
+            spec.rlk_levels = vec![0];
+        }
+
+        let mut rotations = self
+            .requirements
+            .rotations
+            .iter()
+            .map(|(index, depth)| {
+                (
+                    *index,
+                    Planner::level_at_depth(params, *depth, auto_mod_switching),
+                )
+            })
+            .collect::<Vec<(isize, usize)>>();
+        // lowest level of each rotation index comes first
+        rotations.sort();
+        rotations.dedup_by_key(|(index, _)| *index);
+        rotations.into_iter().for_each(|(index, level)| {
+            spec.rtg_indices.push(index);
+            spec.rtg_levels.push(level);
+        });
+
+        spec
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        powers_of_x_requirements, sort_requirements, univariate_less_than,
+        univariate_less_than_requirements,
+    };
+    use bfv::{Encoding, Evaluator, Modulus};
+    use rand::thread_rng;
+
+    #[test]
+    fn requirements_compose() {
+        let powers = powers_of_x_requirements(181);
+        assert_eq!(powers.depth, 8);
+        assert_eq!(powers.ct_ct_muls, 180);
+
+        let rotate = Requirements {
+            rotations: vec![(1, 0)],
+            ..Default::default()
+        };
+        let r = powers.then(&rotate).and(&powers.times(2));
+        assert_eq!(r.depth, 8);
+        assert_eq!(r.ct_ct_muls, 180 * 3);
+        assert_eq!(r.rotations, vec![(1, 8)]);
+
+        let less_than = univariate_less_than_requirements();
+        assert_eq!(less_than.depth, 19);
+        assert_eq!(less_than.ct_ct_muls, 544);
+        assert_eq!(less_than.relinearizations, 363);
+    }
+
+    #[test]
+    fn requirements_of_edge_cases() {
+        assert_eq!(powers_of_x_requirements(1).ct_ct_muls, 0);
+        assert_eq!(
+            sort_requirements(1).ct_ct_muls,
+            powers_of_x_requirements(65536).ct_ct_muls + 1
+        );
+        assert_eq!(sort_requirements(0).ct_ct_muls, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "At least x^1 must be computed")]
+    fn powers_of_x_requirements_rejects_zero() {
+        powers_of_x_requirements(0);
+    }
+
+    #[test]
+    fn planned_evaluation_key_works() {
+        let mut rng = thread_rng();
+
+        let planner = Planner::new(univariate_less_than_requirements());
+        let params: BfvParameters = planner
+            .recommend_parameters(SecurityLevel::Bits128, 65537)
+            .with_degree(1 << 4)
+            .insecure_for_testing(true)
+            .build();
+
+        let spec = planner.evaluation_key_spec(&params, true);
+        assert_eq!(spec.rlk_levels, vec![0]);
+        assert!(spec.rtg_indices.is_empty());
+
+        // single key per rotation index at the lowest level
+        let rotations = Planner::new(Requirements {
+            rotations: vec![(1, 10), (1, 0), (2, 10)],
+            ..Default::default()
+        })
+        .evaluation_key_spec(&params, true);
+        assert_eq!(rotations.rtg_indices, vec![1, 2]);
+        assert_eq!(rotations.rtg_levels[0], 0);
+        assert_eq!(planner.evaluation_key_spec(&params, false).rlk_levels, vec![0]);
+
+        let sk = SecretKey::random_with_params(&params, &mut rng);
+        let ek = spec.generate(&params, &sk, &mut rng);
+
+        let modt_by_2 = Modulus::new(params.plaintext_modulus / 2);
+        let mx = modt_by_2.random_vec(params.degree, &mut rng);
+        let my = modt_by_2.random_vec(params.degree, &mut rng);
+
+        let mut evaluator = Evaluator::new(params);
+        evaluator.enable_auto_mod_switching();
+
+        let x = evaluator.encrypt(&sk, &evaluator.plaintext_encode(&mx, Encoding::default()), &mut rng);
+        let y = evaluator.encrypt(&sk, &evaluator.plaintext_encode(&my, Encoding::default()), &mut rng);
+        let res_ct = univariate_less_than(&evaluator, &x, &y, &ek, &sk);
+
+        let res_m =
+            evaluator.plaintext_decode(&evaluator.decrypt(&sk, &res_ct), Encoding::default());
+        let expected = mx
+            .iter()
+            .zip(my.iter())
+            .map(|(x, y)| if x < y { 1 } else { 0 })
+            .collect::<Vec<u64>>();
+        assert_eq!(res_m, expected);
+    }
+}
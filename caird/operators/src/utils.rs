@@ -1,11 +1,44 @@
 use bfv::{Ciphertext, Encoding, Evaluator, SecretKey};
 use byteorder::{ByteOrder, LittleEndian};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::{
     fmt::format,
     io::{BufReader, Read, Write},
+    ops::Range,
     path::{Path, PathBuf},
 };
 
+/// Maps `f` over `range` and returns outputs in order of `range`.
+///
+/// Calls to `f` run on rayon's thread pool if `parallel` feature is enabled.
+pub(crate) fn map_range<T, F>(range: Range<usize>, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    return range.into_par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    range.map(f).collect()
+}
+
+/// Maps `f` over `range` and folds outputs with `reduce`. Returns `None` if `range` is empty.
+///
+/// Unlike `map_range` outputs are never held in memory all at once, which matters when
+/// they are large (for ex, lazy ciphertext products). `reduce` must be associative.
+pub(crate) fn map_reduce_range<T, F, R>(range: Range<usize>, f: F, reduce: R) -> Option<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Send + Sync,
+    R: Fn(T, T) -> T + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    return range.into_par_iter().map(f).reduce_with(reduce);
+    #[cfg(not(feature = "parallel"))]
+    range.map(f).reduce(reduce)
+}
+
 pub fn store_values(values: &[u64], file_name: &str) {
     let mut buf = vec![0u8; values.len() * 8];
     LittleEndian::write_u64_into(&values, &mut buf);
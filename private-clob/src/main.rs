@@ -5,6 +5,11 @@ use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::Read;
 
+/// Statistical security parameter of circuit privacy. Smudging noise exceeds noise of a fill by this
+/// many bits, so that noise of the delivered fill is statistically independent (up to 2^-40) of the
+/// matching computation.
+const STATISTICAL_SECURITY_BITS: usize = 40;
+
 #[derive(Serialize, Deserialize, Debug)]
 struct Orders {
     pair: String,
@@ -23,19 +28,6 @@ fn main() {
 
     let mut rng = thread_rng();
 
-    let mut params = BfvParameters::new(&[60; 10], t, slots);
-
-    // P - 180 bits
-    params.enable_hybrid_key_switching(&[60; 3]);
-
-    // generate secret key
-    let sk = SecretKey::random_with_params(&params, &mut rng);
-
-    // Create evaluator to evaluate arithmetic operarions
-    let evaluator = Evaluator::new(params);
-
-    let ek = EvaluationKey::new(evaluator.params(), &sk, &[0], &[0], &[1], &mut rng);
-
     // Open and read the file containing the order
     let file_path = "order.json";
     let mut file = File::open(file_path).expect("File not found");
@@ -52,67 +44,83 @@ fn main() {
     let buy_orders_plain = order_data.buy_orders;
     let sell_orders_plain = order_data.sell_orders;
 
-    let order_len = buy_orders_plain.len();
-
-    // Preparing buy orders for encoding
-    let buy_orders_formatted_plain = buy_orders_plain
-        .iter()
-        .map(|x| {
-            let mut val = vec![0; slots];
-            val[0] = *x;
-            val
-        })
-        .collect::<Vec<Vec<u64>>>();
-
-    // Preparing sell orders for encoding
-    let sell_orders_formatted_plain = sell_orders_plain
-        .iter()
-        .map(|x| {
-            let mut val = vec![0; slots];
-            val[0] = *x;
-            val
-        })
-        .collect::<Vec<Vec<u64>>>();
-
-    // encoding buy orders
-    let encoded_buy_orders: Vec<Plaintext> = buy_orders_formatted_plain
-        .iter()
-        .map(|x| evaluator.plaintext_encode(&x, Encoding::default()))
-        .collect::<Vec<Plaintext>>();
-
-    // encoding sell orders
-    let encoded_sell_orders: Vec<Plaintext> = sell_orders_formatted_plain
-        .iter()
-        .map(|x| evaluator.plaintext_encode(&x, Encoding::default()))
-        .collect::<Vec<Plaintext>>();
+    // orders of a side are packed into a single ciphertext, which holds as many orders as slots in
+    // a row
+    let max_orders = slots / 2;
+    for (side, orders) in [("buy", &buy_orders_plain), ("sell", &sell_orders_plain)] {
+        if orders.is_empty() || orders.len() > max_orders {
+            eprintln!(
+                "{} has {} {} orders, but 1 to {} orders per side are supported",
+                file_path,
+                orders.len(),
+                side,
+                max_orders
+            );
+            std::process::exit(1);
+        }
+    }
 
-    // encrypting buy orders
-    let encrypted_buy_orders: Vec<Ciphertext> = encoded_buy_orders
-        .iter()
-        .map(|x| evaluator.encrypt(&sk, &x, &mut rng))
-        .collect::<Vec<Ciphertext>>();
+    // Clients submit one order each in slot 0. Matcher packs orders into a single ciphertext per
+    // side, sums them from the packed ciphertext and extracts them back one at a time while filling
+    // orders.
+    let order_len = std::cmp::max(buy_orders_plain.len(), sell_orders_plain.len());
+    let extract_requirements = (0..order_len).fold(planner::Requirements::default(), |r, i| {
+        r.and(&packing::extract_slot_requirements(i, slots))
+    });
+
+    // generate only the keys matching circuit needs
+    let planner = planner::Planner::new(
+        packing::pack_requirements(order_len)
+            .then(&extract_requirements.and(&packing::sum_slots_requirements(order_len)))
+            .then(&univariate_less_than_requirements()),
+    );
 
-    // encrypting sell orders
-    let encrypted_sell_orders: Vec<Ciphertext> = encoded_sell_orders
-        .iter()
-        .map(|x| evaluator.encrypt(&sk, &x, &mut rng))
-        .collect::<Vec<Ciphertext>>();
+    // TOY SETUP, NOT SECURE: degree is fixed to `slots` to keep the example fast. Remove
+    // `with_degree` and `insecure_for_testing` to let the builder choose a degree that achieves 128
+    // bits of security for circuit's depth.
+    let params: BfvParameters = planner
+        .recommend_parameters(SecurityLevel::Bits128, t)
+        .with_degree(slots)
+        .insecure_for_testing(true)
+        .build();
 
-    // summing up buy order value
-    let sum_buy_orders = encrypted_buy_orders
-        .iter()
-        .skip(1)
-        .fold(encrypted_buy_orders[0].clone(), |acc, x| {
-            evaluator.add(&acc, &x)
-        });
+    // generate secret key
+    let sk = SecretKey::random_with_params(&params, &mut rng);
 
-    // summing up sell order value
-    let sum_sell_orders = encrypted_sell_orders
-        .iter()
-        .skip(1)
-        .fold(encrypted_sell_orders[0].clone(), |acc, x| {
-            evaluator.add(&acc, &x)
-        });
+    // Create evaluator to evaluate arithmetic operarions. Evaluator drops levels
+    // after multiplications as long as noise budget allows
+    let mut evaluator = Evaluator::new(params);
+    evaluator.enable_auto_mod_switching();
+
+    let ek = planner
+        .evaluation_key_spec(evaluator.params(), evaluator.auto_mod_switching())
+        .generate(evaluator.params(), &sk, &mut rng);
+
+    // Clients encrypt their order in slot 0. Matcher only keeps packed orders, individual
+    // ciphertexts are dropped after packing.
+    let submit_orders = |orders: &[u64], rng: &mut _| {
+        let encrypted_orders = orders
+            .iter()
+            .map(|x| {
+                let mut val = vec![0; slots];
+                val[0] = *x;
+                evaluator.encrypt(
+                    &sk,
+                    &evaluator.plaintext_encode(&val, Encoding::default()),
+                    rng,
+                )
+            })
+            .collect::<Vec<Ciphertext>>();
+        packing::pack(&evaluator, &encrypted_orders, &ek)
+    };
+    let packed_buy_orders = submit_orders(&buy_orders_plain, &mut rng);
+    let packed_sell_orders = submit_orders(&sell_orders_plain, &mut rng);
+
+    // summing up order values in slot 0
+    let sum_buy_orders =
+        packing::sum_slots(&evaluator, &packed_buy_orders, buy_orders_plain.len(), &ek);
+    let sum_sell_orders =
+        packing::sum_slots(&evaluator, &packed_sell_orders, sell_orders_plain.len(), &ek);
 
     let is_buy_sum_less_encrypted =
         univariate_less_than(&evaluator, &sum_buy_orders, &sum_sell_orders, &ek, &sk);
@@ -149,7 +157,8 @@ fn main() {
 
     println!("Trying to fill Buy orders !!");
 
-    for (index, order) in encrypted_buy_orders.iter().enumerate() {
+    for index in 0..buy_orders_plain.len() {
+        let order = &packing::extract_slot(&evaluator, &packed_buy_orders, index, &ek);
         // let filled_order = univariate_less_than(&evaluator, order, &sum_sell_orders_temp, &ek, &sk);
         // is order < sum_sell_orders_temp
         let is_less_encrypted =
@@ -178,7 +187,8 @@ fn main() {
 
     println!("Now trying to fill Sell orders !!");
 
-    for (index, order) in encrypted_sell_orders.iter().enumerate() {
+    for index in 0..sell_orders_plain.len() {
+        let order = &packing::extract_slot(&evaluator, &packed_sell_orders, index, &ek);
         let is_less_encrypted =
             univariate_less_than(&evaluator, order, &sum_buy_orders_temp, &ek, &sk); // passing sk just to keep check on noise not to decrypt the order
         let is_less_plain = evaluator.plaintext_decode(
@@ -203,14 +213,44 @@ fn main() {
         println!("Filled {:?}th Buy Order  ", index);
     }
 
+    // Each trader keeps their secret key and only hands out public key. Fills are switched from the
+    // matching key to trader's key, so that each trader can only decrypt their own fill.
+    let deliver_fill = |fill: &Ciphertext, rng: &mut _| {
+        let trader_sk = SecretKey::random_with_params(evaluator.params(), rng);
+        let trader_pk = PublicKey::new(evaluator.params(), &trader_sk, rng);
+
+        let ksk = KeySwitchingKey::new_with_public_key(
+            evaluator.params(),
+            &sk,
+            &trader_pk,
+            fill.level(),
+            rng,
+        );
+        let trader_fill = evaluator.switch_key(fill, &ksk);
+
+        // hide noise of the matching computation, which depends on other traders' orders. Smudging
+        // noise must exceed noise of the fill, thus fill with unknown noise is never delivered.
+        let noise = trader_fill
+            .noise_estimate()
+            .expect("Noise of fill is unknown, refusing to deliver it");
+        let smudging_bits = noise + STATISTICAL_SECURITY_BITS;
+        let trader_fill = evaluator.sanitize(&trader_fill, &trader_pk, smudging_bits, rng);
+
+        // trader decrypts their fill
+        evaluator.plaintext_decode(
+            &evaluator.decrypt(&trader_sk, &trader_fill),
+            Encoding::default(),
+        )[0]
+    };
+
     let buy_orders_filled_plain = buy_orders_filling_encrypted
         .iter()
-        .map(|x| evaluator.plaintext_decode(&evaluator.decrypt(&sk, &x), Encoding::default())[0])
+        .map(|x| deliver_fill(x, &mut rng))
         .collect::<Vec<u64>>();
 
     let sell_orders_filled_plain = sell_orders_filling_encrypted
         .iter()
-        .map(|x| evaluator.plaintext_decode(&evaluator.decrypt(&sk, &x), Encoding::default())[0])
+        .map(|x| deliver_fill(x, &mut rng))
         .collect::<Vec<u64>>();
 
     println!("Buy/Sell orders which could be filled are mentioned with their order value rest which can't be filled are mentioned with value 0");